@@ -0,0 +1,13 @@
+#![no_main]
+
+use golfscript_rs::formatter::format;
+use golfscript_rs::parse::significant_tokens;
+use libfuzzer_sys::fuzz_target;
+
+// Formatting must keep the tokens, and formatting again changes nothing.
+fuzz_target!(|code: &[u8]| {
+    if let Ok(formatted) = format(code) {
+        assert_eq!(significant_tokens(&formatted, true), significant_tokens(code, true));
+        assert_eq!(format(&formatted).as_deref(), Ok(&formatted[..]));
+    }
+});
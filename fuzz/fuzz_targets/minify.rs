@@ -0,0 +1,13 @@
+#![no_main]
+
+use golfscript_rs::minify::minify;
+use golfscript_rs::parse::significant_tokens;
+use libfuzzer_sys::fuzz_target;
+
+// Minifying must keep the tokens, other than comments, and never get longer.
+fuzz_target!(|code: &[u8]| {
+    if let Ok(minified) = minify(code) {
+        assert_eq!(significant_tokens(&minified, false), significant_tokens(code, false));
+        assert!(minified.len() <= code.len());
+    }
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+use golfscript_rs::parse::{diagnose, parse_code, snippet, Location};
+use libfuzzer_sys::fuzz_target;
+
+// Tokenizing, and reporting where tokenizing stopped, must never panic.
+fuzz_target!(|code: &[u8]| {
+    if let Ok((rest, _)) = parse_code(code) {
+        if !rest.is_empty() {
+            let (at, _) = diagnose(rest);
+            let offset = code.len() - at.len();
+            Location::new(code, offset);
+            snippet(code, offset, 1);
+        }
+    }
+});
@@ -0,0 +1,36 @@
+#![no_main]
+
+use golfscript_rs::error::GsError;
+use golfscript_rs::limits::Limits;
+use golfscript_rs::value::Gval;
+use golfscript_rs::Gs;
+use libfuzzer_sys::fuzz_target;
+use std::time::Duration;
+
+// Code and input are separated by the first NUL byte. Step, time and size
+// limits keep runs short, and a low depth limit fits the unoptimized build's
+// stack; errors are fine, panics are not, even those caught as internal
+// errors.
+fuzz_target!(|data: &[u8]| {
+    let (code, input) = match data.iter().position(|&b| b == 0) {
+        Some(i) => (&data[..i], &data[i + 1..]),
+        None => (data, &[][..]),
+    };
+    let mut gs = Gs::builder()
+        .extensions(true)
+        .max_steps(10_000)
+        .timeout(Duration::from_secs(1))
+        .max_depth(200)
+        .limits(Limits {
+            array_len: Some(100_000),
+            string_len: Some(100_000),
+            int_bits: Some(100_000),
+        })
+        .input(Box::new(std::io::empty()))
+        .output(Box::new(std::io::sink()))
+        .build();
+    gs.stack.push(Gval::Str(input.to_vec()));
+    if let Err(GsError::Internal(e)) = gs.run(code) {
+        panic!("{}", e);
+    }
+});
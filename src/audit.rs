@@ -0,0 +1,57 @@
+//! An audit trail of everything a run does outside the interpreter: output,
+//! input, random draws, the clock, files, the environment and commands,
+//! including those the permissions refused. Each entry names the innermost
+//! running token that's in the program, so a `print` inside `puts` is
+//! charged to the `puts`.
+
+use crate::parse::Location;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+pub struct Entry {
+    /// The program offset of the responsible token, if there is one; output
+    /// from code run after the program, like implicit output, has none.
+    pub offset: Option<usize>,
+    pub op: &'static str,
+    /// What was done, as JSON object fields.
+    pub detail: Value,
+}
+
+#[derive(Default)]
+pub struct Audit {
+    pub entries: Vec<Entry>,
+    /// The audited program, once it has started.
+    pub(crate) program: Option<Vec<u8>>,
+    /// Whether the outermost run in progress is the audited program.
+    pub(crate) running: bool,
+    /// The program offset of the running token at each depth.
+    pub(crate) positions: Vec<Option<usize>>,
+    /// Where code that has run starts in the program.
+    pub(crate) bases: HashMap<Vec<u8>, Option<usize>>,
+}
+
+impl Audit {
+    pub(crate) fn log(&mut self, op: &'static str, detail: Value) {
+        let offset = self.positions.iter().rev().find_map(|&p| p);
+        self.entries.push(Entry { offset, op, detail });
+    }
+
+    /// One JSON object per line, with the line and column of each entry's
+    /// token in `program`.
+    pub fn to_json_lines(&self, program: &[u8]) -> String {
+        let mut lines = vec![];
+        for entry in &self.entries {
+            let mut line = json!({ "op": entry.op, "offset": entry.offset });
+            if let Some(at) = entry.offset {
+                let loc = Location::new(program, at);
+                line["line"] = loc.line.into();
+                line["col"] = loc.col.into();
+            }
+            if let (Some(line), Some(detail)) = (line.as_object_mut(), entry.detail.as_object()) {
+                line.extend(detail.clone());
+            }
+            lines.push(line.to_string());
+        }
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,319 @@
+//! What the interpreter knows about its builtins besides how to run them.
+
+use crate::value::Gval;
+use crate::Gs;
+
+/// A builtin operator or word.
+pub struct Builtin {
+    pub name: &'static str,
+    /// How many values it pops, at least.
+    pub arity: usize,
+    /// A one-line description.
+    pub doc: &'static str,
+    /// What it does for each combination of operand types, if that varies.
+    pub overloads: &'static [Overload],
+    /// The first dialect that has it.
+    pub dialect: Dialect,
+}
+
+/// A set of builtins, and the semantics that go with them (`--dialect`).
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Dialect {
+    /// The reference language's builtins, comparing mixed types as the
+    /// reference interpreter does, like `--compat`.
+    Classic,
+    /// Every builtin, with those in [`crate::ext`], like `--extensions`.
+    Extended,
+}
+
+impl Dialect {
+    /// Whether `b` is a builtin in this dialect.
+    pub fn has(self, b: &Builtin) -> bool {
+        b.dialect <= self
+    }
+
+    /// The builtins in this dialect.
+    pub fn builtins(self) -> impl Iterator<Item = &'static Builtin> {
+        BUILTINS.iter().filter(move |b| self.has(b))
+    }
+
+    /// Whether the dialect recognizes the builtins in [`crate::ext`].
+    pub fn extensions(self) -> bool {
+        self >= Dialect::Extended
+    }
+
+    /// Whether the dialect coerces mixed-type comparisons like the
+    /// reference interpreter.
+    pub fn compat(self) -> bool {
+        self == Dialect::Classic
+    }
+}
+
+/// What an operator does for some operand types.
+pub struct Overload {
+    /// The types, deepest first: `int`, `arr`, `str`, `blk`, `seq` for any
+    /// of the last three, or `any`.
+    pub operands: &'static str,
+    pub doc: &'static str,
+    /// Code that shows it.
+    pub example: &'static str,
+}
+
+const fn builtin(name: &'static str, arity: usize, doc: &'static str) -> Builtin {
+    Builtin {
+        name,
+        arity,
+        doc,
+        overloads: &[],
+        dialect: Dialect::Classic,
+    }
+}
+
+const fn extension(name: &'static str, arity: usize, doc: &'static str) -> Builtin {
+    Builtin {
+        name,
+        arity,
+        doc,
+        overloads: &[],
+        dialect: Dialect::Extended,
+    }
+}
+
+const fn operator(
+    name: &'static str,
+    arity: usize,
+    doc: &'static str,
+    overloads: &'static [Overload],
+) -> Builtin {
+    Builtin {
+        name,
+        arity,
+        doc,
+        overloads,
+        dialect: Dialect::Classic,
+    }
+}
+
+const fn overload(operands: &'static str, doc: &'static str, example: &'static str) -> Overload {
+    Overload {
+        operands,
+        doc,
+        example,
+    }
+}
+
+const SETWISE: &str = "Otherwise, coerce to the higher type (int < arr < str < blk), then work setwise, keeping first occurrences in order.";
+
+pub const BUILTINS: &[Builtin] = &[
+    operator("~", 1, "Evaluate a string or block, dump an array onto the stack, or bitwise-not an integer.", &[
+        overload("int", "Bitwise not.", "5~"),
+        overload("arr", "Push the elements.", "[1 2 3]~"),
+        overload("str", "Run it as code.", "\"1 2+\"~"),
+        overload("blk", "Run it.", "{1 2+}~"),
+    ]),
+    operator("`", 1, "Push the source form of a value, like Ruby's `inspect`.", &[
+        overload("any", "The source form.", "[1 \"a\" {b}]`"),
+    ]),
+    operator("!", 1, "1 if the value is false (0, \"\", [] or {}), else 0.", &[
+        overload("any", "1 for 0, \"\", [] and {}, else 0.", "[[] 0 \"a\" 1]{!}%"),
+    ]),
+    builtin("@", 3, "Rotate the top three values: `a b c` becomes `b c a`."),
+    operator("$", 1, "`n $` copies the nth value below the top; sorts a sequence; `seq {key} $` sorts by a key.", &[
+        overload("int", "Copy the value n below the top, counting from 0.", "1 2 3 1$"),
+        overload("seq", "Sort.", "[3 1 2]$ \"cab\"$"),
+        overload("seq blk", "Sort by the key the block computes.", "[\"bb\" \"a\" \"ccc\"]{,}$"),
+    ]),
+    operator("+", 2, "Add integers, or concatenate, coercing to the higher type.", &[
+        overload("int int", "Add.", "1 2+"),
+        overload("any any", "Otherwise, coerce to the higher type (int < arr < str < blk), then concatenate.", "[1 2][3]+ 1[2]+ \"a\"{b}+"),
+    ]),
+    operator("-", 2, "Subtract integers, or remove the second sequence's elements from the first.", &[
+        overload("int int", "Subtract.", "5 3-"),
+        overload("any any", "Otherwise, coerce to the higher type (int < arr < str < blk), then remove the second's elements from the first.", "[1 2 3 2][2]- \"hello\"\"l\"-"),
+    ]),
+    operator("*", 2, "Multiply; repeat a sequence; join with a separator; fold with a block; run a block n times.", &[
+        overload("int int", "Multiply.", "6 7*"),
+        overload("seq int", "Repeat; either order.", "[1 2]3* \"ab\"3*"),
+        overload("arr seq", "Join the elements with a separator.", "[1 2 3]\",\"*"),
+        overload("str str", "Join the first's characters with the second.", "\"abc\"\",\"*"),
+        overload("seq blk", "Fold; either order.", "[1 2 3 4]{+}*"),
+        overload("int blk", "Run the block n times; either order.", "1 5{2*}*"),
+    ]),
+    operator("/", 2, "Divide; split on a separator; chunk into size n; run a block on each element; unfold.", &[
+        overload("int int", "Divide, rounding down.", "7 2/ -7 2/"),
+        overload("seq seq", "Split on a separator.", "\"a,b,,c\"\",\"/"),
+        overload("seq int", "Chunk into pieces of n; either order.", "[1 2 3 4 5]2/"),
+        overload("seq blk", "Run the block on each element; either order.", "[1 2 3]{.}/"),
+        overload("blk blk", "Unfold: while the first block leaves a true value, collect the top and run the second.", "1{10<}{2*}/"),
+    ]),
+    operator("%", 2, "Modulo; map with a block; split, dropping empty parts; take every nth element.", &[
+        overload("int int", "Modulo, with the sign of the divisor.", "7 3% -7 3%"),
+        overload("seq seq", "Split on a separator, dropping empty parts.", "\"a,b,,c\"\",\"%"),
+        overload("seq blk", "Map; either order.", "[1 2 3]{2*}%"),
+        overload("seq int", "Every nth element, from the end if n is negative; either order.", "[1 2 3 4 5]2% \"abc\"-1%"),
+    ]),
+    operator("|", 2, "Bitwise or, or setwise union.", &[
+        overload("int int", "Bitwise or.", "5 3|"),
+        overload("any any", SETWISE, "[1 2][2 3]|"),
+    ]),
+    operator("&", 2, "Bitwise and, or setwise intersection.", &[
+        overload("int int", "Bitwise and.", "5 3&"),
+        overload("any any", SETWISE, "[1 2][2 3]&"),
+    ]),
+    operator("^", 2, "Bitwise xor, or setwise symmetric difference.", &[
+        overload("int int", "Bitwise xor.", "5 3^"),
+        overload("any any", SETWISE, "[1 2][2 3]^"),
+    ]),
+    builtin("[", 0, "Start collecting an array."),
+    builtin("]", 0, "Collect everything pushed since the matching `[` into an array."),
+    builtin("\\", 2, "Swap the top two values."),
+    builtin(";", 1, "Discard the top value."),
+    operator("<", 2, "Less than, or the elements before an index.", &[
+        overload("seq int", "The elements before index n, counting from the end if n is negative; either order.", "[1 2 3 4]2< \"abcd\"-1<"),
+        overload("any any", "Otherwise, 1 if the first is less than the second, else 0.", "1 2< \"b\"\"a\"<"),
+    ]),
+    operator("=", 2, "Equal, or the element at an index.", &[
+        overload("seq int", "The element at index n, counting from the end if n is negative, or nothing; either order.", "[1 2 3]1= \"abc\"-1="),
+        overload("any any", "Otherwise, 1 if they're equal, else 0.", "[1 2][1 2]= \"a\"\"b\"="),
+    ]),
+    operator(">", 2, "Greater than, or the elements from an index on.", &[
+        overload("seq int", "The elements from index n on, counting from the end if n is negative; either order.", "[1 2 3 4]2> \"abcd\"-1>"),
+        overload("any any", "Otherwise, 1 if the first is greater than the second, else 0.", "2 1> \"a\"\"b\">"),
+    ]),
+    operator(",", 1, "`n ,` is `[0 1 ... n-1]`; the length of a sequence; `seq {block} ,` selects elements.", &[
+        overload("int", "The range [0 1 ... n-1].", "5,"),
+        overload("seq", "The length.", "[1 2 3], \"abcd\","),
+        overload("seq blk", "The elements the block leaves a true value for.", "[1 2 3 4]{2%},"),
+    ]),
+    builtin(".", 1, "Duplicate the top value."),
+    operator("?", 2, "Power; the index of an element, or -1; `seq {block} ?` finds the first match.", &[
+        overload("int int", "Power.", "2 10?"),
+        overload("arr any", "The index of the first equal element, or -1; either order for an int or string.", "[1 2 3]2? [1 2 3]5?"),
+        overload("str str", "The index of the first occurrence of the second, or -1.", "\"hello\"\"ll\"?"),
+        overload("str int", "The index of the first byte n, or -1; either order.", "\"abc\"98?"),
+        overload("seq blk", "The first element the block leaves a true value for; either order.", "[1 2 3 4]{2>}?"),
+    ]),
+    operator("(", 1, "Decrement, or remove the first element and push it after the rest.", &[
+        overload("int", "Decrement.", "5("),
+        overload("seq", "Remove the first element and push it after the rest.", "[1 2 3]("),
+    ]),
+    operator(")", 1, "Increment, or remove the last element and push it after the rest.", &[
+        overload("int", "Increment.", "5)"),
+        overload("seq", "Remove the last element and push it after the rest.", "[1 2 3])"),
+    ]),
+    builtin("print", 1, "Write a value to the output without a newline."),
+    builtin("rand", 1, "A random integer from 0 up to, but not including, n."),
+    builtin("do", 1, "`{body} do` runs `body` and pops a condition, repeating while it's true."),
+    builtin("while", 2, "`{cond} {body} while` runs `body` as long as `cond` leaves a true value."),
+    builtin("until", 2, "`{cond} {body} until` runs `body` as long as `cond` leaves a false value."),
+    builtin("if", 3, "`cond a b if` runs or pushes `a` if `cond` is true, else `b`."),
+    builtin("abs", 1, "The absolute value of an integer."),
+    builtin("zip", 1, "Transpose an array of rows."),
+    builtin("base", 2, "`n b base` is the digits of `n` in base `b`; `digits b base` converts them back."),
+    builtin("n", 0, "Push a newline."),
+    builtin("puts", 1, "Write a value to the output followed by a newline."),
+    builtin("p", 1, "Write the source form of a value followed by a newline."),
+    builtin("and", 2, "The first value if it's false, else the second."),
+    builtin("or", 2, "The first value if it's true, else the second."),
+    builtin("xor", 2, "1 if exactly one of the values is true, else 0."),
+    extension("read", 1, "`path read`: push the contents of a file."),
+    extension("write", 2, "`contents path write`: write a string to a file."),
+    extension("require", 1, "`path require`: run a GolfScript file, unless it has already been required."),
+    extension("getenv", 1, "`name getenv`: an environment variable's value, or \"\" if unset."),
+    extension("exec", 1, "`command exec`: run a shell command, pushing its stdout and exit status."),
+    extension("fetch", 1, "`url fetch`: the body of an HTTP or HTTPS GET of a URL."),
+    extension("match", 2, "`str re match`: the first match of a regex and its groups, or []."),
+    extension("scan", 2, "`str re scan`: every match of a regex."),
+    extension("gsub", 3, "`str re replacement gsub`: replace every match of a regex with a string or block."),
+    extension("upcase", 1, "Uppercase ASCII letters."),
+    extension("downcase", 1, "Lowercase ASCII letters."),
+    extension("strip", 1, "Remove leading and trailing ASCII whitespace."),
+    extension("words", 1, "Split on runs of ASCII whitespace."),
+    extension("throw", 1, "`value throw`: unwind to the nearest enclosing `catch`."),
+    extension("catch", 2, "`{body} {handler} catch`: run `body`, and `handler` with the thrown value if it throws."),
+    extension("time", 0, "The current Unix time in seconds."),
+    extension("nanos", 0, "A monotonic nanosecond counter, counting from interpreter startup."),
+    extension("sleep", 1, "`ms sleep`: flush output and pause for `ms` milliseconds."),
+    extension("gets", 0, "The next line of input, including its newline, or \"\" at the end."),
+    extension("flush", 0, "Flush buffered output."),
+    extension("choice", 1, "A uniformly random element of an array or string."),
+    extension("shuffle", 1, "Shuffle an array or string."),
+    extension("pp", 1, "Write a value pretty-printed, one element to a line if it's long, and a newline."),
+    extension("debug", 0, "Print the whole stack to stderr, leaving it untouched."),
+    extension("breakpoint", 0, "Pause in the debugger under `debug`; otherwise do nothing."),
+    extension("local", 2, "`{body} names local`: run `body`, then restore the named variables."),
+    extension("gcd", 2, "The greatest common divisor."),
+    extension("lcm", 2, "The least common multiple."),
+    extension("sqrt", 1, "The integer square root, rounded down."),
+    extension("prime", 1, "1 if the integer is prime, else 0."),
+    extension("sortwith", 2, "`seq {comparator} sortwith`: sort with a block that leaves a negative, zero or positive integer."),
+    extension("zipstrict", 1, "Like `zip`, but fails on rows of differing lengths."),
+    extension("zippad", 2, "`rows fill zippad`: like `zip`, but first pads shorter rows with `fill`."),
+    extension("uniq", 1, "Remove duplicates, keeping first occurrences."),
+    extension("squeeze", 1, "Collapse runs of equal elements."),
+    extension("mapi", 2, "`seq {block} mapi`: map with the index pushed above each element."),
+    extension("cross", 2, "`a b cross`: the cartesian product, as an array of `[x y]` pairs."),
+    extension("perms", 1, "All orderings of an array or string."),
+    extension("combos", 2, "`seq k combos`: all `k`-element subsequences, in order."),
+    extension("printf", 2, "`[args] fmt printf`: format the arguments like C's `printf`."),
+    extension("base64", 1, "Encode a string as padded base64."),
+    extension("unbase64", 1, "Decode base64."),
+    extension("hex", 1, "Encode a string as lowercase hex."),
+    extension("unhex", 1, "Decode hex of either case."),
+    extension("deflate", 1, "Compress a string to the zlib format."),
+    extension("inflate", 1, "Decompress zlib data."),
+    extension("md5", 1, "The 16 raw MD5 digest bytes; follow with `hex` for the usual form."),
+    extension("sha256", 1, "The 32 raw SHA-256 digest bytes; follow with `hex` for the usual form."),
+    extension("powmod", 3, "`b e m powmod`: `b` to the `e` modulo `m`."),
+    extension("modinv", 2, "`a m modinv`: the `x` with `a*x` congruent to 1 modulo `m`."),
+    extension("shl", 2, "`n k shl`: shift `n` left by `k` bits."),
+    extension("shr", 2, "`n k shr`: shift `n` right by `k` bits."),
+];
+
+pub fn lookup(name: &[u8]) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|b| b.name.as_bytes() == name)
+}
+
+/// How many values `name` will pop, given whether a block is on top of the
+/// stack: `,` and `$` take an extra operand below a block.
+pub fn arity(name: &[u8], block_on_top: bool) -> Option<usize> {
+    match name {
+        b"," | b"$" if block_on_top => Some(2),
+        _ => lookup(name).map(|b| b.arity),
+    }
+}
+
+/// The reference for a builtin: what it does and, for an operator, what it
+/// does for each combination of operand types, with the result of running
+/// each example.
+pub fn reference(name: &str) -> Option<String> {
+    let b = lookup(name.as_bytes())?;
+    let mut lines = vec![format!("{} (pops {})", b.name, b.arity), b.doc.to_string()];
+    for o in b.overloads {
+        lines.push(String::new());
+        lines.push(format!("{:<9} {}", o.operands, o.doc));
+        lines.push(format!(
+            "{:<9} {}  gives  {}",
+            "",
+            o.example,
+            run_example(o.example)
+        ));
+    }
+    Some(lines.join("\n"))
+}
+
+/// The stack `code` leaves, as source, or the error it fails with.
+fn run_example(code: &str) -> String {
+    let mut gs = Gs::builder()
+        .extensions(true)
+        .input(Box::new(std::io::empty()))
+        .output(Box::new(std::io::sink()))
+        .build();
+    match gs.run(code.as_bytes()) {
+        Ok(()) => {
+            let stack: Vec<_> = gs.stack.into_iter().map(Gval::inspect).collect();
+            String::from_utf8_lossy(&stack.join(&b' ')).into_owned()
+        }
+        Err(e) => format!("error: {}", e),
+    }
+}
@@ -1,8 +1,9 @@
 use crate::util::to_byte;
 use crate::value::Gval;
 use num::BigInt;
+use std::cmp::Ordering;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Coerced {
     Ints(BigInt, BigInt),
     Arrs(Vec<Gval>, Vec<Gval>),
@@ -19,6 +20,53 @@ impl Coerced {
             Coerced::Blks(a, _) => Gval::Blk(a),
         }
     }
+
+    pub fn pair(self) -> (Gval, Gval) {
+        match self {
+            Coerced::Ints(a, b) => (Gval::Int(a), Gval::Int(b)),
+            Coerced::Arrs(a, b) => (Gval::Arr(a), Gval::Arr(b)),
+            Coerced::Strs(a, b) => (Gval::Str(a), Gval::Str(b)),
+            Coerced::Blks(a, b) => (Gval::Blk(a), Gval::Blk(b)),
+        }
+    }
+
+    /// Concatenate, or add integers: what `+` does.
+    pub fn concat(self) -> Gval {
+        match self {
+            Coerced::Ints(x, y) => Gval::Int(x + y),
+            Coerced::Arrs(mut x, y) => {
+                x.extend(y);
+                Gval::Arr(x)
+            }
+            Coerced::Strs(mut x, y) => {
+                x.extend(y);
+                Gval::Str(x)
+            }
+            Coerced::Blks(mut x, y) => {
+                x.push(b' ');
+                x.extend(y);
+                Gval::Blk(x)
+            }
+        }
+    }
+
+    /// About how much work [`concat`](Self::concat) does: the bytes of
+    /// strings, the values of arrays, the words of integers.
+    pub fn size(&self) -> usize {
+        match self {
+            Coerced::Ints(x, y) => (x.bits().max(y.bits()) / 64) as usize,
+            Coerced::Arrs(x, y) => x.iter().chain(y).map(|v| 1 + v.weight()).sum(),
+            Coerced::Strs(x, y) | Coerced::Blks(x, y) => x.len() + y.len(),
+        }
+    }
+
+    pub fn cmp(&self) -> Ordering {
+        match self {
+            Coerced::Ints(a, b) => a.cmp(b),
+            Coerced::Arrs(a, b) => a.cmp(b),
+            Coerced::Strs(a, b) | Coerced::Blks(a, b) => a.cmp(b),
+        }
+    }
 }
 
 pub fn flatten_append(bytes: &mut Vec<u8>, val: Gval) {
@@ -0,0 +1,239 @@
+//! An interactive debugger: breakpoints by program offset or variable name,
+//! single-stepping, stepping over blocks, and stack and variable inspection.
+//! With `--extensions`, the `breakpoint` builtin stops just after itself.
+//! It talks on stderr, leaving stdout to the program.
+
+use crate::error::{GsError, GsResult};
+use crate::highlight::ansi_range;
+use crate::hook::{Hook, Step};
+use crate::parse::{snippet_with, Location};
+use crate::util::preview;
+use crate::Gs;
+use std::io::BufRead;
+
+const HELP: &str = "\
+s, step           run one token, stopping inside blocks
+n, next           run one token, running any blocks it runs to the end
+c, continue       run to the next breakpoint
+b, break OFFSET   stop at the token at byte OFFSET of the program
+b, break NAME     stop where NAME is run or assigned
+b, break          list breakpoints
+d, delete N       remove breakpoint N, or all of them without N
+st, stack         show the stack, top last
+v, vars           show all variables
+p, print NAME     show one variable
+w, where          show where execution stopped
+q, quit           stop the program
+h, help           show this help";
+
+enum Breakpoint {
+    Offset(usize),
+    Name(Vec<u8>),
+}
+
+impl Breakpoint {
+    fn hits(&self, step: &Step) -> bool {
+        match self {
+            Breakpoint::Offset(at) => step.program_offset == Some(*at),
+            Breakpoint::Name(name) => step.token == name || step.assigns == Some(name),
+        }
+    }
+}
+
+enum Mode {
+    Step,
+    /// Stop at the next token no deeper than this.
+    Next(usize),
+    Continue,
+}
+
+pub struct Debugger {
+    program: Vec<u8>,
+    commands: Box<dyn BufRead>,
+    breakpoints: Vec<Breakpoint>,
+    mode: Mode,
+    /// Whether to color code with ANSI escapes.
+    highlight: bool,
+}
+
+/// How many characters of a value to show.
+const SHOWN: usize = 60;
+
+impl Debugger {
+    /// A debugger for `program`, reading commands from `commands`. It stops
+    /// before the first token.
+    pub fn new(program: &[u8], commands: Box<dyn BufRead>) -> Debugger {
+        Debugger {
+            program: program.to_vec(),
+            commands,
+            breakpoints: vec![],
+            mode: Mode::Step,
+            highlight: false,
+        }
+    }
+
+    /// Color the code it shows, for a terminal.
+    pub fn highlight(mut self, on: bool) -> Debugger {
+        self.highlight = on;
+        self
+    }
+
+    /// The line of `code` at `offset`, with `len` bytes underlined.
+    fn snippet(&self, code: &[u8], offset: usize, len: usize) -> String {
+        snippet_with(code, offset, len, |start, end| match self.highlight {
+            true => ansi_range(code, start, end),
+            false => String::from_utf8_lossy(&code[start..end]).into_owned(),
+        })
+    }
+
+    fn stops(&self, step: &Step) -> bool {
+        if step.token.iter().all(u8::is_ascii_whitespace) || step.token.starts_with(b"#") {
+            return false;
+        }
+        let stepping = match self.mode {
+            Mode::Step => true,
+            Mode::Next(depth) => step.depth <= depth,
+            Mode::Continue => false,
+        };
+        stepping || self.breakpoints.iter().any(|b| b.hits(step))
+    }
+
+    fn show_step(&self, step: &Step) {
+        let token = String::from_utf8_lossy(step.token);
+        match step.program_offset {
+            Some(at) => {
+                let loc = Location::new(&self.program, at);
+                eprintln!(
+                    "at `{}`, line {}, col {} (offset {})",
+                    token, loc.line, loc.col, at
+                );
+                eprintln!("{}", self.snippet(&self.program, at, step.token.len()));
+            }
+            None => {
+                eprintln!("at `{}` in code run by `~`", token);
+                eprintln!("{}", self.snippet(step.code, step.offset, step.token.len()));
+            }
+        }
+    }
+
+    fn show_stack(&self, gs: &Gs) {
+        if gs.stack.is_empty() {
+            eprintln!("(empty stack)");
+        }
+        let depth = gs.stack.len();
+        for (i, v) in gs.stack.iter().enumerate() {
+            eprintln!("{:>3}: {}", depth - 1 - i, preview(v, SHOWN));
+        }
+    }
+
+    fn show_vars(&self, gs: &Gs) {
+        let mut vars: Vec<_> = gs.vars().iter().collect();
+        vars.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, v) in vars {
+            eprintln!("{} = {}", String::from_utf8_lossy(name), preview(v, SHOWN));
+        }
+    }
+
+    fn add_breakpoint(&mut self, arg: &str) {
+        if arg.is_empty() {
+            if self.breakpoints.is_empty() {
+                eprintln!("no breakpoints");
+            }
+            for (i, b) in self.breakpoints.iter().enumerate() {
+                match b {
+                    Breakpoint::Offset(at) => {
+                        let loc = Location::new(&self.program, *at);
+                        eprintln!("{}: offset {} (line {}, col {})", i, at, loc.line, loc.col);
+                    }
+                    Breakpoint::Name(name) => {
+                        eprintln!("{}: `{}`", i, String::from_utf8_lossy(name))
+                    }
+                }
+            }
+            return;
+        }
+        let b = match arg.parse() {
+            Ok(at) if at < self.program.len() => Breakpoint::Offset(at),
+            Ok(_) => {
+                eprintln!("offset {} is past the end of the program", arg);
+                return;
+            }
+            Err(_) => Breakpoint::Name(arg.as_bytes().to_vec()),
+        };
+        self.breakpoints.push(b);
+        eprintln!("breakpoint {} set", self.breakpoints.len() - 1);
+    }
+
+    fn delete_breakpoint(&mut self, arg: &str) {
+        if arg.is_empty() {
+            self.breakpoints.clear();
+            eprintln!("all breakpoints deleted");
+            return;
+        }
+        match arg.parse::<usize>() {
+            Ok(i) if i < self.breakpoints.len() => {
+                self.breakpoints.remove(i);
+                eprintln!("breakpoint {} deleted", i);
+            }
+            _ => eprintln!("no breakpoint {}", arg),
+        }
+    }
+}
+
+impl Hook for Debugger {
+    fn pause(&mut self, _gs: &mut Gs) -> GsResult<()> {
+        self.mode = Mode::Step;
+        Ok(())
+    }
+
+    fn before(&mut self, gs: &mut Gs, step: &Step) -> GsResult<()> {
+        if !self.stops(step) {
+            return Ok(());
+        }
+        // Show output so far, so it's clear what the program has done.
+        gs.flush()?;
+        self.show_step(step);
+        loop {
+            eprint!("(debug) ");
+            let mut line = String::new();
+            match self.commands.read_line(&mut line) {
+                Ok(0) | Err(_) => {
+                    self.mode = Mode::Continue;
+                    self.breakpoints.clear();
+                    return Ok(());
+                }
+                Ok(_) => {}
+            }
+            let line = line.trim();
+            let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+            let arg = arg.trim();
+            match command {
+                "s" | "step" => {
+                    self.mode = Mode::Step;
+                    return Ok(());
+                }
+                "n" | "next" => {
+                    self.mode = Mode::Next(step.depth);
+                    return Ok(());
+                }
+                "c" | "continue" => {
+                    self.mode = Mode::Continue;
+                    return Ok(());
+                }
+                "b" | "break" => self.add_breakpoint(arg),
+                "d" | "delete" => self.delete_breakpoint(arg),
+                "st" | "stack" => self.show_stack(gs),
+                "v" | "vars" => self.show_vars(gs),
+                "p" | "print" => match gs.vars().get(arg.as_bytes()) {
+                    Some(v) => eprintln!("{} = {}", arg, preview(v, SHOWN)),
+                    None => eprintln!("`{}` is not defined", arg),
+                },
+                "w" | "where" => self.show_step(step),
+                "q" | "quit" => return Err(GsError::Runtime("quit in the debugger".into())),
+                "h" | "help" => eprintln!("{}", HELP),
+                "" => {}
+                _ => eprintln!("unknown command `{}`; try `help`", command),
+            }
+        }
+    }
+}
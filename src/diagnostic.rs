@@ -0,0 +1,90 @@
+//! Errors and warnings as JSON objects, one per line, for editors and judging
+//! pipelines that would otherwise have to scrape the human-oriented text.
+
+use crate::error::GsError;
+use crate::parse::Location;
+use serde_json::{json, Value};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiagnosticFormat {
+    /// Messages with the offending line underlined.
+    #[default]
+    Human,
+    /// One JSON object per diagnostic.
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The file the program came from, if it came from one.
+    pub file: Option<String>,
+    /// A byte offset into the program, if the problem can be found there.
+    pub offset: Option<usize>,
+    pub location: Option<Location>,
+    /// A stable name for the kind of problem, like `type-error`.
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(e: &GsError) -> Diagnostic {
+        let message = match e {
+            GsError::Parse(p) => p.message.clone(),
+            e => e.to_string(),
+        };
+        Diagnostic {
+            severity: Severity::Error,
+            file: None,
+            offset: None,
+            location: None,
+            code: e.code(),
+            message,
+        }
+    }
+
+    pub fn warning(code: &'static str, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            file: None,
+            offset: None,
+            location: None,
+            code,
+            message,
+        }
+    }
+
+    pub fn file(mut self, file: Option<&str>) -> Diagnostic {
+        self.file = file.map(str::to_string);
+        self
+    }
+
+    /// Place the diagnostic at `offset` in `program`.
+    pub fn at(mut self, program: &[u8], offset: Option<usize>) -> Diagnostic {
+        self.offset = offset;
+        self.location = offset.map(|offset| Location::new(program, offset));
+        self
+    }
+
+    pub fn to_json(&self) -> Value {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        json!({
+            "severity": severity,
+            "file": self.file,
+            "offset": self.offset,
+            "line": self.location.map(|l| l.line),
+            "col": self.location.map(|l| l.col),
+            "code": self.code,
+            "message": self.message,
+        })
+    }
+}
@@ -0,0 +1,163 @@
+use crate::parse::{snippet, Location};
+use crate::value::Gval;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::panic::AssertUnwindSafe;
+
+/// Code that couldn't be tokenized, and where.
+#[derive(Debug)]
+pub struct ParseError {
+    pub message: String,
+    /// A byte offset into the code.
+    pub offset: usize,
+    pub location: Location,
+    /// The offending line with the problem underlined.
+    pub snippet: String,
+}
+
+impl ParseError {
+    pub fn new(code: &[u8], offset: usize, message: &str) -> ParseError {
+        ParseError {
+            message: message.to_string(),
+            offset,
+            location: Location::new(code, offset),
+            snippet: snippet(code, offset, 1),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GsError {
+    /// The code couldn't be tokenized.
+    Parse(ParseError),
+    /// An operator needed more values than the stack had; which one, once
+    /// the error reaches the token that ran it.
+    StackUnderflow(Option<Underflow>),
+    /// An operator got operands it has no meaning for.
+    Type(TypeError),
+    /// A value raised by `throw` that no `catch` handled.
+    Thrown(Gval),
+    /// A value grew past a size limit, or the program ran too long.
+    Limit(String),
+    /// Any other failure, like division by zero.
+    Runtime(String),
+    /// A bug in the interpreter: it panicked.
+    Internal(String),
+}
+
+#[derive(Debug)]
+pub struct Underflow {
+    pub op: String,
+    /// How many values `op` pops, if it's a builtin.
+    pub needs: Option<usize>,
+    /// How many values were on the stack when it started.
+    pub has: usize,
+}
+
+#[derive(Debug)]
+pub struct TypeError {
+    /// The operator, once the error reaches the token that ran it.
+    pub op: Option<String>,
+    pub message: String,
+}
+
+pub fn type_error(message: String) -> GsError {
+    GsError::Type(TypeError { op: None, message })
+}
+
+pub type GsResult<T> = Result<T, GsError>;
+
+impl GsError {
+    /// A stable name for the kind of error, for tools to match on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            GsError::Parse(_) => "parse-error",
+            GsError::StackUnderflow(_) => "stack-underflow",
+            GsError::Type(_) => "type-error",
+            GsError::Thrown(_) => "uncaught-throw",
+            GsError::Limit(_) => "limit-exceeded",
+            GsError::Runtime(_) => "runtime-error",
+            GsError::Internal(_) => "internal-error",
+        }
+    }
+}
+
+impl fmt::Display for GsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GsError::Parse(e) => write!(
+                f,
+                "parse error at line {}, col {}: {}\n{}",
+                e.location.line, e.location.col, e.message, e.snippet
+            ),
+            GsError::StackUnderflow(None) => write!(f, "stack underflow"),
+            GsError::StackUnderflow(Some(u)) => match u.needs {
+                Some(needs) if u.has < needs => write!(
+                    f,
+                    "`{}` needs {} value{}, stack has {}",
+                    u.op,
+                    needs,
+                    if needs == 1 { "" } else { "s" },
+                    u.has
+                ),
+                _ => write!(f, "stack underflow in `{}`", u.op),
+            },
+            GsError::Type(TypeError { op: None, message }) => {
+                write!(f, "type error: {}", message)
+            }
+            GsError::Type(TypeError {
+                op: Some(op),
+                message,
+            }) => write!(f, "type error in `{}`: {}", op, message),
+            GsError::Thrown(v) => {
+                let v = String::from_utf8_lossy(&v.clone().inspect()).into_owned();
+                write!(f, "uncaught throw: {}", v)
+            }
+            GsError::Limit(msg) | GsError::Runtime(msg) => write!(f, "{}", msg),
+            GsError::Internal(msg) => write!(f, "internal error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for GsError {}
+
+thread_local! {
+    /// How many `catch_panics` calls are running, and where the last panic
+    /// they caught happened, if the panic hook is installed.
+    static CATCHING: Cell<usize> = const { Cell::new(0) };
+    static PANICKED_AT: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, turning a panic into [`GsError::Internal`].
+pub(crate) fn catch_panics(f: impl FnOnce() -> GsResult<()>) -> GsResult<()> {
+    CATCHING.with(|c| c.set(c.get() + 1));
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|c| c.set(c.get() - 1));
+    result.unwrap_or_else(|payload| {
+        let message = match (
+            payload.downcast_ref::<&str>(),
+            payload.downcast_ref::<String>(),
+        ) {
+            (Some(s), _) => s.to_string(),
+            (_, Some(s)) => s.clone(),
+            _ => "panicked".to_string(),
+        };
+        Err(GsError::Internal(match PANICKED_AT.with(|p| p.take()) {
+            Some(at) => format!("{} (at {})", message, at),
+            None => message,
+        }))
+    })
+}
+
+/// Keep panics that become [`GsError::Internal`] off stderr, noting where
+/// they happened in the error instead. Other panics are reported as before.
+pub fn install_panic_hook() {
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if CATCHING.with(Cell::get) > 0 {
+            PANICKED_AT.with(|p| p.replace(info.location().map(ToString::to_string)));
+        } else {
+            default(info)
+        }
+    }));
+}
@@ -0,0 +1,69 @@
+//! Expected output written in the program itself: each `#=> text` comment
+//! is a line the program should print, in order, so a golfed solution can
+//! carry its own test.
+
+use crate::parse::{parse_code, Gtoken};
+
+/// The lines the `#=>` comments in `program` expect, in order.
+pub fn expectations(program: &[u8]) -> Vec<Vec<u8>> {
+    fn walk(tokens: Vec<Gtoken>, out: &mut Vec<Vec<u8>>) {
+        for token in tokens {
+            match token {
+                Gtoken::Block(inner, _) => walk(inner, out),
+                Gtoken::Comment(text) => {
+                    if let Some(line) = text.strip_prefix(b"#=>") {
+                        out.push(line.strip_prefix(b" ").unwrap_or(line).to_vec());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    let mut out = vec![];
+    if let Ok((_, tokens)) = parse_code(program) {
+        walk(tokens, &mut out);
+    }
+    out
+}
+
+/// Compare `output` with the expected lines, returning a diff of them if
+/// they differ: unchanged lines start with a space, missing ones with `-`
+/// and unexpected ones with `+`.
+pub fn check(expected: &[Vec<u8>], output: &[u8]) -> Result<(), String> {
+    let actual: Vec<&[u8]> = match output.strip_suffix(b"\n").unwrap_or(output) {
+        _ if output.is_empty() => vec![],
+        output => output.split(|&b| b == b'\n').collect(),
+    };
+    let expected: Vec<&[u8]> = expected.iter().map(Vec::as_slice).collect();
+    if expected == actual {
+        return Ok(());
+    }
+    // A longest common subsequence of lines, built from the end.
+    let (n, m) = (expected.len(), actual.len());
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let show = |mark: char, line: &[u8]| format!("{} {}", mark, String::from_utf8_lossy(line));
+    let (mut i, mut j) = (0, 0);
+    let mut diff = vec![];
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            diff.push(show(' ', expected[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if j == m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            diff.push(show('-', expected[i]));
+            i += 1;
+        } else {
+            diff.push(show('+', actual[j]));
+            j += 1;
+        }
+    }
+    Err(diff.join("\n"))
+}
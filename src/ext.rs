@@ -0,0 +1,118 @@
+//! Builtins beyond the reference language. They're only recognized when
+//! extensions are enabled (`--extensions`); otherwise these names are plain
+//! unknown symbols, as in the reference interpreter. Builtins that reach
+//! outside the interpreter additionally need a permission flag, and granting
+//! that flag enables them even without `--extensions`.
+
+use crate::error::{GsError, GsResult};
+use crate::Gs;
+use std::path::PathBuf;
+
+mod arrays;
+mod clock;
+mod combinatorics;
+mod compress;
+pub(crate) mod encoding;
+mod format;
+mod fs;
+mod hash;
+mod math;
+mod net;
+mod os;
+mod random;
+mod re;
+mod scope;
+mod sort;
+mod stdio;
+mod strings;
+mod throw;
+
+pub use net::get as http_get;
+
+/// Capabilities granted on the command line. Everything is off by default.
+#[derive(Default)]
+pub struct Permissions {
+    /// The directory `read`, `write` and `require` are confined to.
+    pub fs: Option<PathBuf>,
+    /// Whether `getenv` may read environment variables.
+    pub env: bool,
+    /// Whether `exec` may run shell commands.
+    pub exec: bool,
+    /// Whether `fetch` may make HTTP requests.
+    pub net: bool,
+}
+
+pub fn permission_denied(op: &str, flag: &str) -> GsError {
+    GsError::Runtime(format!("`{}` is disabled; enable it with {}", op, flag))
+}
+
+impl Gs {
+    /// Unknown symbols do nothing, as in the reference interpreter; note
+    /// them so `run` can warn about likely typos. Whitespace is expected.
+    fn ignore(&mut self, name: &[u8]) -> GsResult<()> {
+        if !name.iter().all(u8::is_ascii_whitespace) {
+            self.ignored = true;
+        }
+        Ok(())
+    }
+
+    pub fn extension(&mut self, name: &[u8]) -> GsResult<()> {
+        let p = &self.permissions;
+        match name {
+            b"read" if self.extensions || p.fs.is_some() => self.read_file(),
+            b"write" if self.extensions || p.fs.is_some() => self.write_file(),
+            b"require" if self.extensions || p.fs.is_some() => self.require(),
+            b"getenv" if self.extensions || p.env => self.getenv(),
+            b"exec" if self.extensions || p.exec => self.exec(),
+            b"fetch" if self.extensions || p.net => self.fetch(),
+            b"pp" if self.extensions || self.pretty => self.pp(),
+            _ if !self.extensions => self.ignore(name),
+            b"match" => self.re_match(),
+            b"scan" => self.re_scan(),
+            b"gsub" => self.re_gsub(),
+            b"upcase" => self.upcase(),
+            b"downcase" => self.downcase(),
+            b"strip" => self.strip(),
+            b"words" => self.words(),
+            b"throw" => self.throw(),
+            b"catch" => self.catch(),
+            b"time" => self.time(),
+            b"nanos" => self.nanos(),
+            b"sleep" => self.sleep(),
+            b"gets" => self.gets(),
+            b"flush" => self.flush(),
+            b"choice" => self.choice(),
+            b"shuffle" => self.shuffle(),
+            b"debug" => self.debug(),
+            b"breakpoint" => self.breakpoint(),
+            b"local" => self.local(),
+            b"gcd" => self.gcd(),
+            b"lcm" => self.lcm(),
+            b"sqrt" => self.isqrt(),
+            b"prime" => self.prime(),
+            b"powmod" => self.powmod(),
+            b"modinv" => self.modinv(),
+            b"shl" => self.shl(),
+            b"shr" => self.shr(),
+            b"sortwith" => self.sortwith(),
+            b"zipstrict" => self.zipstrict(),
+            b"zippad" => self.zippad(),
+            b"uniq" => self.uniq(),
+            b"squeeze" => self.squeeze(),
+            b"mapi" => self.mapi(),
+            b"cross" => self.cross(),
+            b"perms" => self.perms(),
+            b"combos" => self.combos(),
+            b"printf" => self.printf(),
+            b"base64" => self.base64(),
+            b"unbase64" => self.unbase64(),
+            b"hex" => self.hex(),
+            b"unhex" => self.unhex(),
+            b"deflate" => self.deflate(),
+            b"inflate" => self.inflate(),
+            b"md5" => self.md5(),
+            b"sha256" => self.sha256(),
+            _ => self.ignore(name),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use crate::coerce::flatten;
+use crate::error::{GsError, GsResult};
+use crate::limits::Size;
+use crate::util::set_or;
+use crate::value::Gval;
+use crate::Gs;
+
+impl Gs {
+    /// Like `zip`, but fails on rows of differing lengths.
+    pub fn zipstrict(&mut self) -> GsResult<()> {
+        let rows = self.pop()?.unwrap_arr()?;
+        let mut expected = None;
+        for (i, row) in rows.iter().enumerate() {
+            let len = row.clone().into_arr()?.len();
+            match expected {
+                Some(n) if n != len => {
+                    return Err(GsError::Runtime(format!(
+                        "`zipstrict`: row {} has length {}, but row 0 has length {}",
+                        i, len, n
+                    )))
+                }
+                _ => expected = Some(len),
+            }
+        }
+        self.push(Gval::Arr(rows));
+        self.zip()
+    }
+
+    /// `rows fill zippad`: like `zip`, but first pads shorter rows with `fill`.
+    pub fn zippad(&mut self) -> GsResult<()> {
+        let fill = self.pop()?;
+        let mut rows = self.pop()?.unwrap_arr()?;
+        let mut lens = vec![];
+        for row in &rows {
+            lens.push(row.clone().into_arr()?.len());
+        }
+        let width = lens.iter().copied().max().unwrap_or(0);
+        for (row, len) in rows.iter_mut().zip(lens) {
+            for _ in len..width {
+                row.push(fill.clone())?;
+            }
+        }
+        self.push(Gval::Arr(rows));
+        self.zip()
+    }
+
+    /// Remove all duplicates, keeping first occurrences, like Ruby's `uniq`.
+    pub fn uniq(&mut self) -> GsResult<()> {
+        let v = self.pop()?;
+        let elems = set_or(v.clone().into_arr()?, vec![]);
+        self.push(v.rebuild(elems)?);
+        Ok(())
+    }
+
+    /// Collapse runs of equal elements, like Ruby's `squeeze`.
+    pub fn squeeze(&mut self) -> GsResult<()> {
+        let v = self.pop()?;
+        let mut elems = v.clone().into_arr()?;
+        elems.dedup();
+        self.push(v.rebuild(elems)?);
+        Ok(())
+    }
+
+    /// `seq {block} mapi`: map with the index pushed above each element.
+    pub fn mapi(&mut self) -> GsResult<()> {
+        let code = self.pop()?.unwrap_blk()?;
+        let seq = self.pop()?;
+        let is_str = matches!(seq, Gval::Str(_));
+        let mut r = vec![];
+        for (i, v) in seq.into_arr()?.into_iter().enumerate() {
+            let lb = self.stack.len();
+            self.push(v);
+            self.push(i.into());
+            self.run(&code)?;
+            r.extend(self.stack.drain(lb.min(self.stack.len())..));
+        }
+        self.push(if is_str {
+            Gval::Str(flatten(r))
+        } else {
+            Gval::Arr(r)
+        });
+        Ok(())
+    }
+
+    /// `a b cross`: the cartesian product, as an array of `[x y]` pairs.
+    pub fn cross(&mut self) -> GsResult<()> {
+        let b = self.pop()?.into_arr()?;
+        let a = self.pop()?.into_arr()?;
+        let len = a.len() as u128 * b.len() as u128;
+        self.check_size(Size::ArrayLen, len)?;
+        let mut r = vec![];
+        for x in &a {
+            for y in &b {
+                self.work(2 + x.weight() + y.weight())?;
+                r.push(Gval::Arr(vec![x.clone(), y.clone()]));
+            }
+        }
+        self.push(Gval::Arr(r));
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use crate::error::GsResult;
+use crate::value::Gval;
+use crate::Gs;
+use num::ToPrimitive;
+use serde_json::json;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+impl Gs {
+    /// Push the current Unix time in seconds.
+    pub fn time(&mut self) -> GsResult<()> {
+        let t = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        self.audit("time", || json!({ "result": t }));
+        self.push(Gval::Int(t.into()));
+        Ok(())
+    }
+
+    /// Push a monotonic nanosecond counter, counting from interpreter startup.
+    pub fn nanos(&mut self) -> GsResult<()> {
+        let t = self.started.elapsed().as_nanos();
+        self.audit("nanos", || json!({ "result": t.to_string() }));
+        self.push(Gval::Int(t.into()));
+        Ok(())
+    }
+
+    /// `ms sleep`: flush output and pause for `ms` milliseconds.
+    pub fn sleep(&mut self) -> GsResult<()> {
+        let ms = self.pop()?.unwrap_int()?;
+        self.audit("sleep", || json!({ "ms": ms.to_string() }));
+        let ms = Duration::from_millis(ms.to_u64().unwrap_or(0));
+        // Sleeping past the time limit would only find it exceeded after.
+        self.check_deadline(ms)?;
+        self.flush()?;
+        std::thread::sleep(ms);
+        Ok(())
+    }
+}
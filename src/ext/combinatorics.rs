@@ -0,0 +1,101 @@
+use crate::error::{GsError, GsResult};
+use crate::limits::Size;
+use crate::value::Gval;
+use crate::Gs;
+use num::ToPrimitive;
+
+/// The most results `perms` or `combos` will generate.
+const MAX_RESULTS: usize = 1 << 22;
+
+fn too_many(op: &str) -> GsError {
+    GsError::Runtime(format!(
+        "`{}` would generate more than {} results",
+        op, MAX_RESULTS
+    ))
+}
+
+/// Advance `ix` to the next permutation in lexicographic order.
+fn next_permutation(ix: &mut [usize]) -> bool {
+    let i = match (1..ix.len()).rev().find(|&i| ix[i - 1] < ix[i]) {
+        Some(i) => i,
+        None => return false,
+    };
+    let j = (i..ix.len())
+        .rev()
+        .find(|&j| ix[j] > ix[i - 1])
+        .unwrap_or(i);
+    ix.swap(i - 1, j);
+    ix[i..].reverse();
+    true
+}
+
+/// Advance `ix` to the next `k`-subset of `0..n` in lexicographic order.
+fn next_combination(ix: &mut [usize], n: usize) -> bool {
+    let k = ix.len();
+    let i = match (0..k).rev().find(|&i| ix[i] < n - k + i) {
+        Some(i) => i,
+        None => return false,
+    };
+    ix[i] += 1;
+    for j in i + 1..k {
+        ix[j] = ix[j - 1] + 1;
+    }
+    true
+}
+
+impl Gs {
+    /// All orderings of an array or string, by position: duplicates are kept.
+    pub fn perms(&mut self) -> GsResult<()> {
+        let seq = self.pop()?;
+        let elems = seq.clone().into_arr()?;
+        let n = elems.len();
+        (1..=n)
+            .try_fold(1usize, |acc, i| acc.checked_mul(i))
+            .filter(|&count| count <= MAX_RESULTS)
+            .ok_or_else(|| too_many("perms"))
+            .and_then(|count| self.check_size(Size::ArrayLen, count))?;
+        let mut ix: Vec<usize> = (0..n).collect();
+        let mut r = vec![];
+        loop {
+            self.work(ix.iter().map(|&i| 1 + elems[i].weight()).sum())?;
+            r.push(seq.rebuild(ix.iter().map(|&i| elems[i].clone()).collect())?);
+            if !next_permutation(&mut ix) {
+                break;
+            }
+        }
+        self.push(Gval::Arr(r));
+        Ok(())
+    }
+
+    /// `seq k combos`: all `k`-element subsequences, in order.
+    pub fn combos(&mut self) -> GsResult<()> {
+        let k = self.pop()?.unwrap_int()?;
+        let seq = self.pop()?;
+        let elems = seq.clone().into_arr()?;
+        let n = elems.len();
+        let k = match k.to_usize() {
+            Some(k) if k <= n => k,
+            _ => {
+                self.push(Gval::Arr(vec![]));
+                return Ok(());
+            }
+        };
+        // C(n, k), computed so each intermediate value is itself a binomial.
+        (0..k.min(n - k))
+            .try_fold(1usize, |acc, i| Some(acc.checked_mul(n - i)? / (i + 1)))
+            .filter(|&count| count <= MAX_RESULTS)
+            .ok_or_else(|| too_many("combos"))
+            .and_then(|count| self.check_size(Size::ArrayLen, count))?;
+        let mut ix: Vec<usize> = (0..k).collect();
+        let mut r = vec![];
+        loop {
+            self.work(ix.iter().map(|&i| 1 + elems[i].weight()).sum())?;
+            r.push(seq.rebuild(ix.iter().map(|&i| elems[i].clone()).collect())?);
+            if !next_combination(&mut ix, n) {
+                break;
+            }
+        }
+        self.push(Gval::Arr(r));
+        Ok(())
+    }
+}
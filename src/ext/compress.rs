@@ -0,0 +1,38 @@
+use crate::error::{GsError, GsResult};
+use crate::limits::Size;
+use crate::value::Gval;
+use crate::Gs;
+use flate2::read::{ZlibDecoder, ZlibEncoder};
+use flate2::Compression;
+use std::io::Read;
+
+impl Gs {
+    /// Compress a string to the zlib format at the best compression level.
+    pub fn deflate(&mut self) -> GsResult<()> {
+        let s = self.pop()?.unwrap_str()?;
+        let mut r = vec![];
+        ZlibEncoder::new(&s[..], Compression::best())
+            .read_to_end(&mut r)
+            .map_err(|e| GsError::Runtime(format!("deflate: {}", e)))?;
+        self.push(Gval::Str(r));
+        Ok(())
+    }
+
+    /// Decompress a zlib string, reading at most one byte past the string
+    /// length limit, so a small string can't inflate without bound.
+    pub fn inflate(&mut self) -> GsResult<()> {
+        let s = self.pop()?.unwrap_str()?;
+        let limit = self
+            .limits
+            .string_len
+            .map_or(u64::MAX, |max| max as u64 + 1);
+        let mut r = vec![];
+        ZlibDecoder::new(&s[..])
+            .take(limit)
+            .read_to_end(&mut r)
+            .map_err(|e| GsError::Runtime(format!("inflate: {}", e)))?;
+        self.check_size(Size::StringLen, r.len())?;
+        self.push(Gval::Str(r));
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use crate::error::{GsError, GsResult};
+use crate::limits::Size;
+use crate::value::Gval;
+use crate::Gs;
+use num::{BigInt, Integer, Signed, ToPrimitive, Zero};
+
+impl Gs {
+    /// `n alphabet base` and `str alphabet base`: like `base`, but the digits
+    /// are the bytes of `alphabet`, and the digit sequence is a string.
+    pub fn alphabet_base(&mut self) -> GsResult<()> {
+        let alphabet = self.pop()?.unwrap_str()?;
+        if alphabet.len() < 2 {
+            return Err(GsError::Runtime(
+                "base alphabet needs at least two digits".to_string(),
+            ));
+        }
+        let mut seen = [false; 256];
+        if alphabet
+            .iter()
+            .any(|&d| std::mem::replace(&mut seen[d as usize], true))
+        {
+            return Err(GsError::Runtime(
+                "base alphabet repeats a digit".to_string(),
+            ));
+        }
+        let b = BigInt::from(alphabet.len());
+        match self.pop()? {
+            Gval::Int(n) => {
+                let mut digits = vec![];
+                let mut i = n.abs();
+                loop {
+                    let (j, k) = i.div_mod_floor(&b);
+                    i = j;
+                    digits.push(alphabet[k.to_usize().unwrap_or(0)]);
+                    if i.is_zero() {
+                        break;
+                    }
+                }
+                digits.reverse();
+                self.push(Gval::Str(digits));
+            }
+            s => {
+                let mut total = BigInt::zero();
+                for c in s.unwrap_str()? {
+                    let digit = alphabet.iter().position(|&a| a == c).ok_or_else(|| {
+                        GsError::Runtime(format!("{:?} is not in the base alphabet", c as char))
+                    })?;
+                    total = total * &b + digit;
+                }
+                self.push(Gval::Int(total));
+            }
+        }
+        Ok(())
+    }
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn invalid(what: &str) -> GsError {
+    GsError::Runtime(format!("invalid {} input", what))
+}
+
+/// Standard padded base64.
+pub fn base64_encode(s: &[u8]) -> Vec<u8> {
+    let mut r = Vec::with_capacity(s.len().div_ceil(3) * 4);
+    for chunk in s.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                r.push(BASE64[(n >> (18 - 6 * i) & 63) as usize]);
+            } else {
+                r.push(b'=');
+            }
+        }
+    }
+    r
+}
+
+/// Decode base64, ignoring whitespace and accepting missing padding.
+pub fn base64_decode(s: &[u8]) -> GsResult<Vec<u8>> {
+    let mut r = vec![];
+    let (mut acc, mut bits) = (0u32, 0);
+    let body = s.iter().filter(|b| !b.is_ascii_whitespace());
+    let mut padding = false;
+    for &c in body {
+        if c == b'=' {
+            padding = true;
+            continue;
+        }
+        let v = BASE64.iter().position(|&a| a == c);
+        let v = v.filter(|_| !padding).ok_or_else(|| invalid("base64"))?;
+        acc = acc << 6 | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            r.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    if bits >= 6 {
+        return Err(invalid("base64"));
+    }
+    Ok(r)
+}
+
+impl Gs {
+    pub fn base64(&mut self) -> GsResult<()> {
+        let s = self.pop()?.unwrap_str()?;
+        let r = base64_encode(&s);
+        self.check_size(Size::StringLen, r.len())?;
+        self.push(Gval::Str(r));
+        Ok(())
+    }
+
+    pub fn unbase64(&mut self) -> GsResult<()> {
+        let s = self.pop()?.unwrap_str()?;
+        self.push(Gval::Str(base64_decode(&s)?));
+        Ok(())
+    }
+
+    /// Lowercase hex, two digits per byte.
+    pub fn hex(&mut self) -> GsResult<()> {
+        let s = self.pop()?.unwrap_str()?;
+        self.check_size(Size::StringLen, s.len() as u64 * 2)?;
+        let r = s.iter().flat_map(|b| format!("{:02x}", b).into_bytes());
+        self.push(Gval::Str(r.collect()));
+        Ok(())
+    }
+
+    /// Decode hex of either case, ignoring whitespace.
+    pub fn unhex(&mut self) -> GsResult<()> {
+        let s = self.pop()?.unwrap_str()?;
+        let digits = s
+            .iter()
+            .filter(|b| !b.is_ascii_whitespace())
+            .map(|&b| (b as char).to_digit(16).ok_or_else(|| invalid("hex")))
+            .collect::<GsResult<Vec<u32>>>()?;
+        if digits.len() % 2 != 0 {
+            return Err(invalid("hex"));
+        }
+        let r = digits.chunks(2).map(|d| (d[0] << 4 | d[1]) as u8);
+        self.push(Gval::Str(r.collect()));
+        Ok(())
+    }
+}
@@ -0,0 +1,165 @@
+use crate::error::{GsError, GsResult};
+use crate::limits::Size;
+use crate::value::Gval;
+use crate::Gs;
+use num::{BigInt, Signed, ToPrimitive};
+
+fn format_error(msg: &str) -> GsError {
+    GsError::Runtime(format!("printf: {}", msg))
+}
+
+/// One `%[flags][width][.precision]conversion` directive.
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    fn pad(&self, gs: &mut Gs, body: Vec<u8>, sign: &[u8]) -> GsResult<Vec<u8>> {
+        let fill = self.width.saturating_sub(sign.len() + body.len());
+        gs.check_size(Size::StringLen, sign.len() + body.len() + fill)?;
+        gs.work(fill)?;
+        let mut r = vec![];
+        if self.left {
+            r.extend_from_slice(sign);
+            r.extend(body);
+            r.resize(r.len() + fill, b' ');
+        } else if self.zero {
+            r.extend_from_slice(sign);
+            r.resize(r.len() + fill, b'0');
+            r.extend(body);
+        } else {
+            r.resize(fill, b' ');
+            r.extend_from_slice(sign);
+            r.extend(body);
+        }
+        Ok(r)
+    }
+
+    fn int(&self, gs: &mut Gs, n: &BigInt, radix: u32, upper: bool) -> GsResult<Vec<u8>> {
+        let mut digits = n.abs().to_str_radix(radix).into_bytes();
+        if upper {
+            digits.make_ascii_uppercase();
+        }
+        if let Some(p) = self.precision {
+            gs.check_size(Size::StringLen, p)?;
+            gs.work(p)?;
+            let mut padded = vec![b'0'; p.saturating_sub(digits.len())];
+            padded.extend(digits);
+            digits = padded;
+        }
+        let sign: &[u8] = if n.is_negative() {
+            b"-"
+        } else if self.plus {
+            b"+"
+        } else if self.space {
+            b" "
+        } else {
+            b""
+        };
+        self.pad(gs, digits, sign)
+    }
+
+    fn text(&self, gs: &mut Gs, mut s: Vec<u8>) -> GsResult<Vec<u8>> {
+        if let Some(p) = self.precision {
+            s.truncate(p);
+        }
+        Spec {
+            zero: false,
+            ..*self
+        }
+        .pad(gs, s, b"")
+    }
+}
+
+/// A width or precision, which like C's is at most `i32::MAX`.
+fn number(fmt: &[u8], i: &mut usize) -> GsResult<usize> {
+    let mut n = 0i32;
+    while let Some(d) = fmt.get(*i).filter(|b| b.is_ascii_digit()) {
+        n = n
+            .checked_mul(10)
+            .and_then(|n| n.checked_add((d - b'0') as i32))
+            .ok_or_else(|| format_error("width or precision too large"))?;
+        *i += 1;
+    }
+    Ok(n as usize)
+}
+
+/// Format `args` according to `fmt`, in the style of C's `printf`, within
+/// `gs`'s limit on string length.
+pub fn format(gs: &mut Gs, fmt: &[u8], args: Vec<Gval>) -> GsResult<Vec<u8>> {
+    let mut args = args.into_iter();
+    let mut r = vec![];
+    let mut i = 0;
+    while i < fmt.len() {
+        if fmt[i] != b'%' {
+            r.push(fmt[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+        let mut spec = Spec::default();
+        while let Some(&flag) = fmt.get(i) {
+            match flag {
+                b'-' => spec.left = true,
+                b'0' => spec.zero = true,
+                b'+' => spec.plus = true,
+                b' ' => spec.space = true,
+                _ => break,
+            }
+            i += 1;
+        }
+        spec.width = number(fmt, &mut i)?;
+        if fmt.get(i) == Some(&b'.') {
+            i += 1;
+            spec.precision = Some(number(fmt, &mut i)?);
+        }
+        let conversion = *fmt
+            .get(i)
+            .ok_or_else(|| format_error("incomplete directive"))?;
+        i += 1;
+        if conversion == b'%' {
+            r.push(b'%');
+            continue;
+        }
+        let arg = args
+            .next()
+            .ok_or_else(|| format_error("too few arguments"))?;
+        r.extend(match conversion {
+            b'd' | b'i' => spec.int(gs, &arg.unwrap_int()?, 10, false)?,
+            b'x' => spec.int(gs, &arg.unwrap_int()?, 16, false)?,
+            b'X' => spec.int(gs, &arg.unwrap_int()?, 16, true)?,
+            b'o' => spec.int(gs, &arg.unwrap_int()?, 8, false)?,
+            b'b' => spec.int(gs, &arg.unwrap_int()?, 2, false)?,
+            b'c' => {
+                let c = arg.unwrap_int()?.to_u8();
+                spec.text(gs, vec![c.ok_or_else(|| format_error("%c needs a byte"))?])?
+            }
+            b's' => spec.text(gs, arg.into_gs())?,
+            b'p' => spec.text(gs, arg.inspect())?,
+            c => {
+                let msg = format!("unknown conversion %{}", c as char);
+                return Err(format_error(&msg));
+            }
+        });
+        gs.check_size(Size::StringLen, r.len())?;
+    }
+    Ok(r)
+}
+
+impl Gs {
+    /// `[args] fmt printf`: format the arguments like C's `printf`, with
+    /// `%d %x %X %o %b %c %s %p` and the `- 0 + space` flags.
+    pub fn printf(&mut self) -> GsResult<()> {
+        let fmt = self.pop()?.unwrap_str()?;
+        let args = self.pop()?.into_arr()?;
+        let r = format(self, &fmt, args)?;
+        self.push(Gval::Str(r));
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use crate::error::{GsError, GsResult};
+use crate::value::Gval;
+use crate::Gs;
+use serde_json::json;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Open `path`, which `sandboxed` resolved to no symlink inside `root`,
+/// without following one that has replaced it since. A directory on the way
+/// may have been swapped too, so the file opened must still be the one that
+/// `path` resolves to, inside `root`.
+fn open_resolved(root: &Path, path: &Path, options: &mut OpenOptions) -> std::io::Result<File> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+        let file = options.custom_flags(libc::O_NOFOLLOW).open(path)?;
+        let there = path.canonicalize()?;
+        let (opened, there_now) = (file.metadata()?, there.metadata()?);
+        let same = (opened.dev(), opened.ino()) == (there_now.dev(), there_now.ino());
+        if !same || !there.starts_with(root) {
+            return Err(std::io::Error::other("changed while it was being opened"));
+        }
+        Ok(file)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = root;
+        options.open(path)
+    }
+}
+
+impl Gs {
+    /// Resolve `path` inside the `--allow-fs` directory, refusing anything
+    /// (`..`, absolute paths, symlinks) that would escape it. A symlink is
+    /// resolved to what it points to, and one that points to nothing is
+    /// refused, since writing would create the file wherever it points.
+    fn sandboxed(&mut self, op: &'static str, path: Gval) -> GsResult<PathBuf> {
+        let root = match &self.permissions.fs {
+            Some(root) => root.clone(),
+            None => return Err(self.deny(op, "--allow-fs")),
+        };
+        let path = root.join(String::from_utf8_lossy(&path.unwrap_str()?).as_ref());
+        let io_error = |e| GsError::Runtime(format!("`{}`: {}: {}", op, path.display(), e));
+        // The file itself may not exist yet when writing, so check its parent.
+        let resolved = match (path.parent(), path.file_name()) {
+            (Some(dir), Some(file)) => dir.canonicalize().map_err(io_error)?.join(file),
+            _ => path.canonicalize().map_err(io_error)?,
+        };
+        let resolved = match resolved.canonicalize() {
+            Ok(resolved) => resolved,
+            Err(e) if resolved.symlink_metadata().is_ok() => return Err(io_error(e)),
+            Err(_) => resolved,
+        };
+        if resolved.starts_with(&root) && resolved != root {
+            self.audit(op, || json!({ "path": resolved.display().to_string() }));
+            Ok(resolved)
+        } else {
+            let e = GsError::Runtime(format!(
+                "`{}`: {} is outside {}",
+                op,
+                path.display(),
+                root.display()
+            ));
+            self.audit(op, || json!({ "denied": e.to_string() }));
+            Err(e)
+        }
+    }
+
+    /// Open a path that `sandboxed` resolved, with `open_resolved`.
+    fn open_sandboxed(&self, op: &str, path: &Path, options: &mut OpenOptions) -> GsResult<File> {
+        let root = self
+            .permissions
+            .fs
+            .as_ref()
+            .expect("only --allow-fs resolves paths");
+        open_resolved(root, path, options)
+            .map_err(|e| GsError::Runtime(format!("`{}`: {}: {}", op, path.display(), e)))
+    }
+
+    /// `path read`: push the contents of a file.
+    pub fn read_file(&mut self) -> GsResult<()> {
+        let path = self.pop()?;
+        let path = self.sandboxed("read", path)?;
+        let mut file = self.open_sandboxed("read", &path, OpenOptions::new().read(true))?;
+        let mut bytes = vec![];
+        file.read_to_end(&mut bytes)
+            .map_err(|e| GsError::Runtime(format!("`read`: {}: {}", path.display(), e)))?;
+        self.push(Gval::Str(bytes));
+        Ok(())
+    }
+
+    /// `contents path write`: write a string (or anything, flattened) to a file.
+    pub fn write_file(&mut self) -> GsResult<()> {
+        let path = self.pop()?;
+        let path = self.sandboxed("write", path)?;
+        let contents = self.pop()?.into_gs();
+        // Truncated only once it's known to be the file that was checked.
+        let mut options = OpenOptions::new();
+        options.write(true).create(true);
+        let mut file = self.open_sandboxed("write", &path, &mut options)?;
+        file.set_len(0)
+            .and_then(|()| file.write_all(&contents))
+            .map_err(|e| GsError::Runtime(format!("`write`: {}: {}", path.display(), e)))
+    }
+
+    /// `path require`: run a GolfScript file, unless it has already been
+    /// required, so libraries can be shared between files.
+    pub fn require(&mut self) -> GsResult<()> {
+        let path = self.pop()?;
+        let path = self.sandboxed("require", path)?;
+        if !self.required.insert(path.clone()) {
+            return Ok(());
+        }
+        let mut file = self.open_sandboxed("require", &path, OpenOptions::new().read(true))?;
+        let mut code = vec![];
+        file.read_to_end(&mut code)
+            .map_err(|e| GsError::Runtime(format!("`require`: {}: {}", path.display(), e)))?;
+        self.run(&code)
+    }
+}
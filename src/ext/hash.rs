@@ -0,0 +1,23 @@
+use crate::error::GsResult;
+use crate::value::Gval;
+use crate::Gs;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+impl Gs {
+    fn digest<D: Digest>(&mut self) -> GsResult<()> {
+        let s = self.pop()?.unwrap_str()?;
+        self.push(Gval::Str(D::digest(&s).to_vec()));
+        Ok(())
+    }
+
+    /// The 16 raw digest bytes; follow with `hex` for the usual form.
+    pub fn md5(&mut self) -> GsResult<()> {
+        self.digest::<Md5>()
+    }
+
+    /// The 32 raw digest bytes; follow with `hex` for the usual form.
+    pub fn sha256(&mut self) -> GsResult<()> {
+        self.digest::<Sha256>()
+    }
+}
@@ -0,0 +1,160 @@
+use crate::error::{GsError, GsResult};
+use crate::limits::Size;
+use crate::value::Gval;
+use crate::Gs;
+use num::BigInt;
+use num::Integer;
+use num::One;
+use num::Signed;
+use num::ToPrimitive;
+use num::Zero;
+
+/// Miller-Rabin with the first 13 primes as witnesses: exact below 3.3e24,
+/// and wrong beyond that with negligible probability.
+pub fn is_prime(n: &BigInt) -> bool {
+    const WITNESSES: [u32; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+    if *n < BigInt::from(2) {
+        return false;
+    }
+    for &p in &WITNESSES {
+        if *n == BigInt::from(p) {
+            return true;
+        }
+        if (n % p).is_zero() {
+            return false;
+        }
+    }
+    let n1: BigInt = n - 1;
+    let s = n1.trailing_zeros().unwrap_or(0);
+    let d = &n1 >> s;
+    'witness: for &a in &WITNESSES {
+        let mut x = BigInt::from(a).modpow(&d, n);
+        if x.is_one() || x == n1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = x.modpow(&BigInt::from(2), n);
+            if x == n1 {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// The inverse of `a` modulo `m`, in the same range `mod_floor` uses.
+pub fn modinv(a: &BigInt, m: &BigInt) -> Option<BigInt> {
+    if m.is_zero() {
+        return None;
+    }
+    let e = a.mod_floor(m).extended_gcd(m);
+    if e.gcd.abs().is_one() {
+        Some(e.x.mod_floor(m))
+    } else {
+        None
+    }
+}
+
+fn not_invertible(a: &BigInt, m: &BigInt) -> GsError {
+    GsError::Runtime(format!("{} has no inverse modulo {}", a, m))
+}
+
+impl Gs {
+    fn pop_int(&mut self) -> GsResult<BigInt> {
+        self.pop()?.unwrap_int()
+    }
+
+    pub fn gcd(&mut self) -> GsResult<()> {
+        let b = self.pop_int()?;
+        let a = self.pop_int()?;
+        self.push(Gval::Int(a.gcd(&b)));
+        Ok(())
+    }
+
+    pub fn lcm(&mut self) -> GsResult<()> {
+        let b = self.pop_int()?;
+        let a = self.pop_int()?;
+        self.push(Gval::Int(a.lcm(&b)));
+        Ok(())
+    }
+
+    /// The integer square root, rounded down.
+    pub fn isqrt(&mut self) -> GsResult<()> {
+        let n = self.pop_int()?;
+        if n.is_negative() {
+            return Err(GsError::Runtime("`sqrt` of a negative number".into()));
+        }
+        self.push(Gval::Int(n.sqrt()));
+        Ok(())
+    }
+
+    pub fn prime(&mut self) -> GsResult<()> {
+        let n = self.pop_int()?;
+        self.push(Gval::bool(is_prime(&n)));
+        Ok(())
+    }
+
+    /// `b e m powmod`: `b` to the `e` modulo `m`. A negative exponent raises
+    /// the modular inverse instead.
+    pub fn powmod(&mut self) -> GsResult<()> {
+        let m = self.pop_int()?;
+        let e = self.pop_int()?;
+        let b = self.pop_int()?;
+        if m.is_zero() {
+            return Err(GsError::Runtime("`powmod` by zero".into()));
+        }
+        let b = if e.is_negative() {
+            modinv(&b, &m).ok_or_else(|| not_invertible(&b, &m))?
+        } else {
+            b
+        };
+        self.push(Gval::Int(b.modpow(&e.abs(), &m)));
+        Ok(())
+    }
+
+    /// `a m modinv`: the `x` with `a*x` congruent to 1 modulo `m`.
+    pub fn modinv(&mut self) -> GsResult<()> {
+        let m = self.pop_int()?;
+        let a = self.pop_int()?;
+        let x = modinv(&a, &m).ok_or_else(|| not_invertible(&a, &m))?;
+        self.push(Gval::Int(x));
+        Ok(())
+    }
+
+    /// Shift `a` left by `n` bits, or right for negative `n`. Right shifts
+    /// round down, like `/` by a power of two.
+    fn shift(&mut self, a: BigInt, n: BigInt) -> GsResult<()> {
+        let r = match (n.is_negative(), n.abs().to_usize()) {
+            (false, Some(n)) => {
+                if !a.is_zero() {
+                    self.check_size(Size::IntBits, a.bits() + n as u64)?;
+                    self.work(((a.bits() + n as u64) / 64) as usize)?;
+                }
+                a << n
+            }
+            (false, None) if a.is_zero() => a,
+            (false, None) => return Err(GsError::Runtime("shift amount too large".into())),
+            (true, Some(n)) => {
+                self.work((a.bits().saturating_sub(n as u64) / 64) as usize)?;
+                a >> n
+            }
+            (true, None) if a.is_negative() => -BigInt::one(),
+            (true, None) => BigInt::zero(),
+        };
+        self.push(Gval::Int(r));
+        Ok(())
+    }
+
+    pub fn shl(&mut self) -> GsResult<()> {
+        let n = self.pop_int()?;
+        let a = self.pop_int()?;
+        self.shift(a, n)
+    }
+
+    pub fn shr(&mut self) -> GsResult<()> {
+        let n = self.pop_int()?;
+        let a = self.pop_int()?;
+        self.shift(a, -n)
+    }
+}
@@ -0,0 +1,54 @@
+use crate::error::{GsError, GsResult};
+use crate::limits::Size;
+use crate::value::Gval;
+use crate::Gs;
+use serde_json::json;
+
+/// How long a request may take in all.
+#[cfg(feature = "net")]
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// The body of an HTTP or HTTPS GET of `url`, reading at most `max` bytes
+/// and one more, so a caller can tell the body was longer.
+#[cfg(feature = "net")]
+pub fn get(url: &str, max: Option<usize>) -> Result<Vec<u8>, String> {
+    use std::io::Read;
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    let response = agent.get(url).call().map_err(|e| {
+        // Errors start with the URL, which callers say already.
+        let e = e.to_string();
+        let prefix = format!("{}: ", url);
+        e.strip_prefix(&prefix).unwrap_or(&e).to_string()
+    })?;
+    let limit = max.map_or(u64::MAX, |max| max as u64 + 1);
+    let mut body = vec![];
+    response
+        .into_reader()
+        .take(limit)
+        .read_to_end(&mut body)
+        .map_err(|e| e.to_string())?;
+    Ok(body)
+}
+
+#[cfg(not(feature = "net"))]
+pub fn get(_url: &str, _max: Option<usize>) -> Result<Vec<u8>, String> {
+    Err("golfscript-rs was built without the `net` feature".to_string())
+}
+
+impl Gs {
+    /// `url fetch`: push the body of an HTTP or HTTPS GET of a URL.
+    pub fn fetch(&mut self) -> GsResult<()> {
+        if !self.permissions.net {
+            return Err(self.deny("fetch", "--allow-net"));
+        }
+        let url = self.pop()?.unwrap_str()?;
+        let url = String::from_utf8_lossy(&url).into_owned();
+        self.flush()?;
+        let body = get(&url, self.limits.string_len)
+            .map_err(|e| GsError::Runtime(format!("`fetch`: {}: {}", url, e)))?;
+        self.audit("fetch", || json!({ "url": url, "bytes": body.len() }));
+        self.check_size(Size::StringLen, body.len())?;
+        self.push(Gval::Str(body));
+        Ok(())
+    }
+}
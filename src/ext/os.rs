@@ -0,0 +1,46 @@
+use crate::error::{GsError, GsResult};
+use crate::value::Gval;
+use crate::Gs;
+use serde_json::json;
+use std::process::Command;
+
+impl Gs {
+    /// `name getenv`: push an environment variable's value, or `""` if unset.
+    pub fn getenv(&mut self) -> GsResult<()> {
+        if !self.permissions.env {
+            return Err(self.deny("getenv", "--allow-env"));
+        }
+        let name = self.pop()?.unwrap_str()?;
+        let name = String::from_utf8_lossy(&name).into_owned();
+        let value = std::env::var_os(&name);
+        self.audit("getenv", || json!({ "name": name, "set": value.is_some() }));
+        let bytes = value.map_or(vec![], |v| v.to_string_lossy().into_owned().into_bytes());
+        self.push(Gval::Str(bytes));
+        Ok(())
+    }
+
+    /// `command exec`: run a shell command, pushing its stdout and exit status.
+    pub fn exec(&mut self) -> GsResult<()> {
+        if !self.permissions.exec {
+            return Err(self.deny("exec", "--allow-exec"));
+        }
+        let command = self.pop()?.unwrap_str()?;
+        let command = String::from_utf8_lossy(&command).into_owned();
+        self.flush()?;
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        let output = Command::new(shell)
+            .args([flag, &command])
+            .output()
+            .map_err(|e| GsError::Runtime(format!("`exec`: {}", e)))?;
+        let status = output.status.code();
+        self.audit("exec", || json!({ "command": command, "status": status }));
+        self.push(Gval::Str(output.stdout));
+        // A command killed by a signal has no exit code.
+        self.push(Gval::Int(output.status.code().unwrap_or(-1).into()));
+        Ok(())
+    }
+}
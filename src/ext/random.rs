@@ -0,0 +1,33 @@
+use crate::error::{GsError, GsResult};
+use crate::Gs;
+use num::ToPrimitive;
+
+impl Gs {
+    /// A uniform index in `0..n`, for positive `n`, from the interpreter RNG.
+    fn random_index(&mut self, n: usize) -> usize {
+        self.draw(&n.into()).to_usize().unwrap_or(0)
+    }
+
+    /// Push a uniformly random element of an array or string.
+    pub fn choice(&mut self) -> GsResult<()> {
+        let vs = self.pop()?.into_arr()?;
+        if vs.is_empty() {
+            return Err(GsError::Runtime("`choice` from an empty sequence".into()));
+        }
+        let i = self.random_index(vs.len());
+        self.push(vs[i].clone());
+        Ok(())
+    }
+
+    /// Fisher-Yates shuffle an array or string.
+    pub fn shuffle(&mut self) -> GsResult<()> {
+        let v = self.pop()?;
+        let mut elems = v.clone().into_arr()?;
+        for i in (1..elems.len()).rev() {
+            let j = self.random_index(i + 1);
+            elems.swap(i, j);
+        }
+        self.push(v.rebuild(elems)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use crate::coerce::flatten;
+use crate::error::{GsError, GsResult};
+use crate::limits::Size;
+use crate::value::Gval;
+use crate::Gs;
+use regex::bytes::{Captures, Regex};
+
+fn compile(pattern: Gval) -> GsResult<Regex> {
+    let pattern = pattern.unwrap_str()?;
+    let pattern = String::from_utf8_lossy(&pattern);
+    Regex::new(&pattern).map_err(|e| GsError::Runtime(format!("invalid regex: {}", e)))
+}
+
+fn groups(caps: &Captures) -> Vec<Gval> {
+    caps.iter()
+        .map(|m| Gval::Str(m.map_or(vec![], |m| m.as_bytes().to_vec())))
+        .collect()
+}
+
+impl Gs {
+    /// `str re match`: the first match and its groups, or `[]`.
+    pub fn re_match(&mut self) -> GsResult<()> {
+        let re = compile(self.pop()?)?;
+        let s = self.pop()?.unwrap_str()?;
+        let r = re.captures(&s).map_or(vec![], |caps| groups(&caps));
+        self.push(Gval::Arr(r));
+        Ok(())
+    }
+
+    /// `str re scan`: every match, like Ruby's `String#scan`; with groups,
+    /// each match is the array of its groups.
+    pub fn re_scan(&mut self) -> GsResult<()> {
+        let re = compile(self.pop()?)?;
+        let s = self.pop()?.unwrap_str()?;
+        let r = re
+            .captures_iter(&s)
+            .map(|caps| match caps.len() {
+                1 => Gval::Str(caps[0].to_vec()),
+                _ => Gval::Arr(groups(&caps)[1..].to_vec()),
+            })
+            .collect();
+        self.push(Gval::Arr(r));
+        Ok(())
+    }
+
+    /// `str re replacement gsub`: replace every match. A string replacement
+    /// may refer to groups as `$1`; a block is run with the match on the stack.
+    pub fn re_gsub(&mut self) -> GsResult<()> {
+        let replacement = self.pop()?;
+        let re = compile(self.pop()?)?;
+        let s = self.pop()?.unwrap_str()?;
+        let r = match replacement {
+            Gval::Blk(code) => {
+                let mut r = vec![];
+                let mut last = 0;
+                let matches: Vec<_> = re.find_iter(&s).map(|m| m.range()).collect();
+                for range in matches {
+                    r.extend(&s[last..range.start]);
+                    let lb = self.stack.len();
+                    self.push(Gval::Str(s[range.clone()].to_vec()));
+                    self.run(&code)?;
+                    r.extend(flatten(
+                        self.stack.drain(lb.min(self.stack.len())..).collect(),
+                    ));
+                    last = range.end;
+                    self.check_size(Size::StringLen, r.len())?;
+                }
+                r.extend(&s[last..]);
+                r
+            }
+            // Like `replace_all`, but checking the length as it grows.
+            rep => {
+                let rep = rep.unwrap_str()?;
+                let mut r = vec![];
+                let mut last = 0;
+                for caps in re.captures_iter(&s) {
+                    let whole = caps.get(0).expect("group 0 is the whole match");
+                    r.extend(&s[last..whole.start()]);
+                    caps.expand(&rep, &mut r);
+                    last = whole.end();
+                    self.check_size(Size::StringLen, r.len())?;
+                }
+                r.extend(&s[last..]);
+                r
+            }
+        };
+        self.push(Gval::Str(r));
+        Ok(())
+    }
+}
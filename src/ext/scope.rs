@@ -0,0 +1,39 @@
+use crate::error::GsResult;
+use crate::value::Gval;
+use crate::Gs;
+
+impl Gs {
+    /// `{body} names local`: run `body`, then restore the named variables to
+    /// what they were before (or undefine them), even if `body` fails. Names
+    /// are a whitespace-separated string like `"i acc"` or an array of strings.
+    pub fn local(&mut self) -> GsResult<()> {
+        let names = match self.pop()? {
+            Gval::Arr(vs) => vs
+                .into_iter()
+                .map(Gval::unwrap_str)
+                .collect::<GsResult<Vec<_>>>()?,
+            v => v
+                .unwrap_str()?
+                .split(|b| b.is_ascii_whitespace())
+                .filter(|w| !w.is_empty())
+                .map(|w| w.to_vec())
+                .collect(),
+        };
+        let body = self.pop()?;
+        let saved: Vec<_> = names
+            .into_iter()
+            .map(|name| {
+                let v = self.vars.get(&name).cloned();
+                (name, v)
+            })
+            .collect();
+        let result = self.go(body);
+        for (name, v) in saved {
+            match v {
+                Some(v) => self.vars.insert(name, v),
+                None => self.vars.remove(&name),
+            };
+        }
+        result
+    }
+}
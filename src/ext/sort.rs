@@ -0,0 +1,50 @@
+use crate::error::GsResult;
+use crate::value::Gval;
+use crate::Gs;
+use num::Signed;
+
+impl Gs {
+    /// Whether `b` should come before `a`: the comparator leaves a positive
+    /// integer, like Ruby's `<=>`.
+    fn after(&mut self, code: &[u8], a: &Gval, b: &Gval) -> GsResult<bool> {
+        self.work(a.weight() + b.weight())?;
+        self.push(a.clone());
+        self.push(b.clone());
+        self.run(code)?;
+        Ok(self.pop()?.unwrap_int()?.is_positive())
+    }
+
+    /// A stable merge sort. Unlike `slice::sort_by`, it tolerates comparators
+    /// that aren't a consistent total order.
+    fn merge_sort(&mut self, code: &[u8], mut vs: Vec<Gval>) -> GsResult<Vec<Gval>> {
+        if vs.len() <= 1 {
+            return Ok(vs);
+        }
+        let right = vs.split_off(vs.len() / 2);
+        let left = self.merge_sort(code, vs)?;
+        let right = self.merge_sort(code, right)?;
+        let mut r = Vec::with_capacity(left.len() + right.len());
+        let mut left = left.into_iter().peekable();
+        let mut right = right.into_iter().peekable();
+        while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+            if self.after(code, a, b)? {
+                r.extend(right.next());
+            } else {
+                r.extend(left.next());
+            }
+        }
+        r.extend(left);
+        r.extend(right);
+        Ok(r)
+    }
+
+    /// `seq {comparator} sortwith`: sort with a block that compares two
+    /// elements and leaves a negative, zero, or positive integer.
+    pub fn sortwith(&mut self) -> GsResult<()> {
+        let code = self.pop()?.unwrap_blk()?;
+        let seq = self.pop()?;
+        let sorted = self.merge_sort(&code, seq.clone().into_arr()?)?;
+        self.push(seq.rebuild(sorted)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use crate::error::{GsError, GsResult};
+use crate::pretty::pretty;
+use crate::value::Gval;
+use crate::Gs;
+use std::io::Write;
+
+impl Gs {
+    /// Push the next line of input, including its newline, or `""` at EOF.
+    pub fn gets(&mut self) -> GsResult<()> {
+        // Interactive programs usually print a prompt first.
+        self.flush()?;
+        let mut line = vec![];
+        self.input
+            .read_until(b'\n', &mut line)
+            .map_err(|e| GsError::Runtime(format!("`gets`: {}", e)))?;
+        if let Some(journal) = &mut self.journal {
+            journal.reads.push(line.clone());
+        }
+        self.audit("gets", || serde_json::json!({ "bytes": line.len() }));
+        self.push(Gval::Str(line));
+        Ok(())
+    }
+
+    /// Print a value like `p`, but with long arrays broken up one element to
+    /// a line, and colored if the output is a terminal.
+    pub fn pp(&mut self) -> GsResult<()> {
+        let mut text = pretty(&self.pop()?, self.color);
+        text.push(b'\n');
+        self.print(&text)
+    }
+
+    /// Print the whole stack to stderr, leaving it untouched.
+    pub fn debug(&mut self) -> GsResult<()> {
+        // Keep stdout and stderr in order when both go to a terminal.
+        self.flush()?;
+        let mut line = Gval::Arr(self.stack.clone()).inspect();
+        line.push(b'\n');
+        let _ = std::io::stderr().write_all(&line);
+        Ok(())
+    }
+
+    /// Pause before the next token if a debugger is attached; otherwise do
+    /// nothing.
+    pub fn breakpoint(&mut self) -> GsResult<()> {
+        match self.hook.take() {
+            Some(mut hook) => {
+                let result = hook.pause(self);
+                self.hook = Some(hook);
+                result
+            }
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use crate::error::GsResult;
+use crate::value::Gval;
+use crate::Gs;
+
+impl Gs {
+    pub fn upcase(&mut self) -> GsResult<()> {
+        let s = self.pop()?.unwrap_str()?;
+        self.push(Gval::Str(s.to_ascii_uppercase()));
+        Ok(())
+    }
+
+    pub fn downcase(&mut self) -> GsResult<()> {
+        let s = self.pop()?.unwrap_str()?;
+        self.push(Gval::Str(s.to_ascii_lowercase()));
+        Ok(())
+    }
+
+    /// Remove leading and trailing ASCII whitespace.
+    pub fn strip(&mut self) -> GsResult<()> {
+        let s = self.pop()?.unwrap_str()?;
+        let start = s.iter().position(|b| !b.is_ascii_whitespace());
+        let end = s.iter().rposition(|b| !b.is_ascii_whitespace());
+        self.push(Gval::Str(match (start, end) {
+            (Some(i), Some(j)) => s[i..=j].to_vec(),
+            _ => vec![],
+        }));
+        Ok(())
+    }
+
+    /// Split on runs of ASCII whitespace, like Ruby's `String#split`.
+    pub fn words(&mut self) -> GsResult<()> {
+        let s = self.pop()?.unwrap_str()?;
+        let r = s
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|w| !w.is_empty())
+            .map(|w| Gval::Str(w.to_vec()))
+            .collect();
+        self.push(Gval::Arr(r));
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use crate::error::{GsError, GsResult};
+use crate::Gs;
+
+impl Gs {
+    /// `value throw`: unwind to the nearest enclosing `catch`.
+    pub fn throw(&mut self) -> GsResult<()> {
+        let v = self.pop()?;
+        Err(GsError::Thrown(v))
+    }
+
+    /// `{body} {handler} catch`: run `body`. If it throws, the stack is rolled
+    /// back to where it was before `body`, and `handler` runs with the thrown
+    /// value pushed on top. Other errors pass through.
+    pub fn catch(&mut self) -> GsResult<()> {
+        let handler = self.pop()?;
+        let body = self.pop()?;
+        // Keep the whole stack: the body may pop values from below its own.
+        self.work(self.stack.iter().map(|v| 1 + v.weight()).sum())?;
+        let stack = self.stack.clone();
+        let lb = self.lb.len();
+        let trace = self.trace.len();
+        match self.go(body) {
+            Err(GsError::Thrown(v)) => {
+                self.stack = stack;
+                self.lb.truncate(lb);
+                self.trace.truncate(trace);
+                self.push(v);
+                self.go(handler)
+            }
+            r => r,
+        }
+    }
+}
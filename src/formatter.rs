@@ -0,0 +1,143 @@
+//! Reprinting a program with tidy spacing: one space between tokens, none
+//! inside braces and brackets, the program's own line breaks kept but blank lines
+//! squeezed to one, and lines indented by how deeply they're nested in
+//! blocks. Comments stay where they are. The tokens don't change, which is
+//! checked by tokenizing the result, but a block's text does, which a
+//! program that prints a block or turns one into a string can tell.
+
+use crate::parse::{assigns_whitespace, parse_code, significant_tokens, Gtoken};
+
+enum Item<'a> {
+    Token(Vec<u8>),
+    Comment(&'a [u8]),
+    Open,
+    Close,
+    Newline,
+}
+
+/// Whether `text` ends partway through a UTF-8 character.
+fn unfinished(text: &[u8]) -> bool {
+    std::str::from_utf8(text).is_err_and(|e| e.error_len().is_none())
+}
+
+fn items<'a>(tokens: Vec<Gtoken<'a>>, out: &mut Vec<Item<'a>>) {
+    for token in tokens {
+        match token {
+            Gtoken::Block(inner, _) => {
+                out.push(Item::Open);
+                items(inner, out);
+                out.push(Item::Close);
+            }
+            Gtoken::Assignment(name) => out.push(Item::Token([b":", name].concat())),
+            Gtoken::Comment(text) => out.push(Item::Comment(text)),
+            Gtoken::Symbol(b"\n") => out.push(Item::Newline),
+            Gtoken::Symbol(s) if s.iter().all(u8::is_ascii_whitespace) => {}
+            Gtoken::Symbol(s) if !s.is_ascii() => match out.last_mut() {
+                // Each byte of a multibyte character is its own symbol, and
+                // they must stay together.
+                Some(Item::Token(last)) if unfinished(last) => last.extend_from_slice(s),
+                _ => out.push(Item::Token(s.to_vec())),
+            },
+            token => out.push(Item::Token(token.lexeme().to_vec())),
+        }
+    }
+}
+
+/// Lines of output, built an item at a time.
+#[derive(Default)]
+struct Layout {
+    lines: Vec<Vec<u8>>,
+    line: Vec<u8>,
+    /// How deeply the current line starts nested, and how deeply the next
+    /// item is.
+    indent: usize,
+    depth: usize,
+    /// Whether the next item continues the line after `{` or `[`, or has
+    /// to start a new one after a comment.
+    opened: bool,
+    commented: bool,
+}
+
+impl Layout {
+    /// Add `text` to the line, after a space unless it's `tight`.
+    fn put(&mut self, text: &[u8], tight: bool) {
+        if self.commented {
+            self.newline();
+        }
+        if self.line.is_empty() {
+            self.indent = self.depth;
+        } else if !self.opened && !tight {
+            self.line.push(b' ');
+        }
+        self.line.extend_from_slice(text);
+        self.opened = false;
+        self.commented = false;
+    }
+
+    fn newline(&mut self) {
+        if self.line.is_empty() {
+            // Keep one blank line between paragraphs, and none at the start.
+            if self.lines.last().is_some_and(|l| !l.is_empty()) {
+                self.lines.push(vec![]);
+            }
+            return;
+        }
+        let mut line = vec![b' '; 2 * self.indent];
+        line.append(&mut self.line);
+        self.lines.push(line);
+        self.commented = false;
+    }
+}
+
+/// `program` reprinted with tidy spacing, or why it can't be. A program
+/// that doesn't parse, or that assigns to whitespace, isn't changed.
+pub fn format(program: &[u8]) -> Result<Vec<u8>, String> {
+    let tokens = match parse_code(program) {
+        Ok((&[], tokens)) => tokens,
+        _ => return Err("the program doesn't parse".to_string()),
+    };
+    if assigns_whitespace(program) {
+        return Err("the program assigns to whitespace, so its spacing matters".to_string());
+    }
+    let mut list = vec![];
+    items(tokens, &mut list);
+    let mut layout = Layout::default();
+    for item in list {
+        match item {
+            Item::Token(text) => {
+                layout.put(&text, text == b"]");
+                layout.opened = text == b"[";
+            }
+            // A comment ends at a carriage return too, so what follows
+            // may need a new line.
+            Item::Comment(text) => {
+                layout.put(text, false);
+                layout.commented = true;
+            }
+            Item::Open => {
+                layout.put(b"{", false);
+                layout.depth += 1;
+                layout.opened = true;
+            }
+            Item::Close => {
+                // A line that starts with the `}` lines up with the `{`.
+                layout.depth -= 1;
+                layout.put(b"}", true);
+            }
+            Item::Newline => layout.newline(),
+        }
+    }
+    layout.newline();
+    while layout.lines.last().is_some_and(|l| l.is_empty()) {
+        layout.lines.pop();
+    }
+    let mut out = layout.lines.join(&b'\n');
+    // A final newline after a `:` would be its name.
+    if !out.ends_with(b":") {
+        out.push(b'\n');
+    }
+    if significant_tokens(&out, true) != significant_tokens(program, true) {
+        return Err("formatting would change the tokens; this is a bug".to_string());
+    }
+    Ok(out)
+}
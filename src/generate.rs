@@ -0,0 +1,164 @@
+//! Random programs that parse, for stress-testing the interpreter and
+//! feeding [`crate::reference`]: a grammar of literals, operators, arrays,
+//! blocks with the operators that run them, and variables, instead of the
+//! random bytes a fuzzer starts from, which mostly make short programs.
+//! Loops count down a variable of their own that nothing else assigns, so
+//! they end, though code run with `~` or a block run many times can still
+//! take long; run generated programs with a step limit.
+
+use crate::rng::Rng;
+use num::{BigInt, ToPrimitive};
+
+/// Operators and words the generator uses, all in the reference
+/// interpreter; `rand` is left out so runs can be compared.
+const OPERATORS: &[&str] = &[
+    "~", "`", "!", "@", "$", "+", "-", "*", "/", "%", "|", "&", "^", "\\", ";", "<", ">", "=", ",",
+    ".", "?", "(", ")", "abs", "zip", "base", "and", "or", "xor", "if",
+];
+
+/// Operators that do something with a block and the value under it.
+const BLOCK_OPERATORS: &[&str] = &["%", ",", "/", "*", "$", "?", "~", "if"];
+
+const WORDS: &[&str] = &["print", "puts", "p", "n"];
+
+const STRING_CHARS: &[u8] = b"abc xyz019,.-";
+
+pub struct Generator {
+    rng: Rng,
+    /// How many more items may be made, across the whole program.
+    budget: usize,
+    /// How deeply arrays and blocks may nest.
+    max_depth: usize,
+    /// The variables assigned so far, which later items may use.
+    vars: Vec<String>,
+    /// How many loops have been made, for their counters' names.
+    loops: usize,
+}
+
+impl Generator {
+    pub fn new(seed: u64) -> Generator {
+        Generator {
+            rng: Rng::Lcg(seed),
+            budget: 0,
+            max_depth: 3,
+            vars: vec![],
+            loops: 0,
+        }
+    }
+
+    /// How deeply arrays and blocks may nest; 3 by default.
+    pub fn max_depth(mut self, max: usize) -> Generator {
+        self.max_depth = max;
+        self
+    }
+
+    /// A number in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        self.rng.below(&BigInt::from(n)).to_usize().unwrap_or(0)
+    }
+
+    fn choose<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len())]
+    }
+
+    /// A program of about `size` items.
+    pub fn program(&mut self, size: usize) -> Vec<u8> {
+        self.budget = size.max(1);
+        self.vars.clear();
+        self.loops = 0;
+        let mut items = vec![];
+        while self.budget > 0 {
+            items.push(self.item(0));
+        }
+        items.join(" ").into_bytes()
+    }
+
+    /// Items until the budget or a random stop, at nesting `depth`.
+    fn sequence(&mut self, depth: usize) -> String {
+        let mut items = vec![];
+        while self.budget > 0 && (items.is_empty() || self.below(4) > 0) {
+            items.push(self.item(depth));
+        }
+        items.join(" ")
+    }
+
+    fn item(&mut self, depth: usize) -> String {
+        self.budget = self.budget.saturating_sub(1);
+        let nested = depth < self.max_depth;
+        match self.below(20) {
+            0..=3 => self.int(),
+            4 | 5 => self.string(),
+            6..=10 => self.choose(OPERATORS).to_string(),
+            11 => self.choose(WORDS).to_string(),
+            12 | 13 if nested => format!("[{}]", self.sequence(depth + 1)),
+            14 | 15 if nested => {
+                let block = self.sequence(depth + 1);
+                match self.below(3) {
+                    0 => format!("{{{}}}", block),
+                    _ => format!("{{{}}}{}", block, self.choose(BLOCK_OPERATORS)),
+                }
+            }
+            16 if nested => self.count_loop(depth),
+            17 => {
+                let name = match self.vars.len() {
+                    n if n < 3 && self.below(2) == 0 => {
+                        self.vars.push(format!("v{}", n));
+                        self.vars[n].clone()
+                    }
+                    0 => return self.int(),
+                    n => {
+                        let i = self.below(n);
+                        self.vars[i].clone()
+                    }
+                };
+                format!(":{}", name)
+            }
+            18 if !self.vars.is_empty() => {
+                let n = self.below(self.vars.len());
+                self.vars[n].clone()
+            }
+            _ => self.int(),
+        }
+    }
+
+    fn int(&mut self) -> String {
+        match self.below(10) {
+            0 => (self.below(1000) as i64 - 500).to_string(),
+            _ => (self.below(12) as i64 - 2).to_string(),
+        }
+    }
+
+    fn string(&mut self) -> String {
+        let len = self.below(5);
+        let text: String = (0..len)
+            .map(|_| STRING_CHARS[self.below(STRING_CHARS.len())] as char)
+            .collect();
+        match self.below(2) {
+            0 => format!("'{}'", text),
+            _ => format!("\"{}\"", text),
+        }
+    }
+
+    /// A `do`, `while` or `until` loop that runs at most a few times,
+    /// counting down a variable of its own.
+    fn count_loop(&mut self, depth: usize) -> String {
+        let counter = format!("c{}", self.loops);
+        self.loops += 1;
+        let times = self.below(4) + 1;
+        let body = self.sequence(depth + 1);
+        match self.below(3) {
+            0 => format!(
+                "{}:{};{{{} {} 1-:{}}}do",
+                times, counter, body, counter, counter
+            ),
+            1 => format!(
+                "{}:{};{{{}}}{{{} {} 1-:{};}}while",
+                times, counter, counter, body, counter, counter
+            ),
+            _ => format!(
+                "{}:{};{{{}!}}{{{} {} 1-:{};}}until",
+                times, counter, counter, body, counter, counter
+            ),
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! Suggestions for making a program shorter: small rewrites of a few
+//! neighbouring tokens, like `1+` to `)`, each with the bytes it saves. Some
+//! only hold for some types of operand, which the message says; they're
+//! hints for a golfer to check, not changes to make blindly.
+
+use crate::parse::{assigns_whitespace, offset, parse_code, Gtoken};
+use num::{BigInt, Integer, Signed, Zero};
+use std::collections::HashSet;
+
+pub struct Suggestion {
+    /// The bytes of the program to replace.
+    pub offset: usize,
+    pub len: usize,
+    pub replacement: Vec<u8>,
+    /// How many bytes shorter the program gets.
+    pub saves: usize,
+    /// A stable name for the kind of rewrite, like `increment`.
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Rewrites of a run of tokens: the tokens, what to write instead, a stable
+/// name, and when it holds.
+const RULES: &[(&[&str], &str, &str, &str)] = &[
+    (&["1", "+"], ")", "increment", "when the value is a number"),
+    (&["-1", "-"], ")", "increment", "when the value is a number"),
+    (&["1", "-"], "(", "decrement", "when the value is a number"),
+    (&["-1", "+"], "(", "decrement", "when the value is a number"),
+    (
+        &[".", ";"],
+        "",
+        "no-op",
+        "copying a value and dropping the copy does nothing",
+    ),
+    (&["\\", "\\"], "", "no-op", "swapping twice does nothing"),
+    (
+        &["@", "@", "@"],
+        "",
+        "no-op",
+        "rotating three times does nothing",
+    ),
+    (&["0", "$"], ".", "copy", "both copy the top value"),
+    (
+        &["(", "\\", ";"],
+        "0=",
+        "first",
+        "when the value is an array or string",
+    ),
+    (&["`", "puts"], "p", "print", "`p` prints a value as code"),
+    (
+        &["print", "n", "print"],
+        "puts",
+        "print",
+        "`puts` prints a newline after",
+    ),
+];
+
+/// A token and the bytes it spans in the program, braces included.
+struct Spanned<'a> {
+    token: &'a Gtoken<'a>,
+    start: usize,
+    end: usize,
+}
+
+/// The tokens of a program, braces included, with where each starts.
+fn flatten(program: &[u8], tokens: &[Gtoken], out: &mut Vec<(usize, Vec<u8>)>) {
+    for token in tokens {
+        let lexeme = token.lexeme();
+        let at = offset(program, lexeme);
+        match token {
+            Gtoken::Block(inner, _) => {
+                out.push((at - 1, b"{".to_vec()));
+                flatten(program, inner, out);
+                out.push((at + lexeme.len(), b"}".to_vec()));
+            }
+            Gtoken::Assignment(name) => out.push((at - 1, [b":", *name].concat())),
+            Gtoken::Symbol(s) if s.iter().all(u8::is_ascii_whitespace) => {}
+            _ => out.push((at, lexeme.to_vec())),
+        }
+    }
+}
+
+fn tokens_of(program: &[u8]) -> Option<Vec<(usize, Vec<u8>)>> {
+    let Ok((&[], tokens)) = parse_code(program) else {
+        return None;
+    };
+    let mut out = vec![];
+    flatten(program, &tokens, &mut out);
+    Some(out)
+}
+
+/// Whether replacing `start..end` of `program` with `replacement` changes
+/// only the tokens in between, not how its neighbours tokenize.
+fn keeps_neighbours(program: &[u8], start: usize, end: usize, replacement: &[u8]) -> bool {
+    let rewritten = [&program[..start], replacement, &program[end..]].concat();
+    let (Some(before), Some(after), Some(new)) = (
+        tokens_of(program),
+        tokens_of(&rewritten),
+        tokens_of(replacement),
+    ) else {
+        return false;
+    };
+    let text = |ts: &[(usize, Vec<u8>)]| ts.iter().map(|t| t.1.clone()).collect::<Vec<_>>();
+    let i = before.iter().take_while(|t| t.0 < start).count();
+    let j = before.iter().take_while(|t| t.0 < end).count();
+    let expected = [text(&before[..i]), text(&new), text(&before[j..])].concat();
+    text(&after) == expected
+}
+
+/// `a op b` for integer literals, as GolfScript computes it, if it's cheap
+/// to.
+fn fold(a: &BigInt, op: &[u8], b: &BigInt) -> Option<BigInt> {
+    Some(match op {
+        b"+" => a + b,
+        b"-" => a - b,
+        b"*" => a * b,
+        b"/" if !b.is_zero() => a.div_floor(b),
+        b"%" if !b.is_zero() => a.mod_floor(b),
+        b"?" if !b.is_negative() && *b <= BigInt::from(64) && a.bits() <= 64 => {
+            num::pow(a.clone(), b.try_into().ok()?)
+        }
+        _ => return None,
+    })
+}
+
+fn walk(program: &[u8], tokens: &[Gtoken], assigned: &HashSet<&[u8]>, out: &mut Vec<Suggestion>) {
+    // Runs of tokens with only whitespace between them.
+    let mut runs: Vec<Vec<Spanned>> = vec![vec![]];
+    for token in tokens {
+        let lexeme = token.lexeme();
+        let at = offset(program, lexeme);
+        match token {
+            Gtoken::Block(inner, _) => walk(program, inner, assigned, out),
+            Gtoken::Symbol(s) if s.iter().all(u8::is_ascii_whitespace) => continue,
+            _ => {}
+        }
+        match token {
+            Gtoken::Comment(_) | Gtoken::Block(..) | Gtoken::Assignment(_) => {
+                runs.push(vec![]);
+            }
+            _ => runs.last_mut().unwrap().push(Spanned {
+                token,
+                start: at,
+                end: at + lexeme.len(),
+            }),
+        }
+    }
+    for run in runs {
+        let mut i = 0;
+        while i < run.len() {
+            match rewrite(program, &run[i..], assigned) {
+                Some((n, suggestion)) => {
+                    out.push(suggestion);
+                    i += n;
+                }
+                None => i += 1,
+            }
+        }
+    }
+}
+
+/// A suggestion for the tokens starting `run`, and how many it covers.
+fn rewrite(
+    program: &[u8],
+    run: &[Spanned],
+    assigned: &HashSet<&[u8]>,
+) -> Option<(usize, Suggestion)> {
+    let suggest = |n: usize, replacement: Vec<u8>, code, message: String| {
+        let (start, end) = (run[0].start, run[n - 1].end);
+        let saves = (end - start).checked_sub(replacement.len())?;
+        if saves == 0 || !keeps_neighbours(program, start, end, &replacement) {
+            return None;
+        }
+        let shown = |b: &[u8]| String::from_utf8_lossy(b).into_owned();
+        let message = match replacement.is_empty() {
+            true => format!("`{}` can go: {}", shown(&program[start..end]), message),
+            false => format!(
+                "`{}` can be `{}`: {}",
+                shown(&program[start..end]),
+                shown(&replacement),
+                message
+            ),
+        };
+        Some((
+            n,
+            Suggestion {
+                offset: start,
+                len: end - start,
+                replacement,
+                saves,
+                code,
+                message,
+            },
+        ))
+    };
+    for &(pattern, replacement, code, when) in RULES {
+        let matches = pattern.len() <= run.len()
+            && pattern
+                .iter()
+                .zip(run)
+                .all(|(p, t)| t.token.lexeme() == p.as_bytes());
+        // The rewrite means nothing if the program changes what it uses.
+        let redefined = pattern
+            .iter()
+            .copied()
+            .chain(symbols(replacement))
+            .any(|name| assigned.contains(name.as_bytes()));
+        if matches && !redefined {
+            let replacement = replacement.as_bytes().to_vec();
+            if let Some(s) = suggest(pattern.len(), replacement, code, when.to_string()) {
+                return Some(s);
+            }
+        }
+    }
+    if let [a, b, op, ..] = run {
+        if let (Gtoken::IntLiteral(x), Gtoken::IntLiteral(y), Gtoken::Symbol(o)) =
+            (a.token, b.token, op.token)
+        {
+            let parse = |s: &[u8]| std::str::from_utf8(s).ok()?.parse::<BigInt>().ok();
+            let unassigned = [x, y, o].iter().all(|t| !assigned.contains(*t));
+            if let (true, Some(x), Some(y)) = (unassigned, parse(x), parse(y)) {
+                if let Some(r) = fold(&x, o, &y) {
+                    let message = "the result of the operation on two numbers".to_string();
+                    if let Some(s) = suggest(3, r.to_string().into_bytes(), "constant", message) {
+                        return Some(s);
+                    }
+                }
+            }
+        }
+    }
+    if let Gtoken::IntLiteral(n) = run[0].token {
+        let digits = n.strip_prefix(b"-").unwrap_or(n);
+        if digits.len() > 1 && digits[0] == b'0' && !assigned.contains(n) {
+            let value: BigInt = std::str::from_utf8(n).ok()?.parse().ok()?;
+            let message = "leading zeros don't change a number".to_string();
+            return suggest(1, value.to_string().into_bytes(), "leading-zeros", message);
+        }
+    }
+    None
+}
+
+/// The names a replacement uses.
+fn symbols(replacement: &str) -> Vec<&str> {
+    replacement
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .filter(|w| !w.is_empty())
+        .chain(replacement.matches(|c: char| c.is_ascii_punctuation()))
+        .collect()
+}
+
+fn assigned<'a>(tokens: &[Gtoken<'a>], names: &mut HashSet<&'a [u8]>) {
+    for token in tokens {
+        match token {
+            Gtoken::Block(inner, _) => assigned(inner, names),
+            Gtoken::Assignment(name) => {
+                names.insert(name);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Ways to make `program` shorter, in order. A program that assigns to
+/// whitespace gets none, since dropping a space could change what it does.
+pub fn suggest(program: &[u8]) -> Vec<Suggestion> {
+    let mut out = vec![];
+    let Ok((&[], tokens)) = parse_code(program) else {
+        return out;
+    };
+    if assigns_whitespace(program) {
+        return out;
+    }
+    let mut names = HashSet::new();
+    assigned(&tokens, &mut names);
+    walk(program, &tokens, &names, &mut out);
+    out.sort_by_key(|s| s.offset);
+    out
+}
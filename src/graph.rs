@@ -0,0 +1,160 @@
+//! A program's structure as a Graphviz graph: which blocks are nested in
+//! which, and which blocks assign and use which variables. A block assigned
+//! straight to a variable, like `{...}:f`, is drawn as its value. Render it
+//! with `dot -Tsvg`.
+
+use crate::parse::{offset, parse_code, Gtoken, Location};
+use crate::util::preview_code;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+/// A node for the program or a block, by where it starts.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Scope {
+    Program,
+    Block(usize),
+}
+
+impl Scope {
+    fn id(self) -> String {
+        match self {
+            Scope::Program => "program".to_string(),
+            Scope::Block(at) => format!("block{}", at),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Graph {
+    /// Each block's label and the scope it's in.
+    blocks: BTreeMap<usize, (String, Scope)>,
+    /// Which scopes assign and use each variable, and how often.
+    assigns: BTreeMap<(Scope, Vec<u8>), usize>,
+    uses: BTreeMap<(Scope, Vec<u8>), usize>,
+    /// Blocks assigned straight to a variable.
+    values: BTreeSet<(usize, Vec<u8>)>,
+}
+
+/// `text` for a DOT label: quotes and backslashes escaped, and long text
+/// cut short.
+fn label(text: &[u8]) -> String {
+    preview_code(text, 24)
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+}
+
+fn collect(
+    program: &[u8],
+    tokens: &[Gtoken],
+    scope: Scope,
+    names: &HashSet<&[u8]>,
+    graph: &mut Graph,
+) {
+    let mut previous_block = None;
+    for token in tokens {
+        let lexeme = token.lexeme();
+        let at = offset(program, lexeme);
+        match token {
+            Gtoken::Block(inner, _) => {
+                let loc = Location::new(program, at - 1);
+                let text = format!("{{{}}}", label(lexeme));
+                let name = format!("{}\\n{}:{}", text, loc.line, loc.col);
+                graph.blocks.insert(at - 1, (name, scope));
+                collect(program, inner, Scope::Block(at - 1), names, graph);
+            }
+            Gtoken::Assignment(name) => {
+                *graph.assigns.entry((scope, name.to_vec())).or_default() += 1;
+                if let Some(block) = previous_block {
+                    graph.values.insert((block, name.to_vec()));
+                }
+            }
+            Gtoken::Symbol(s) if names.contains(s) => {
+                *graph.uses.entry((scope, s.to_vec())).or_default() += 1;
+            }
+            _ => {}
+        }
+        previous_block = match token {
+            Gtoken::Block(..) => Some(at - 1),
+            Gtoken::Comment(_) => previous_block,
+            Gtoken::Symbol(s) if s.iter().all(u8::is_ascii_whitespace) => previous_block,
+            _ => None,
+        };
+    }
+}
+
+fn assigned<'a>(tokens: &[Gtoken<'a>], names: &mut HashSet<&'a [u8]>) {
+    for token in tokens {
+        match token {
+            Gtoken::Block(inner, _) => assigned(inner, names),
+            Gtoken::Assignment(name) => {
+                names.insert(name);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The structure of `program` in Graphviz's DOT language, or why it can't
+/// be drawn.
+pub fn dot(program: &[u8]) -> Result<String, String> {
+    let tokens = match parse_code(program) {
+        Ok((&[], tokens)) => tokens,
+        _ => return Err("the program doesn't parse".to_string()),
+    };
+    let mut names = HashSet::new();
+    assigned(&tokens, &mut names);
+    let mut graph = Graph::default();
+    collect(program, &tokens, Scope::Program, &names, &mut graph);
+    let var = |name: &[u8]| format!("\"var {}\"", label(name));
+    let times = |n: usize| match n {
+        1 => String::new(),
+        n => format!(" x{}", n),
+    };
+    let mut lines = vec![
+        "digraph program {".to_string(),
+        "  node [fontname=\"monospace\"];".to_string(),
+        "  program [shape=box, style=bold, label=\"program\"];".to_string(),
+    ];
+    for (at, (name, parent)) in &graph.blocks {
+        let block = Scope::Block(*at).id();
+        lines.push(format!("  {} [shape=box, label=\"{}\"];", block, name));
+        lines.push(format!(
+            "  {} -> {} [label=\"contains\"];",
+            parent.id(),
+            block
+        ));
+    }
+    let mut names: Vec<_> = names.into_iter().collect();
+    names.sort();
+    for name in names {
+        lines.push(format!(
+            "  {} [shape=ellipse, label=\"{}\"];",
+            var(name),
+            label(name)
+        ));
+    }
+    for ((scope, name), n) in &graph.assigns {
+        lines.push(format!(
+            "  {} -> {} [label=\"assigns{}\", style=bold];",
+            scope.id(),
+            var(name),
+            times(*n)
+        ));
+    }
+    for ((scope, name), n) in &graph.uses {
+        lines.push(format!(
+            "  {} -> {} [label=\"uses{}\", style=dashed];",
+            scope.id(),
+            var(name),
+            times(*n)
+        ));
+    }
+    for (at, name) in &graph.values {
+        lines.push(format!(
+            "  {} -> {} [label=\"value of\", style=dotted];",
+            Scope::Block(*at).id(),
+            var(name)
+        ));
+    }
+    lines.push("}".to_string());
+    Ok(lines.join("\n") + "\n")
+}
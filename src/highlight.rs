@@ -0,0 +1,224 @@
+//! Programs with their tokens colored by kind, for a terminal or as a web
+//! page. Names are colored as builtins unless the program assigns to them.
+
+use crate::builtins::lookup;
+use crate::parse::{lex, Class, Lexeme};
+use std::collections::HashSet;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HighlightFormat {
+    /// ANSI escape codes, for a terminal.
+    Ansi,
+    /// A standalone HTML page.
+    Html,
+}
+
+/// What a lexeme is, more finely than its [`Class`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Int,
+    String,
+    Comment,
+    Builtin,
+    Variable,
+    Operator,
+    Assignment,
+    Brace,
+    Plain,
+    Error,
+}
+
+impl Kind {
+    fn ansi(self) -> &'static str {
+        match self {
+            Kind::Int => "36",
+            Kind::String => "32",
+            Kind::Comment => "90",
+            Kind::Builtin => "35",
+            Kind::Variable => "33",
+            Kind::Operator => "34",
+            Kind::Assignment => "1;33",
+            Kind::Brace => "1",
+            Kind::Plain => "",
+            Kind::Error => "4;31",
+        }
+    }
+
+    fn css_class(self) -> &'static str {
+        match self {
+            Kind::Int => "int",
+            Kind::String => "str",
+            Kind::Comment => "comment",
+            Kind::Builtin => "builtin",
+            Kind::Variable => "var",
+            Kind::Operator => "op",
+            Kind::Assignment => "assign",
+            Kind::Brace => "brace",
+            Kind::Plain => "",
+            Kind::Error => "error",
+        }
+    }
+}
+
+const CSS: &str = "\
+pre.gs { background: #fdfdfd; color: #222; padding: 1em; }
+.gs .int { color: #0a7f8c; }
+.gs .str { color: #2e7d32; }
+.gs .comment { color: #888; font-style: italic; }
+.gs .builtin { color: #8e24aa; }
+.gs .var { color: #a66a00; }
+.gs .op { color: #1e5bb8; }
+.gs .assign { color: #a66a00; font-weight: bold; }
+.gs .brace { font-weight: bold; }
+.gs .error { color: #c62828; text-decoration: underline wavy; }";
+
+/// The lexemes of `code`, each with its kind.
+fn kinds(code: &[u8]) -> Vec<(Lexeme, Kind)> {
+    let lexemes = lex(code);
+    let text = |l: &Lexeme| &code[l.offset..l.offset + l.len];
+    let assigned: HashSet<&[u8]> = lexemes
+        .iter()
+        .filter(|l| l.class == Class::Assignment)
+        .map(|l| &text(l)[1..])
+        .collect();
+    lexemes
+        .iter()
+        .map(|l| {
+            let kind = match l.class {
+                Class::Int => Kind::Int,
+                Class::String => Kind::String,
+                Class::Comment => Kind::Comment,
+                Class::Word | Class::Symbol if assigned.contains(text(l)) => Kind::Variable,
+                Class::Word if lookup(text(l)).is_some() => Kind::Builtin,
+                Class::Word => Kind::Variable,
+                Class::Symbol => Kind::Operator,
+                Class::Assignment => Kind::Assignment,
+                Class::Brace => Kind::Brace,
+                Class::Whitespace => Kind::Plain,
+                Class::Unparsed => Kind::Error,
+            };
+            (*l, kind)
+        })
+        .collect()
+}
+
+/// The lexemes of `code`, which may be unfinished, each with its kind. A
+/// brace or quote without its partner is an error, and what follows an
+/// unclosed one is colored as the code it'd be once closed.
+fn unfinished_kinds(code: &[u8]) -> Vec<(Lexeme, Kind)> {
+    let mut out = vec![];
+    let mut base = 0;
+    loop {
+        let rest = &code[base..];
+        let mut kinds = kinds(rest);
+        let Some((unparsed, _)) = kinds.pop_if(|(l, _)| l.class == Class::Unparsed) else {
+            out.extend(kinds.into_iter().map(|(l, kind)| (shift(l, base), kind)));
+            return out;
+        };
+        let at = base + unparsed.offset;
+        out.extend(kinds.into_iter().map(|(l, kind)| (shift(l, base), kind)));
+        let delimiter = Lexeme {
+            offset: at,
+            len: 1,
+            class: Class::Unparsed,
+        };
+        out.push((delimiter, Kind::Error));
+        match code[at] {
+            b'{' | b'}' => base = at + 1,
+            b'"' | b'\'' if at + 1 < code.len() => {
+                let string = Lexeme {
+                    offset: at + 1,
+                    len: code.len() - at - 1,
+                    class: Class::String,
+                };
+                out.push((string, Kind::String));
+                return out;
+            }
+            _ => {
+                out.last_mut().unwrap().0.len = code.len() - at;
+                return out;
+            }
+        }
+    }
+}
+
+/// `l`, from code starting `by` bytes into the program.
+fn shift(l: Lexeme, by: usize) -> Lexeme {
+    Lexeme {
+        offset: l.offset + by,
+        ..l
+    }
+}
+
+/// Append the bytes `start..end` of `code` to `out`, colored as `kinds`
+/// says.
+fn paint(out: &mut String, code: &[u8], kinds: Vec<(Lexeme, Kind)>, start: usize, end: usize) {
+    for (l, kind) in kinds {
+        let (from, to) = (l.offset.max(start), (l.offset + l.len).min(end));
+        if from >= to {
+            continue;
+        }
+        let text = String::from_utf8_lossy(&code[from..to]);
+        match kind.ansi() {
+            "" => out.push_str(&text),
+            color => {
+                // Color each line separately, so lines can be shown alone.
+                let lines: Vec<_> = text
+                    .split('\n')
+                    .map(|line| match line {
+                        "" => String::new(),
+                        line => format!("\x1b[{}m{}\x1b[0m", color, line),
+                    })
+                    .collect();
+                out.push_str(&lines.join("\n"));
+            }
+        }
+    }
+}
+
+/// The bytes `start..end` of `code` with ANSI colors, lexed in the context
+/// of the whole program.
+pub fn ansi_range(code: &[u8], start: usize, end: usize) -> String {
+    let mut out = String::new();
+    paint(&mut out, code, kinds(code), start, end);
+    out
+}
+
+/// `code` with ANSI colors.
+pub fn ansi(code: &[u8]) -> String {
+    ansi_range(code, 0, code.len())
+}
+
+/// `code` with ANSI colors, as typed so far: a brace or quote without its
+/// partner is red, so it shows before the code runs.
+pub fn ansi_unfinished(code: &[u8]) -> String {
+    let mut out = String::new();
+    paint(&mut out, code, unfinished_kinds(code), 0, code.len());
+    out
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `code` as a standalone HTML page titled `title`.
+pub fn html(code: &[u8], title: &str) -> String {
+    let mut body = String::new();
+    for (l, kind) in kinds(code) {
+        let text = escape_html(&String::from_utf8_lossy(&code[l.offset..l.offset + l.len]));
+        match kind.css_class() {
+            "" => body.push_str(&text),
+            class => body.push_str(&format!("<span class=\"{}\">{}</span>", class, text)),
+        }
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\n{}\n</style>\n</head>\n<body>\n<pre class=\"gs\">{}</pre>\n</body>\n</html>\n",
+        escape_html(title),
+        CSS,
+        body
+    )
+}
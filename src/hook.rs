@@ -0,0 +1,53 @@
+//! Callbacks into a running program, for debuggers and profilers.
+
+use crate::error::GsResult;
+use crate::Gs;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// A token about to run.
+pub struct Step<'a> {
+    /// What is running: the program, a block body, or an evaluated string.
+    pub code: &'a [u8],
+    pub offset: usize,
+    /// The token; for an assignment, including its `:`.
+    pub token: &'a [u8],
+    /// The name an assignment assigns to.
+    pub assigns: Option<&'a [u8]>,
+    /// How many runs are nested: 1 for the program, more inside blocks.
+    pub depth: usize,
+    /// Where the token is in the program, if it can be found there.
+    pub program_offset: Option<usize>,
+}
+
+pub trait Hook {
+    /// Called before each token runs. An error stops the program there.
+    fn before(&mut self, gs: &mut Gs, step: &Step) -> GsResult<()>;
+
+    /// Called by the `breakpoint` builtin.
+    fn pause(&mut self, _gs: &mut Gs) -> GsResult<()> {
+        Ok(())
+    }
+}
+
+/// Share a hook, to look at what it collected after the run.
+impl<H: Hook> Hook for Rc<RefCell<H>> {
+    fn before(&mut self, gs: &mut Gs, step: &Step) -> GsResult<()> {
+        self.borrow_mut().before(gs, step)
+    }
+
+    fn pause(&mut self, gs: &mut Gs) -> GsResult<()> {
+        self.borrow_mut().pause(gs)
+    }
+}
+
+/// Several hooks, called in order.
+impl Hook for Vec<Box<dyn Hook>> {
+    fn before(&mut self, gs: &mut Gs, step: &Step) -> GsResult<()> {
+        self.iter_mut().try_for_each(|hook| hook.before(gs, step))
+    }
+
+    fn pause(&mut self, gs: &mut Gs) -> GsResult<()> {
+        self.iter_mut().try_for_each(|hook| hook.pause(gs))
+    }
+}
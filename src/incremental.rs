@@ -0,0 +1,210 @@
+//! Running a program again as it's edited, from where the edit is rather
+//! than from the start, for `watch`. The interpreter's state is kept before
+//! each of the program's top-level tokens, and when the code changes, the
+//! run resumes from the last state that the tokens before it, all
+//! unchanged, left. That's what running it all again would do only if those
+//! tokens did nothing outside the interpreter: lines they read with `gets`,
+//! the time they looked at and files they read aren't read again.
+
+use crate::error::GsResult;
+use crate::hook::{Hook, Step};
+use crate::parse::{offset, parse_program, Gtoken};
+use crate::rng::Rng;
+use crate::util::Buffer;
+use crate::value::Gval;
+use crate::Gs;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Instant;
+
+/// The interpreter's state between two tokens, and what it had printed.
+#[derive(Clone)]
+struct Snapshot {
+    stack: Vec<Gval>,
+    vars: HashMap<Vec<u8>, Gval>,
+    lb: Vec<usize>,
+    rng: Rng,
+    draws: u64,
+    steps: u64,
+    written: u64,
+    output: usize,
+}
+
+impl Snapshot {
+    fn of(gs: &Gs, output: usize) -> Snapshot {
+        Snapshot {
+            stack: gs.stack.clone(),
+            vars: gs.vars.clone(),
+            lb: gs.lb.clone(),
+            rng: gs.rng.clone(),
+            draws: gs.draws,
+            steps: gs.steps,
+            written: gs.written,
+            output,
+        }
+    }
+
+    /// Put `gs` back in this state, with the whole time limit ahead of it
+    /// again, as a new run would have.
+    fn restore(self, gs: &mut Gs, output: &Buffer) {
+        gs.stack = self.stack;
+        gs.vars = self.vars;
+        gs.lb = self.lb;
+        gs.rng = self.rng;
+        gs.draws = self.draws;
+        gs.steps = self.steps;
+        gs.written = self.written;
+        gs.work = 0;
+        gs.ignored = false;
+        gs.trace.clear();
+        if let Some((_, timeout)) = gs.deadline {
+            gs.deadline = Some((Instant::now() + timeout, timeout));
+        }
+        output.0.borrow_mut().truncate(self.output);
+    }
+}
+
+/// Takes a snapshot before each top-level token that does something.
+struct Snapshots {
+    output: Buffer,
+    taken: Vec<(usize, Snapshot)>,
+}
+
+impl Hook for Snapshots {
+    fn before(&mut self, gs: &mut Gs, step: &Step) -> GsResult<()> {
+        let token = step.token;
+        let inert = token.starts_with(b"#") || token.iter().all(u8::is_ascii_whitespace);
+        if step.depth == 1 && !inert {
+            let output = self.output.0.borrow().len();
+            self.taken.push((step.offset, Snapshot::of(gs, output)));
+        }
+        Ok(())
+    }
+}
+
+/// How a program was run again.
+pub struct Rerun {
+    pub result: GsResult<()>,
+    /// Where in the program running resumed: 0 if it ran from the start,
+    /// or `None` if it didn't run, not parsing.
+    pub resumed_at: Option<usize>,
+}
+
+/// An interpreter running versions of a program, keeping what it can of
+/// each run for the next.
+pub struct Incremental {
+    gs: Gs,
+    output: Buffer,
+    /// The state before any of the program ran.
+    start: Snapshot,
+    /// The last version run, and the snapshots taken running it, in order.
+    code: Vec<u8>,
+    snapshots: Rc<RefCell<Snapshots>>,
+}
+
+impl Incremental {
+    /// Run programs on `gs` as it is now, keeping what they print to hand
+    /// back rather than writing it where `gs` writes.
+    pub fn new(mut gs: Gs) -> Incremental {
+        let output = Buffer::default();
+        gs.replace_output(Box::new(output.clone()));
+        let start = Snapshot::of(&gs, 0);
+        let snapshots = Snapshots {
+            output: output.clone(),
+            taken: vec![],
+        };
+        Incremental {
+            gs,
+            output,
+            start,
+            code: vec![],
+            snapshots: Rc::new(RefCell::new(snapshots)),
+        }
+    }
+
+    /// Run `code` as if from the start, but resuming from the last snapshot
+    /// the previous version's unchanged tokens left, if there is one.
+    /// Code that doesn't parse isn't run, and leaves everything as it was.
+    pub fn run(&mut self, code: &[u8]) -> Rerun {
+        let starts: Vec<usize> = match parse_program(code) {
+            Ok(tokens) => tokens.iter().map(|t| token_start(code, t)).collect(),
+            Err(e) => {
+                return Rerun {
+                    result: Err(e),
+                    resumed_at: None,
+                }
+            }
+        };
+        let (resumed_at, snapshot) = {
+            let mut snapshots = self.snapshots.borrow_mut();
+            let reusable = self.reusable(code, &starts, &snapshots.taken);
+            // The snapshot resumed from is taken again when its token runs.
+            snapshots.taken.truncate(reusable);
+            snapshots
+                .taken
+                .pop()
+                .unwrap_or_else(|| (0, self.start.clone()))
+        };
+        snapshot.restore(&mut self.gs, &self.output);
+        self.code = code.to_vec();
+        self.gs.set_hook(Some(Box::new(self.snapshots.clone())));
+        let result = self.gs.resume(code, resumed_at);
+        self.gs.set_hook(None);
+        Rerun {
+            result,
+            resumed_at: Some(resumed_at),
+        }
+    }
+
+    /// How many of `taken` come before tokens that are all the same in
+    /// `code`: those at offsets in the part it shares with the last version
+    /// that `starts`, where its tokens start, has too.
+    fn reusable(&self, code: &[u8], starts: &[usize], taken: &[(usize, Snapshot)]) -> usize {
+        let shared = self
+            .code
+            .iter()
+            .zip(code)
+            .take_while(|(a, b)| a == b)
+            .count();
+        taken
+            .iter()
+            .take_while(|(at, _)| *at <= shared && starts.binary_search(at).is_ok())
+            .count()
+    }
+
+    /// What the last run printed, from the start of the program, and since
+    /// then with [`Incremental::gs`].
+    pub fn output(&self) -> Vec<u8> {
+        self.output.0.borrow().clone()
+    }
+
+    /// The interpreter, as the last run left it. Running more code on it,
+    /// like printing the stack, doesn't change what the next run resumes
+    /// from.
+    pub fn gs(&mut self) -> &mut Gs {
+        &mut self.gs
+    }
+}
+
+/// Where `token` starts in `code`, including an assignment's `:` and a
+/// block's `{`.
+fn token_start(code: &[u8], token: &Gtoken) -> usize {
+    let start = offset(code, token.lexeme());
+    match token {
+        Gtoken::Assignment(_) | Gtoken::Block(..) => start - 1,
+        _ => start,
+    }
+}
+
+impl Gs {
+    /// Run `code` as `run` would, but only its tokens from offset `from`
+    /// on, as if those before had run.
+    fn resume(&mut self, code: &[u8], from: usize) -> GsResult<()> {
+        self.enter(code, |gs| {
+            let mut tokens = parse_program(code)?;
+            tokens.retain(|t| token_start(code, t) >= from);
+            gs.run_parsed_tokens(code, tokens)
+        })
+    }
+}
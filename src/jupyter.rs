@@ -0,0 +1,564 @@
+//! A Jupyter kernel: cells run one after another on the same stack and
+//! variables, as lines do in [`crate::repl`], and each shows the stack after
+//! it as plain text and as an HTML table. A cell that fails is undone.
+//!
+//! Jupyter talks to a kernel over five ZeroMQ sockets whose ports are in a
+//! connection file; [`zmtp`] speaks just enough of ZeroMQ for that, so the
+//! kernel doesn't need libzmq. Only TCP and HMAC-SHA256 signatures are
+//! supported, which is what Jupyter uses by default.
+
+use crate::builtins::reference;
+use crate::highlight::escape_html;
+use crate::parse::{completeness, Completeness};
+use crate::trace::render;
+use crate::value::Gval;
+use crate::Gs;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use zmtp::Connection;
+
+mod zmtp;
+
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+const PROTOCOL_VERSION: &str = "5.3";
+
+/// What cells print, kept for the kernel to send on as a stream.
+#[derive(Clone, Default)]
+pub struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// HMAC-SHA256 of `parts`, to sign a message with or check its signature.
+fn hmac(key: &[u8], parts: &[&[u8]]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+/// Decode a signature's hex digits, of either case.
+fn unhex(hex: &[u8]) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    let digit = |b: u8| (b as char).to_digit(16);
+    hex.chunks(2)
+        .map(|d| Some((digit(d[0])? << 4 | digit(d[1])?) as u8))
+        .collect()
+}
+
+/// The time now in ISO 8601, as message headers want it.
+fn now() -> String {
+    let since = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since.as_secs();
+    // Days since 1970-01-01 to a civil date, after Howard Hinnant.
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        since.subsec_micros()
+    )
+}
+
+/// A request from a shell or control socket, and the connection to reply on.
+struct Request {
+    frames: Vec<Vec<u8>>,
+    reply: Connection,
+}
+
+/// A message, split into its parts.
+struct Message {
+    identities: Vec<Vec<u8>>,
+    header: Value,
+    content: Value,
+}
+
+struct Kernel {
+    key: Vec<u8>,
+    session: String,
+    messages: u64,
+    subscribers: Arc<Mutex<Vec<Connection>>>,
+    execution_count: u64,
+}
+
+impl Kernel {
+    fn parse(&self, frames: Vec<Vec<u8>>) -> io::Result<Message> {
+        let split = frames
+            .iter()
+            .position(|f| f == DELIMITER)
+            .ok_or_else(|| invalid("no <IDS|MSG> delimiter".to_string()))?;
+        let (identities, rest) = frames.split_at(split);
+        if rest.len() < 6 {
+            return Err(invalid("too few frames".to_string()));
+        }
+        if !self.key.is_empty() {
+            let parts: Vec<&[u8]> = rest[2..6].iter().map(Vec::as_slice).collect();
+            // Compared in constant time, so timing doesn't leak the signature.
+            let signature = unhex(&rest[1]).unwrap_or_default();
+            if hmac(&self.key, &parts).verify_slice(&signature).is_err() {
+                return Err(invalid("bad signature".to_string()));
+            }
+        }
+        let json = |frame: &[u8]| serde_json::from_slice(frame).map_err(|e| invalid(e.to_string()));
+        Ok(Message {
+            identities: identities.to_vec(),
+            header: json(&rest[2])?,
+            content: json(&rest[5])?,
+        })
+    }
+
+    /// The frames of a message of type `kind` replying to `parent`, after
+    /// `prefix`.
+    fn frames(
+        &mut self,
+        prefix: Vec<Vec<u8>>,
+        kind: &str,
+        parent: &Value,
+        content: Value,
+    ) -> Vec<Vec<u8>> {
+        self.messages += 1;
+        let header = json!({
+            "msg_id": format!("{}-{}", self.session, self.messages),
+            "session": self.session,
+            "username": "kernel",
+            "date": now(),
+            "msg_type": kind,
+            "version": PROTOCOL_VERSION,
+        });
+        let parts = [
+            header.to_string().into_bytes(),
+            parent.to_string().into_bytes(),
+            b"{}".to_vec(),
+            content.to_string().into_bytes(),
+        ];
+        let signature = match self.key.is_empty() {
+            true => String::new(),
+            false => {
+                let parts: Vec<&[u8]> = parts.iter().map(Vec::as_slice).collect();
+                let mac = hmac(&self.key, &parts).finalize().into_bytes();
+                mac.iter().map(|b| format!("{:02x}", b)).collect()
+            }
+        };
+        let mut frames = prefix;
+        frames.push(DELIMITER.to_vec());
+        frames.push(signature.into_bytes());
+        frames.extend(parts);
+        frames
+    }
+
+    fn reply(&mut self, request: &mut Request, message: &Message, kind: &str, content: Value) {
+        let frames = self.frames(message.identities.clone(), kind, &message.header, content);
+        if let Err(e) = request.reply.send(&frames) {
+            eprintln!("kernel: can't reply: {}", e);
+        }
+    }
+
+    /// Send a message to every iopub subscriber, forgetting any that are
+    /// gone.
+    fn publish(&mut self, message: &Message, kind: &str, content: Value) {
+        let topic = format!("kernel.{}.{}", self.session, kind).into_bytes();
+        let frames = self.frames(vec![topic], kind, &message.header, content);
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain_mut(|s| s.send(&frames).is_ok());
+    }
+
+    fn status(&mut self, message: &Message, state: &str) {
+        self.publish(message, "status", json!({ "execution_state": state }));
+    }
+
+    /// Handle `request`, returning whether the kernel should shut down.
+    fn handle(&mut self, gs: &mut Gs, output: &Output, mut request: Request) -> bool {
+        let frames = std::mem::take(&mut request.frames);
+        let message = match self.parse(frames) {
+            Ok(message) => message,
+            Err(e) => {
+                eprintln!("kernel: ignoring a message: {}", e);
+                return false;
+            }
+        };
+        let kind = message.header["msg_type"]
+            .as_str()
+            .unwrap_or("")
+            .to_string();
+        let reply_kind = match kind.strip_suffix("_request") {
+            Some(base) => format!("{}_reply", base),
+            None => return false,
+        };
+        self.status(&message, "busy");
+        let content = &message.content;
+        let reply = match kind.as_str() {
+            "kernel_info_request" => json!({
+                "status": "ok",
+                "protocol_version": PROTOCOL_VERSION,
+                "implementation": "golfscript-rs",
+                "implementation_version": env!("CARGO_PKG_VERSION"),
+                "language_info": {
+                    "name": "golfscript",
+                    "version": "",
+                    "mimetype": "text/x-golfscript",
+                    "file_extension": ".gs",
+                },
+                "banner": "GolfScript",
+                "help_links": [],
+            }),
+            "execute_request" => self.execute(gs, output, &message),
+            "is_complete_request" => {
+                let code = content["code"].as_str().unwrap_or("").as_bytes();
+                let status = match completeness(code) {
+                    Completeness::Complete => "complete",
+                    Completeness::Incomplete => "incomplete",
+                    Completeness::Invalid => "invalid",
+                };
+                match status {
+                    "incomplete" => json!({ "status": status, "indent": "" }),
+                    _ => json!({ "status": status }),
+                }
+            }
+            "complete_request" => complete(gs, content),
+            "inspect_request" => {
+                let found = word_at(content).and_then(|(_, _, word)| reference(&word));
+                json!({
+                    "status": "ok",
+                    "found": found.is_some(),
+                    "data": match &found {
+                        Some(text) => json!({ "text/plain": text }),
+                        None => json!({}),
+                    },
+                    "metadata": {},
+                })
+            }
+            "comm_info_request" => json!({ "status": "ok", "comms": {} }),
+            "history_request" => json!({ "status": "ok", "history": [] }),
+            "shutdown_request" => json!({
+                "status": "ok",
+                "restart": content["restart"].as_bool().unwrap_or(false),
+            }),
+            _ => {
+                json!({ "status": "error", "ename": "unsupported", "evalue": kind, "traceback": [] })
+            }
+        };
+        self.reply(&mut request, &message, &reply_kind, reply);
+        self.status(&message, "idle");
+        kind == "shutdown_request"
+    }
+
+    /// Run a cell, publishing what it printed and the stack after it, and
+    /// return the reply.
+    fn execute(&mut self, gs: &mut Gs, output: &Output, message: &Message) -> Value {
+        let code = message.content["code"].as_str().unwrap_or("").to_string();
+        let silent = message.content["silent"].as_bool().unwrap_or(false);
+        if !silent {
+            self.execution_count += 1;
+            self.publish(
+                message,
+                "execute_input",
+                json!({ "code": code, "execution_count": self.execution_count }),
+            );
+        }
+        let code = code.as_bytes();
+        let snapshot = (gs.stack.clone(), gs.vars.clone(), gs.lb.clone());
+        let result = gs.run(code).and_then(|()| gs.flush());
+        let _ = gs.flush();
+        let printed = output.0.take();
+        if !printed.is_empty() && !silent {
+            self.publish(
+                message,
+                "stream",
+                json!({ "name": "stdout", "text": String::from_utf8_lossy(&printed) }),
+            );
+        }
+        let count = self.execution_count;
+        match result {
+            Err(e) => {
+                let mut traceback = vec![format!("error: {}", e)];
+                if !gs.trace.is_empty() {
+                    traceback.extend(render(code, &gs.trace).lines().map(str::to_string));
+                }
+                traceback.push("(the cell was undone)".to_string());
+                gs.trace.clear();
+                (gs.stack, gs.vars, gs.lb) = snapshot;
+                let error = json!({
+                    "ename": e.code(),
+                    "evalue": e.to_string(),
+                    "traceback": traceback,
+                });
+                if !silent {
+                    self.publish(message, "error", error.clone());
+                }
+                let mut reply = error;
+                reply["status"] = json!("error");
+                reply["execution_count"] = json!(count);
+                reply
+            }
+            Ok(()) => {
+                if !silent {
+                    let result = json!({
+                        "execution_count": count,
+                        "data": stack_data(&gs.stack),
+                        "metadata": {},
+                    });
+                    self.publish(message, "execute_result", result);
+                }
+                json!({
+                    "status": "ok",
+                    "execution_count": count,
+                    "user_expressions": {},
+                    "payload": [],
+                })
+            }
+        }
+    }
+}
+
+/// The stack as plain text, like the REPL shows it, and as an HTML table
+/// with the top at the top.
+fn stack_data(stack: &[Gval]) -> Value {
+    let plain = Gval::Arr(stack.to_vec()).inspect();
+    let mut html = String::from("<table>\n<tr><th></th><th>type</th><th>value</th></tr>\n");
+    for (depth, v) in stack.iter().rev().enumerate() {
+        let kind = match v {
+            Gval::Int(_) => "int",
+            Gval::Arr(_) => "array",
+            Gval::Str(_) => "string",
+            Gval::Blk(_) => "block",
+        };
+        let text = escape_html(&String::from_utf8_lossy(&v.clone().inspect()));
+        let label = if depth == 0 { "top" } else { "" };
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td><code>{}</code></td></tr>\n",
+            label, kind, text
+        ));
+    }
+    html.push_str("</table>");
+    json!({ "text/plain": String::from_utf8_lossy(&plain), "text/html": html })
+}
+
+/// The word around the cursor in a complete or inspect request, as its
+/// start and end in code points, and the word. An operator character counts
+/// as a word on its own.
+fn word_at(content: &Value) -> Option<(usize, usize, String)> {
+    let code: Vec<char> = content["code"].as_str()?.chars().collect();
+    let cursor = (content["cursor_pos"].as_u64()? as usize).min(code.len());
+    let is_word = |c: &char| c.is_ascii_alphanumeric() || *c == '_';
+    let start = cursor
+        - code[..cursor]
+            .iter()
+            .rev()
+            .take_while(|c| is_word(c))
+            .count();
+    let end = cursor + code[cursor..].iter().take_while(|c| is_word(c)).count();
+    match (start, end) {
+        (start, end) if start < end => Some((start, end, code[start..end].iter().collect())),
+        _ if cursor > 0 && !code[cursor - 1].is_whitespace() => {
+            Some((cursor - 1, cursor, code[cursor - 1].to_string()))
+        }
+        _ => None,
+    }
+}
+
+/// Builtin words and variables starting with the word before the cursor.
+fn complete(gs: &Gs, content: &Value) -> Value {
+    let cursor = content["cursor_pos"].as_u64().unwrap_or(0) as usize;
+    let (start, prefix) = match word_at(content) {
+        Some((start, _, word)) if word.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+            let typed = cursor.saturating_sub(start);
+            (start, word.chars().take(typed).collect::<String>())
+        }
+        _ => (cursor, String::new()),
+    };
+    let mut matches: Vec<String> = gs
+        .dialect()
+        .builtins()
+        .map(|b| b.name.to_string())
+        .chain(
+            gs.vars
+                .keys()
+                .map(|k| String::from_utf8_lossy(k).into_owned()),
+        )
+        .filter(|name| name.starts_with(&prefix) && name.starts_with(char::is_alphabetic))
+        .collect();
+    matches.sort();
+    matches.dedup();
+    json!({
+        "status": "ok",
+        "matches": matches,
+        "cursor_start": start,
+        "cursor_end": cursor,
+        "metadata": {},
+    })
+}
+
+fn bind(ip: &str, config: &Value, name: &str) -> io::Result<TcpListener> {
+    let port = config[name]
+        .as_u64()
+        .ok_or_else(|| invalid(format!("the connection file has no {}", name)))?;
+    TcpListener::bind((ip, port as u16))
+}
+
+/// Accept connections on `listener` as `socket_type`, handling each on a
+/// thread of its own.
+fn listen(
+    listener: TcpListener,
+    socket_type: &'static str,
+    handle: impl Fn(Connection) + Send + Clone + 'static,
+) {
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let handle = handle.clone();
+            std::thread::spawn(move || match Connection::accept(stream, socket_type) {
+                Ok(connection) => handle(connection),
+                Err(e) => eprintln!("kernel: {}", e),
+            });
+        }
+    });
+}
+
+/// Forward requests from a shell or control socket to the kernel.
+fn forward(requests: Sender<Request>) -> impl Fn(Connection) + Send + Clone + 'static {
+    move |mut connection: Connection| {
+        while let Ok(frames) = connection.receive() {
+            let Ok(reply) = connection.try_clone() else {
+                return;
+            };
+            if requests.send(Request { frames, reply }).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Serve the kernel described by the connection file at `path` until it's
+/// told to shut down, running cells on `gs`, which prints to `output`.
+pub fn serve(gs: &mut Gs, output: Output, path: &str) -> io::Result<()> {
+    let config: Value = serde_json::from_slice(&std::fs::read(path)?)
+        .map_err(|e| invalid(format!("{}: {}", path, e)))?;
+    if config["transport"].as_str().unwrap_or("tcp") != "tcp" {
+        return Err(invalid("only the tcp transport is supported".to_string()));
+    }
+    let scheme = config["signature_scheme"].as_str().unwrap_or("hmac-sha256");
+    if scheme != "hmac-sha256" {
+        return Err(invalid(format!("unsupported signature scheme {}", scheme)));
+    }
+    let ip = config["ip"].as_str().unwrap_or("127.0.0.1");
+    let (requests, received) = channel();
+    listen(
+        bind(ip, &config, "shell_port")?,
+        "ROUTER",
+        forward(requests.clone()),
+    );
+    listen(
+        bind(ip, &config, "control_port")?,
+        "ROUTER",
+        forward(requests),
+    );
+    let subscribers = Arc::new(Mutex::new(vec![]));
+    let iopub = subscribers.clone();
+    listen(bind(ip, &config, "iopub_port")?, "PUB", move |mut c| {
+        if let Ok(clone) = c.try_clone() {
+            iopub.lock().unwrap().push(clone);
+        }
+        // Subscriptions are ignored: everything goes to everyone.
+        while c.receive().is_ok() {}
+    });
+    listen(bind(ip, &config, "hb_port")?, "REP", |mut c| {
+        while let Ok(frames) = c.receive() {
+            if c.send(&frames).is_err() {
+                return;
+            }
+        }
+    });
+    // Cells can't read input, so requests for it never come.
+    listen(bind(ip, &config, "stdin_port")?, "ROUTER", |mut c| {
+        while c.receive().is_ok() {}
+    });
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut kernel = Kernel {
+        key: config["key"].as_str().unwrap_or("").as_bytes().to_vec(),
+        session: format!("{:x}-{:x}", std::process::id(), started.as_nanos()),
+        messages: 0,
+        subscribers,
+        execution_count: 0,
+    };
+    for request in received {
+        if kernel.handle(gs, &output, request) {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// Where Jupyter looks for the kernel spec: `$JUPYTER_DATA_DIR`, or the
+/// platform's user data directory.
+fn kernel_dir() -> Option<PathBuf> {
+    let data = match std::env::var_os("JUPYTER_DATA_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None if cfg!(windows) => PathBuf::from(std::env::var_os("APPDATA")?).join("jupyter"),
+        None if cfg!(target_os = "macos") => {
+            PathBuf::from(std::env::var_os("HOME")?).join("Library/Jupyter")
+        }
+        None => PathBuf::from(std::env::var_os("HOME")?).join(".local/share/jupyter"),
+    };
+    Some(data.join("kernels").join("golfscript"))
+}
+
+/// Write a kernel spec that runs this executable with `flags`, and return
+/// where it went.
+pub fn install(flags: &[String]) -> io::Result<PathBuf> {
+    let dir = kernel_dir().ok_or_else(|| invalid("can't find Jupyter's data directory".into()))?;
+    let exe = std::env::current_exe()?;
+    let mut argv = vec![exe.to_string_lossy().into_owned(), "kernel".to_string()];
+    argv.extend(flags.iter().cloned());
+    argv.extend([
+        "--connection-file".to_string(),
+        "{connection_file}".to_string(),
+    ]);
+    let spec = json!({
+        "argv": argv,
+        "display_name": "GolfScript",
+        "language": "golfscript",
+    });
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("kernel.json");
+    std::fs::write(&path, serde_json::to_string_pretty(&spec)? + "\n")?;
+    Ok(path)
+}
@@ -0,0 +1,115 @@
+//! Just enough of ZMTP 3.0, ZeroMQ's wire protocol, to be the listening end
+//! of Jupyter's sockets over TCP: the greeting, the NULL security mechanism
+//! and multipart messages. There's no reconnecting or queueing; a ROUTER
+//! replies on the connection a message came in on, and a PUB sends every
+//! message to every subscriber.
+
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+const MORE: u8 = 1;
+const LONG: u8 = 2;
+const COMMAND: u8 = 4;
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+pub struct Connection {
+    stream: TcpStream,
+}
+
+impl Connection {
+    /// Greet a peer that connected and agree to talk as `socket_type`, like
+    /// `ROUTER`.
+    pub fn accept(stream: TcpStream, socket_type: &str) -> io::Result<Connection> {
+        let mut connection = Connection { stream };
+        let mut greeting = vec![0xff, 0, 0, 0, 0, 0, 0, 0, 1, 0x7f, 3, 0];
+        greeting.extend(b"NULL");
+        greeting.resize(64, 0);
+        connection.stream.write_all(&greeting)?;
+        let mut theirs = [0; 64];
+        connection.stream.read_exact(&mut theirs[..10])?;
+        if theirs[0] != 0xff || theirs[9] != 0x7f {
+            return Err(invalid("not a ZeroMQ peer"));
+        }
+        connection.stream.read_exact(&mut theirs[10..])?;
+        if theirs[10] < 3 || !theirs[12..].starts_with(b"NULL\0") {
+            return Err(invalid("needs ZMTP 3 with no security"));
+        }
+        let mut ready = vec![5];
+        ready.extend(b"READY");
+        ready.push(11);
+        ready.extend(b"Socket-Type");
+        ready.extend((socket_type.len() as u32).to_be_bytes());
+        ready.extend(socket_type.as_bytes());
+        connection.write_frame(&ready, COMMAND)?;
+        let (flags, body) = connection.read_frame()?;
+        if flags & COMMAND == 0 || !body.starts_with(b"\x05READY") {
+            return Err(invalid("expected READY"));
+        }
+        Ok(connection)
+    }
+
+    pub fn try_clone(&self) -> io::Result<Connection> {
+        Ok(Connection {
+            stream: self.stream.try_clone()?,
+        })
+    }
+
+    fn read_frame(&mut self) -> io::Result<(u8, Vec<u8>)> {
+        let mut flags = [0];
+        self.stream.read_exact(&mut flags)?;
+        let len = if flags[0] & LONG != 0 {
+            let mut len = [0; 8];
+            self.stream.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        } else {
+            let mut len = [0];
+            self.stream.read_exact(&mut len)?;
+            len[0] as u64
+        };
+        let mut body = vec![];
+        (&mut self.stream).take(len).read_to_end(&mut body)?;
+        if body.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok((flags[0], body))
+    }
+
+    fn write_frame(&mut self, body: &[u8], flags: u8) -> io::Result<()> {
+        let mut frame = Vec::with_capacity(body.len() + 9);
+        if body.len() > 255 {
+            frame.push(flags | LONG);
+            frame.extend((body.len() as u64).to_be_bytes());
+        } else {
+            frame.push(flags);
+            frame.push(body.len() as u8);
+        }
+        frame.extend(body);
+        self.stream.write_all(&frame)
+    }
+
+    /// The next message's frames, skipping commands.
+    pub fn receive(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        let mut frames = vec![];
+        loop {
+            let (flags, body) = self.read_frame()?;
+            if flags & COMMAND != 0 {
+                continue;
+            }
+            frames.push(body);
+            if flags & MORE == 0 {
+                return Ok(frames);
+            }
+        }
+    }
+
+    pub fn send(&mut self, frames: &[Vec<u8>]) -> io::Result<()> {
+        for (i, frame) in frames.iter().enumerate() {
+            let more = if i + 1 < frames.len() { MORE } else { 0 };
+            self.write_frame(frame, more)?;
+        }
+        self.stream.flush()
+    }
+}
@@ -0,0 +1,354 @@
+//! An interactive tutorial: short lessons, each followed by exercises that
+//! the learner answers with a line of GolfScript. Answers run on the real
+//! interpreter and are checked against the stack and output each exercise
+//! expects, on every case it lists, so any answer that works is accepted.
+
+use crate::parse::significant_tokens;
+use crate::trace::render;
+use crate::util::Buffer;
+use crate::value::Gval;
+use crate::Gs;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+pub struct Lesson {
+    pub title: &'static str,
+    pub text: &'static str,
+    pub exercises: &'static [Exercise],
+}
+
+pub struct Exercise {
+    pub prompt: &'static str,
+    /// Tokens an answer must use, for exercises about them.
+    pub requires: &'static [&'static str],
+    pub cases: &'static [Case],
+    pub hints: &'static [&'static str],
+    pub solution: &'static str,
+}
+
+/// A way an answer is run, and what it must leave.
+pub struct Case {
+    /// Code run first, to set up the stack.
+    pub setup: &'static str,
+    /// The stack the answer must leave, as the REPL shows it.
+    pub stack: &'static str,
+    /// What the answer must print.
+    pub output: &'static str,
+}
+
+const fn case(setup: &'static str, stack: &'static str) -> Case {
+    Case {
+        setup,
+        stack,
+        output: "",
+    }
+}
+
+pub const LESSONS: &[Lesson] = &[
+    Lesson {
+        title: "The stack",
+        text: "A GolfScript program is a row of tokens run left to right. A number \
+               pushes itself onto the stack, and when the program ends, whatever is \
+               on the stack is printed. `1 2` leaves 1 with 2 on top of it.",
+        exercises: &[Exercise {
+            prompt: "Leave 1, 2 and 3 on the stack, with 3 on top.",
+            requires: &[],
+            cases: &[case("", "[1 2 3]")],
+            hints: &["Numbers are pushed in the order they're written."],
+            solution: "1 2 3",
+        }],
+    },
+    Lesson {
+        title: "Arithmetic",
+        text: "Operators pop their operands and push a result. `+`, `-`, `*`, `/` and \
+               `%` work on the top two values, with the top one on the right: `7 2-` \
+               is 5. Division rounds down.",
+        exercises: &[
+            Exercise {
+                prompt: "Leave 6 times 7.",
+                requires: &["*"],
+                cases: &[case("", "[42]")],
+                hints: &["Push both numbers, then multiply: the operator comes last."],
+                solution: "6 7*",
+            },
+            Exercise {
+                prompt: "Leave 2 minus 5.",
+                requires: &["-"],
+                cases: &[case("", "[-3]")],
+                hints: &["The value pushed first is on the left."],
+                solution: "2 5-",
+            },
+        ],
+    },
+    Lesson {
+        title: "Moving values around",
+        text: "`.` copies the top value, `\\` swaps the top two, `;` drops the top one \
+               and `@` brings the third from the top up to the top.",
+        exercises: &[
+            Exercise {
+                prompt: "The stack has a number on it. Replace it with its square.",
+                requires: &["."],
+                cases: &[case("9", "[81]"), case("-4", "[16]")],
+                hints: &["Copy it, then multiply the copies."],
+                solution: ".*",
+            },
+            Exercise {
+                prompt: "The stack has two numbers on it. Leave the lower one minus the \
+                         one on top, then the same again the other way round.",
+                requires: &["\\"],
+                cases: &[case("5 3", "[2 -2]"), case("1 10", "[-9 9]")],
+                hints: &[
+                    "Copy both first: `.` copies one value, but `1$` copies the second \
+                     from the top.",
+                    "After subtracting, bring the other pair back with `@` and swap it.",
+                ],
+                solution: "1$1$-@@\\-",
+            },
+        ],
+    },
+    Lesson {
+        title: "Strings and arrays",
+        text: "`\"text\"` pushes a string and `[1 2 3]` an array: `[` and `]` collect \
+               whatever is pushed between them. `,` gives a string's or array's \
+               length, `+` joins two of them, and `,` on a number n makes the array of \
+               0 up to n-1.",
+        exercises: &[
+            Exercise {
+                prompt: "Leave the length of the string \"golf\".",
+                requires: &[","],
+                cases: &[case("", "[4]")],
+                hints: &["Push the string, then take its length."],
+                solution: "\"golf\",",
+            },
+            Exercise {
+                prompt: "Leave the array [0 1 2 3 4], without writing it out.",
+                requires: &[","],
+                cases: &[case("", "[[0 1 2 3 4]]")],
+                hints: &["`,` on a number counts up to it."],
+                solution: "5,",
+            },
+        ],
+    },
+    Lesson {
+        title: "Blocks",
+        text: "`{...}` pushes a block of code without running it; `~` runs it. \
+               Operators take blocks too: an array and a block with `%` runs the \
+               block on each element and collects the results, and with `*` folds \
+               the block between the elements.",
+        exercises: &[
+            Exercise {
+                prompt: "The stack has an array of numbers. Square each of them.",
+                requires: &["%"],
+                cases: &[case("[1 2 3]", "[[1 4 9]]"), case("[]", "[[]]")],
+                hints: &[
+                    "Square a number with `.*`.",
+                    "Put that in a block and map it with `%`.",
+                ],
+                solution: "{.*}%",
+            },
+            Exercise {
+                prompt: "The stack has an array of numbers. Leave their sum.",
+                requires: &["*"],
+                cases: &[case("[1 2 3 4]", "[10]"), case("[5]", "[5]")],
+                hints: &["Fold `+` between the elements with `*`."],
+                solution: "{+}*",
+            },
+        ],
+    },
+    Lesson {
+        title: "Input and output",
+        text: "A program starts with its input on the stack, as a string. `~` on a \
+               string runs it as code, which turns \"21\" into 21. `puts` prints a \
+               value and a newline, and `print` prints it alone.",
+        exercises: &[
+            Exercise {
+                prompt: "The input is a number. Leave twice it.",
+                requires: &["~"],
+                cases: &[case("\"21\"", "[42]"), case("\"-5\"", "[-10]")],
+                hints: &["Turn the string into a number with `~` first."],
+                solution: "~2*",
+            },
+            Exercise {
+                prompt: "Print hello on a line of its own, leaving the stack empty.",
+                requires: &["puts"],
+                cases: &[Case {
+                    setup: "",
+                    stack: "[]",
+                    output: "hello\n",
+                }],
+                hints: &["`puts` pops what it prints."],
+                solution: "\"hello\"puts",
+            },
+        ],
+    },
+    Lesson {
+        title: "Variables",
+        text: "`:name` stores the top value in a variable without popping it, and \
+               `name` pushes it again. Any token can be a variable, even `+`, but \
+               names are usually letters.",
+        exercises: &[Exercise {
+            prompt: "The stack has a number on it. Store it in x, drop it, and leave x \
+                     times x plus x.",
+            requires: &[":x"],
+            cases: &[case("3", "[12]"), case("0", "[0]")],
+            hints: &[
+                "`:x;` stores and drops.",
+                "Then push x as often as you need it.",
+            ],
+            solution: ":x;x x*x+",
+        }],
+    },
+    Lesson {
+        title: "Choices and loops",
+        text: "`if` pops a condition and two values and keeps the first if the \
+               condition is true, otherwise the second; 0, empty strings, arrays and \
+               blocks are false. A value kept that's a block is run. `{...}do` runs \
+               a block, then again while it leaves something true.",
+        exercises: &[
+            Exercise {
+                prompt: "The stack has a number on it. Leave \"even\" or \"odd\".",
+                requires: &["if"],
+                cases: &[case("4", "[\"even\"]"), case("7", "[\"odd\"]")],
+                hints: &[
+                    "`2%` is 0 for even numbers and 1 for odd ones.",
+                    "0 is false, so the value for odd numbers comes first.",
+                ],
+                solution: "2%\"odd\"\"even\"if",
+            },
+            Exercise {
+                prompt: "The stack has a positive number n on it. Leave n, n-1, ..., 1.",
+                requires: &["do"],
+                cases: &[case("3", "[3 2 1]"), case("1", "[1]")],
+                hints: &[
+                    "`(` subtracts 1.",
+                    "Copy the number, take 1 from the copy, and loop while it's not 0.",
+                ],
+                solution: "{.(.}do;",
+            },
+        ],
+    },
+];
+
+/// How many steps, and how long, an answer may take.
+const MAX_STEPS: u64 = 100_000;
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// What running an answer on a case left, or why it failed.
+fn attempt(case: &Case, answer: &[u8]) -> Result<(String, String), String> {
+    let buffer = Buffer::default();
+    let mut gs = Gs::builder()
+        .max_steps(MAX_STEPS)
+        .timeout(TIMEOUT)
+        .input(Box::new(io::empty()))
+        .output(Box::new(buffer.clone()))
+        .build();
+    gs.run(case.setup.as_bytes())
+        .map_err(|e| format!("the setup failed: {}", e))?;
+    if let Err(e) = gs.run(answer).and_then(|()| gs.flush()) {
+        let mut message = format!("error: {}", e);
+        if !gs.trace.is_empty() {
+            message = format!("{}\n{}", message, render(answer, &gs.trace));
+        }
+        return Err(message);
+    }
+    let stack = Gval::Arr(std::mem::take(&mut gs.stack)).inspect();
+    let output = buffer.0.take();
+    Ok((
+        String::from_utf8_lossy(&stack).into_owned(),
+        String::from_utf8_lossy(&output).into_owned(),
+    ))
+}
+
+/// Why `answer` isn't right, or `None` if it is.
+fn check(exercise: &Exercise, answer: &[u8]) -> Option<String> {
+    let tokens = significant_tokens(answer, false).unwrap_or_default();
+    for required in exercise.requires {
+        if !tokens.iter().any(|t| t == required.as_bytes()) {
+            return Some(format!(
+                "this exercise is about `{}`; try using it",
+                required
+            ));
+        }
+    }
+    for case in exercise.cases {
+        let starting = match case.setup {
+            "" => String::new(),
+            setup => format!(" starting with {}", setup),
+        };
+        match attempt(case, answer) {
+            Err(e) => return Some(format!("{}{}", e, starting)),
+            Ok((stack, _)) if stack != case.stack => {
+                return Some(format!(
+                    "that leaves {}{}, but it should leave {}",
+                    stack, starting, case.stack
+                ))
+            }
+            Ok((_, output)) if output != case.output => {
+                return Some(format!(
+                    "that prints {:?}{}, but it should print {:?}",
+                    output, starting, case.output
+                ))
+            }
+            Ok(_) => {}
+        }
+    }
+    None
+}
+
+/// Run the tutorial from lesson `first` (1-based), reading answers from
+/// `input` and writing to `output`. Besides answers, a line can be `hint`,
+/// `show` for a solution, `skip` or `quit`.
+pub fn run(first: usize, mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    writeln!(
+        output,
+        "Answer each exercise with a line of GolfScript. Type `hint` for a hint, \
+         `show` to see an answer, `skip` to move on or `quit` to stop."
+    )?;
+    for (l, lesson) in LESSONS.iter().enumerate().skip(first.saturating_sub(1)) {
+        writeln!(output)?;
+        writeln!(
+            output,
+            "Lesson {} of {}: {}",
+            l + 1,
+            LESSONS.len(),
+            lesson.title
+        )?;
+        writeln!(output, "{}", lesson.text)?;
+        for (e, exercise) in lesson.exercises.iter().enumerate() {
+            writeln!(output)?;
+            writeln!(output, "Exercise {}.{}: {}", l + 1, e + 1, exercise.prompt)?;
+            let mut hints = exercise.hints.iter();
+            loop {
+                write!(output, "> ")?;
+                output.flush()?;
+                let mut line = String::new();
+                if input.read_line(&mut line)? == 0 {
+                    writeln!(output)?;
+                    return Ok(());
+                }
+                match line.trim() {
+                    "" => continue,
+                    "quit" => return Ok(()),
+                    "skip" => break,
+                    "hint" => match hints.next() {
+                        Some(hint) => writeln!(output, "hint: {}", hint)?,
+                        None => writeln!(output, "no more hints; `show` shows an answer")?,
+                    },
+                    "show" => writeln!(output, "one answer: {}", exercise.solution)?,
+                    answer => match check(exercise, answer.as_bytes()) {
+                        Some(problem) => writeln!(output, "not yet: {}", problem)?,
+                        None => {
+                            writeln!(output, "right!")?;
+                            break;
+                        }
+                    },
+                }
+            }
+        }
+    }
+    writeln!(output)?;
+    writeln!(
+        output,
+        "That's all the lessons. `golfscript-rs doc` lists every builtin."
+    )
+}
@@ -0,0 +1,1855 @@
+//! A GolfScript interpreter. Build a [`Gs`] with [`Gs::builder`], push the
+//! input, and [`Gs::run`] the code.
+
+use crate::coerce::flatten;
+use crate::parse::{offset, parse_program, snippet};
+use crate::util::chunk;
+use crate::util::every_nth;
+use crate::util::index;
+use crate::util::slice;
+use crate::util::split;
+use crate::util::string_index;
+use num::BigInt;
+use num::Integer;
+use num::One;
+use num::Signed;
+use num::ToPrimitive;
+use num::Zero;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::BufWriter;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+pub mod audit;
+pub mod builtins;
+mod coerce;
+pub mod debug;
+pub mod diagnostic;
+pub mod error;
+pub mod expect;
+pub mod ext;
+pub mod formatter;
+pub mod generate;
+pub mod golf;
+pub mod graph;
+pub mod highlight;
+pub mod hook;
+pub mod incremental;
+#[cfg(feature = "jupyter")]
+pub mod jupyter;
+pub mod learn;
+pub mod limits;
+pub mod lint;
+pub mod lsp;
+pub mod minify;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+pub mod parse;
+pub mod postmortem;
+pub mod pretty;
+pub mod profile;
+pub mod pseudocode;
+pub mod record;
+pub mod reference;
+pub mod repl;
+pub mod rng;
+pub mod score;
+pub mod state;
+pub mod stats;
+pub mod timeline;
+pub mod trace;
+pub mod transpile;
+mod unescape;
+mod unicode;
+pub mod utf8;
+mod util;
+pub mod value;
+
+use crate::audit::Audit;
+use crate::builtins::{arity, lookup, Dialect};
+use crate::coerce::{coerce, Coerced};
+use crate::diagnostic::{Diagnostic, DiagnosticFormat};
+use crate::error::{catch_panics, type_error, GsError, GsResult, TypeError, Underflow};
+use crate::ext::{permission_denied, Permissions};
+use crate::hook::{Hook, Step};
+use crate::limits::{Limits, Size};
+use crate::parse::Gtoken;
+use crate::postmortem::PostMortem;
+use crate::record::Journal;
+use crate::rng::{entropy_seed, Rng};
+use crate::trace::{find_literal, Frame};
+use crate::unescape::unescape;
+use crate::unicode::{by_chars, char_at, continues_char, decode, encode, flatten_chars, scalar};
+use crate::util::{set_and, set_or, set_subtract, set_xor};
+use crate::value::Gval;
+
+fn division_by_zero() -> GsError {
+    GsError::Runtime("division by zero".into())
+}
+
+fn mismatch(a: Gval, b: Gval) -> GsError {
+    type_error(format!(
+        "no meaning for {} and {}",
+        a.describe(),
+        b.describe()
+    ))
+}
+
+fn empty_sequence(op: &str) -> GsError {
+    GsError::Runtime(format!("`{}` on an empty sequence", op))
+}
+
+fn output_error(e: std::io::Error) -> GsError {
+    GsError::Runtime(format!("can't write output: {}", e))
+}
+
+/// Builtins that the reference interpreter defines in GolfScript itself, so
+/// programs can inspect or redefine them (e.g. changing `n` changes `puts`).
+const BUILTIN_DEFINITIONS: &[u8] = br#""
+":n;
+{print n print}:puts;
+{`puts}:p;
+{1$if}:and;
+{1$\if}:or;
+{\!!{!}*}:xor;
+"#;
+
+/// Helper definitions for `--prelude std`.
+pub const STD_PRELUDE: &[u8] = include_bytes!("prelude/std.gs");
+
+/// How deeply blocks and `~` may nest by default. Each level takes native
+/// stack, and this many fit in 8 MB with an optimized build.
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+/// How many elements an operation like `,` may build, or digits it may
+/// multiply, per step it's counted as.
+const WORK_PER_STEP: usize = 64;
+
+/// How many of the last tokens run a [`PostMortem`] shows, and how much of
+/// each.
+const RECENT_TOKENS: usize = 10;
+const RECENT_TOKEN_LEN: usize = 40;
+
+pub struct Gs {
+    pub stack: Vec<Gval>,
+    vars: HashMap<Vec<u8>, Gval>,
+    /// The variables as the builtin definitions left them.
+    builtin_vars: HashMap<Vec<u8>, Gval>,
+    lb: Vec<usize>,
+    rng: Rng,
+    extensions: bool,
+    compat: bool,
+    /// Whether to log every coercion (`--explain-coercions`).
+    explain_coercions: bool,
+    /// Whether to log every random draw (`--log-draws`), and how many there
+    /// have been.
+    log_draws: bool,
+    draws: u64,
+    /// Whether string operators work on Unicode scalar values (`--unicode`).
+    unicode: bool,
+    /// Whether popping an empty stack yields `""` instead of failing.
+    lenient: bool,
+    /// Whether `p` and the REPL pretty-print values (`--pretty`), and
+    /// whether pretty-printed values are colored.
+    pretty: bool,
+    color: bool,
+    started: Instant,
+    permissions: Permissions,
+    /// Where `gets` reads lines from.
+    input: Box<dyn BufRead>,
+    /// Where `print` writes; buffered, so flushed at exit and before reads.
+    out: Box<dyn Write>,
+    /// Where warnings and logs go, stderr unless the builder says otherwise.
+    /// Logging coercions only has `&self`, hence the `RefCell`.
+    log: RefCell<Box<dyn Write>>,
+    /// Bytes printed so far, and how many may be.
+    written: u64,
+    max_output: Option<u64>,
+    /// Files already run by `require`.
+    required: HashSet<PathBuf>,
+    /// Where the error being returned happened, innermost first.
+    trace: Vec<Frame>,
+    /// The state when the last outermost `run` failed, and the tokens that
+    /// led up to it, with their depths.
+    post_mortem: Option<PostMortem>,
+    recent: VecDeque<(usize, Vec<u8>)>,
+    /// Whether to warn about unknown symbols.
+    warnings: bool,
+    /// Set when the last token was an unknown symbol.
+    ignored: bool,
+    /// Unknown symbols already warned about.
+    warned: HashSet<Vec<u8>>,
+    /// How warnings are written, and the file they're about.
+    diagnostics: DiagnosticFormat,
+    file: Option<String>,
+    /// The code of the outermost `run` in progress, and how many are nested.
+    program: Vec<u8>,
+    depth: usize,
+    /// Called before each token, if set.
+    hook: Option<Box<dyn Hook>>,
+    /// What the run did, if it's being recorded.
+    journal: Option<Journal>,
+    /// What the run did outside the interpreter, if it's being audited.
+    audit: Option<Audit>,
+    /// Tokens run so far, and how many may run before giving up. Long
+    /// operations count as several steps, by the work they do.
+    steps: u64,
+    max_steps: Option<u64>,
+    /// Work done in the current step, up to [`WORK_PER_STEP`].
+    work: usize,
+    /// When to give up, and the time limit that set it.
+    deadline: Option<(Instant, Duration)>,
+    max_depth: usize,
+    /// How big values may grow.
+    limits: Limits,
+}
+
+impl Default for Gs {
+    fn default() -> Self {
+        Gs::new()
+    }
+}
+
+/// Options for a [`Gs`]. Everything defaults to the reference language's
+/// behavior, reading stdin and writing stdout.
+#[derive(Default)]
+pub struct GsBuilder {
+    extensions: bool,
+    compat: bool,
+    explain_coercions: bool,
+    log_draws: bool,
+    unicode: bool,
+    lenient: bool,
+    pretty: bool,
+    color: bool,
+    warnings: bool,
+    diagnostics: DiagnosticFormat,
+    file: Option<String>,
+    max_steps: Option<u64>,
+    timeout: Option<Duration>,
+    max_depth: Option<usize>,
+    max_output: Option<u64>,
+    limits: Limits,
+    permissions: Permissions,
+    rng: Option<Rng>,
+    input: Option<Box<dyn BufRead>>,
+    out: Option<Box<dyn Write>>,
+    log: Option<Box<dyn Write>>,
+}
+
+impl GsBuilder {
+    /// Recognize the builtins in [`ext`].
+    pub fn extensions(mut self, on: bool) -> Self {
+        self.extensions = on;
+        self
+    }
+
+    /// Coerce mixed-type comparisons like the reference interpreter.
+    pub fn compat(mut self, on: bool) -> Self {
+        self.compat = on;
+        self
+    }
+
+    /// Use the builtins and semantics of `dialect`, setting `extensions`
+    /// and `compat`.
+    pub fn dialect(self, dialect: Dialect) -> Self {
+        self.extensions(dialect.extensions())
+            .compat(dialect.compat())
+    }
+
+    /// Log every coercion of mismatched operands: the operator, and the
+    /// operands before and after.
+    pub fn explain_coercions(mut self, on: bool) -> Self {
+        self.explain_coercions = on;
+        self
+    }
+
+    /// Log every random draw: its index, the bound and the result.
+    pub fn log_draws(mut self, on: bool) -> Self {
+        self.log_draws = on;
+        self
+    }
+
+    /// Make string operators work on Unicode scalar values, not bytes.
+    pub fn unicode(mut self, on: bool) -> Self {
+        self.unicode = on;
+        self
+    }
+
+    /// Make popping an empty stack yield `""`, as if there were more empty
+    /// input, instead of failing with a stack underflow.
+    pub fn lenient(mut self, on: bool) -> Self {
+        self.lenient = on;
+        self
+    }
+
+    /// Make `p` pretty-print values like `pp`, and the REPL show the stack
+    /// that way.
+    pub fn pretty(mut self, on: bool) -> Self {
+        self.pretty = on;
+        self
+    }
+
+    /// Color pretty-printed values with ANSI escapes.
+    pub fn color(mut self, on: bool) -> Self {
+        self.color = on;
+        self
+    }
+
+    /// Warn in the log, once per name, about symbols that do nothing because
+    /// they're neither builtins nor defined.
+    pub fn warnings(mut self, on: bool) -> Self {
+        self.warnings = on;
+        self
+    }
+
+    /// How to write warnings.
+    pub fn diagnostics(mut self, format: DiagnosticFormat) -> Self {
+        self.diagnostics = format;
+        self
+    }
+
+    /// The file the program came from, for warnings to name.
+    pub fn file(mut self, file: &str) -> Self {
+        self.file = Some(file.to_string());
+        self
+    }
+
+    /// Fail once this many tokens have run, so untrusted code can't loop
+    /// forever. Operations that build big values, like `,` and `*`, or copy
+    /// them, like `.`, count a step for every few dozen elements.
+    pub fn max_steps(mut self, max: u64) -> Self {
+        self.max_steps = Some(max);
+        self
+    }
+
+    /// Fail once the program has run this long, counting from `build`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Fail when blocks and `~` nest deeper than this, instead of
+    /// overflowing the native stack; [`DEFAULT_MAX_DEPTH`] otherwise. A
+    /// higher limit needs a thread with a bigger stack.
+    pub fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// Fail once the program tries to print more than this many bytes in
+    /// all, after printing as many as it may.
+    pub fn max_output(mut self, max: u64) -> Self {
+        self.max_output = Some(max);
+        self
+    }
+
+    /// Fail instead of building values bigger than these.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn permissions(mut self, permissions: Permissions) -> Self {
+        self.permissions = permissions;
+        self
+    }
+
+    pub fn rng(mut self, rng: Rng) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+
+    /// Where `gets` reads from.
+    pub fn input(mut self, input: Box<dyn BufRead>) -> Self {
+        self.input = Some(input);
+        self
+    }
+
+    /// Where `print` writes. It isn't buffered further.
+    pub fn output(mut self, out: Box<dyn Write>) -> Self {
+        self.out = Some(out);
+        self
+    }
+
+    /// Where warnings and logs like `explain_coercions` write, instead of
+    /// stderr.
+    pub fn log(mut self, log: Box<dyn Write>) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    pub fn build(self) -> Gs {
+        let mut gs = Gs::new();
+        gs.extensions = self.extensions;
+        gs.compat = self.compat;
+        gs.explain_coercions = self.explain_coercions;
+        gs.log_draws = self.log_draws;
+        gs.unicode = self.unicode;
+        gs.lenient = self.lenient;
+        gs.pretty = self.pretty;
+        gs.color = self.color;
+        if self.pretty {
+            // As if the builtin definitions said `{pp}:p;`.
+            gs.vars.insert(b"p".to_vec(), Gval::Blk(b"pp".to_vec()));
+            gs.builtin_vars = gs.vars.clone();
+        }
+        gs.warnings = self.warnings;
+        gs.diagnostics = self.diagnostics;
+        gs.file = self.file;
+        gs.max_steps = self.max_steps;
+        gs.deadline = self.timeout.map(|t| (Instant::now() + t, t));
+        if let Some(max) = self.max_depth {
+            gs.max_depth = max;
+        }
+        gs.max_output = self.max_output;
+        gs.limits = self.limits;
+        gs.permissions = self.permissions;
+        if let Some(rng) = self.rng {
+            gs.rng = rng;
+        }
+        if let Some(input) = self.input {
+            gs.input = input;
+        }
+        if let Some(out) = self.out {
+            gs.out = out;
+        }
+        if let Some(log) = self.log {
+            gs.log = RefCell::new(log);
+        }
+        gs
+    }
+}
+
+impl Gs {
+    pub fn new() -> Gs {
+        let mut gs = Gs {
+            stack: vec![],
+            vars: HashMap::new(),
+            builtin_vars: HashMap::new(),
+            lb: vec![],
+            rng: Rng::Lcg(entropy_seed()),
+            extensions: false,
+            compat: false,
+            explain_coercions: false,
+            log_draws: false,
+            draws: 0,
+            unicode: false,
+            lenient: false,
+            pretty: false,
+            color: false,
+            started: Instant::now(),
+            permissions: Permissions::default(),
+            input: Box::new(BufReader::new(std::io::stdin())),
+            out: Box::new(BufWriter::new(std::io::stdout())),
+            log: RefCell::new(Box::new(std::io::stderr())),
+            written: 0,
+            max_output: None,
+            required: HashSet::new(),
+            trace: vec![],
+            post_mortem: None,
+            recent: VecDeque::with_capacity(RECENT_TOKENS),
+            warnings: false,
+            ignored: false,
+            warned: HashSet::new(),
+            diagnostics: DiagnosticFormat::Human,
+            file: None,
+            program: vec![],
+            depth: 0,
+            hook: None,
+            journal: None,
+            audit: None,
+            steps: 0,
+            max_steps: None,
+            work: 0,
+            deadline: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            limits: Limits::default(),
+        };
+        gs.run(BUILTIN_DEFINITIONS)
+            .expect("builtin definitions should run");
+        gs.builtin_vars = gs.vars.clone();
+        gs
+    }
+
+    pub fn builder() -> GsBuilder {
+        GsBuilder::default()
+    }
+
+    /// Where the last error returned by `run` happened, innermost first.
+    pub fn trace(&self) -> &[Frame] {
+        &self.trace
+    }
+
+    /// The stack, variables and last tokens run when the last program run
+    /// failed, if it did.
+    pub fn post_mortem(&self) -> Option<&PostMortem> {
+        self.post_mortem.as_ref()
+    }
+
+    /// The dialect whose builtins are recognized.
+    pub fn dialect(&self) -> Dialect {
+        if self.extensions {
+            Dialect::Extended
+        } else {
+            Dialect::Classic
+        }
+    }
+
+    pub fn vars(&self) -> &HashMap<Vec<u8>, Gval> {
+        &self.vars
+    }
+
+    /// Start recording what the run does, for [`record`].
+    pub fn start_journal(&mut self) {
+        self.journal = Some(Journal::default());
+    }
+
+    pub fn take_journal(&mut self) -> Option<Journal> {
+        self.journal.take()
+    }
+
+    /// Log side effects from now on, charged to tokens of the next program
+    /// run.
+    pub fn start_audit(&mut self) {
+        self.audit = Some(Audit::default());
+    }
+
+    pub fn take_audit(&mut self) -> Option<Audit> {
+        self.audit.take()
+    }
+
+    /// Call `hook` before each token from now on, or stop calling one.
+    pub fn set_hook(&mut self, hook: Option<Box<dyn Hook>>) {
+        self.hook = hook;
+    }
+
+    pub fn run(&mut self, code: &[u8]) -> GsResult<()> {
+        self.enter(code, |gs| gs.run_tokens(code))
+    }
+
+    /// Run `tokens`, which were parsed from all of `code`, as `run` would
+    /// run `code`, without parsing it again. Nothing checks that they were.
+    pub fn run_parsed(&mut self, code: &[u8], tokens: Vec<Gtoken>) -> GsResult<()> {
+        self.enter(code, |gs| gs.run_parsed_tokens(code, tokens))
+    }
+
+    /// Run `code` with `f`, one level deeper.
+    fn enter(&mut self, code: &[u8], f: impl FnOnce(&mut Gs) -> GsResult<()>) -> GsResult<()> {
+        if self.depth == 0 {
+            self.program = code.to_vec();
+            if let Some(audit) = &mut self.audit {
+                audit.running = *audit.program.get_or_insert_with(|| code.to_vec()) == code;
+                audit.positions.clear();
+            }
+            self.post_mortem = None;
+            self.recent.clear();
+        }
+        if self.depth >= self.max_depth {
+            return Err(GsError::Limit(format!(
+                "recursion limit of {} exceeded",
+                self.max_depth
+            )));
+        }
+        // A panic caught further out may have skipped inner runs' cleanup.
+        let depth = self.depth;
+        self.depth += 1;
+        let result = f(self);
+        self.depth = depth;
+        if depth == 0 && result.is_err() {
+            self.post_mortem = Some(self.autopsy());
+        }
+        result
+    }
+
+    /// Variables that aren't as the builtin definitions left them, by name.
+    pub fn changed_vars(&self) -> Vec<(Vec<u8>, Gval)> {
+        let mut vars: Vec<_> = self
+            .vars
+            .iter()
+            .filter(|&(name, v)| self.builtin_vars.get(name) != Some(v))
+            .map(|(name, v)| (name.clone(), v.clone()))
+            .collect();
+        vars.sort_by(|a, b| a.0.cmp(&b.0));
+        vars
+    }
+
+    fn autopsy(&self) -> PostMortem {
+        PostMortem {
+            stack: self.stack.clone(),
+            vars: self.changed_vars(),
+            recent: self.recent.iter().cloned().collect(),
+        }
+    }
+
+    /// Keep `token` among the recent ones, reusing the oldest one's buffer.
+    fn remember(&mut self, token: &[u8]) {
+        let mut text = match self.recent.len() {
+            RECENT_TOKENS => self.recent.pop_front().map(|(_, t)| t).unwrap_or_default(),
+            _ => vec![],
+        };
+        text.clear();
+        if token.len() > RECENT_TOKEN_LEN {
+            text.extend_from_slice(&token[..RECENT_TOKEN_LEN - 3]);
+            text.extend_from_slice(b"...");
+        } else {
+            text.extend_from_slice(token);
+        }
+        self.recent.push_back((self.depth, text));
+    }
+
+    fn run_tokens(&mut self, code: &[u8]) -> GsResult<()> {
+        let tokens = parse_program(code)?;
+        self.run_parsed_tokens(code, tokens)
+    }
+
+    fn run_parsed_tokens(&mut self, code: &[u8], tokens: Vec<Gtoken>) -> GsResult<()> {
+        for token in tokens {
+            let lexeme = token.lexeme();
+            let assignment = matches!(token, Gtoken::Assignment(_));
+            // Include an assignment's `:` and a block's braces.
+            let start = offset(code, lexeme);
+            let (at, end) = match token {
+                Gtoken::Assignment(_) => (start - 1, start + lexeme.len()),
+                Gtoken::Block(..) => (start - 1, start + lexeme.len() + 1),
+                _ => (start, start + lexeme.len()),
+            };
+            let inert = match token {
+                Gtoken::Block(..) => false,
+                Gtoken::Comment(_) => true,
+                _ => lexeme.iter().all(u8::is_ascii_whitespace),
+            };
+            if !inert {
+                self.remember(&code[at..end]);
+            }
+            if let Some(mut hook) = self.hook.take() {
+                let step = Step {
+                    code,
+                    offset: at,
+                    token: &code[at..end],
+                    assigns: assignment.then_some(lexeme),
+                    depth: self.depth,
+                    program_offset: self.locate(code).map(|base| base + at),
+                };
+                let result = hook.before(self, &step);
+                self.hook = Some(hook);
+                result?;
+            }
+            if self.journal.is_some() {
+                self.log_token(code, offset(code, lexeme) - assignment as usize);
+            }
+            if self.audit.is_some() {
+                self.audit_token(code, offset(code, lexeme) - assignment as usize);
+            }
+            if let Err(e) = catch_panics(|| self.run_token(token)) {
+                // An assignment's lexeme is its name, just after the `:`.
+                let (at, token) = if assignment {
+                    (offset(code, lexeme) - 1, [b":", lexeme].concat())
+                } else {
+                    (offset(code, lexeme), lexeme.to_vec())
+                };
+                self.trace.push(Frame {
+                    code: code.to_vec(),
+                    offset: at,
+                    token,
+                });
+                return Err(e);
+            }
+            if std::mem::take(&mut self.ignored) {
+                self.warn_unknown(code, lexeme);
+            }
+        }
+        Ok(())
+    }
+
+    /// Where `code` starts in the program, if it's the program or a literal
+    /// in it.
+    fn locate(&self, code: &[u8]) -> Option<usize> {
+        if code == self.program {
+            Some(0)
+        } else {
+            find_literal(&self.program, code, true)
+        }
+    }
+
+    fn log_token(&mut self, code: &[u8], at: usize) {
+        let cached = self.journal.as_ref().and_then(|j| j.bases.get(code));
+        let base = match cached {
+            Some(&base) => base,
+            None => {
+                let base = self.locate(code);
+                if let Some(journal) = &mut self.journal {
+                    journal.bases.insert(code.to_vec(), base);
+                }
+                base
+            }
+        };
+        if let Some(journal) = &mut self.journal {
+            journal.tokens.push(base.map(|b| b + at));
+        }
+    }
+
+    fn audit_token(&mut self, code: &[u8], at: usize) {
+        let Some(audit) = &self.audit else { return };
+        let base = match audit.bases.get(code) {
+            _ if !audit.running => None,
+            Some(&base) => base,
+            None => {
+                let base = self.locate(code);
+                if let Some(audit) = &mut self.audit {
+                    audit.bases.insert(code.to_vec(), base);
+                }
+                base
+            }
+        };
+        if let Some(audit) = &mut self.audit {
+            audit.positions.truncate(self.depth - 1);
+            audit.positions.push(base.map(|b| b + at));
+        }
+    }
+
+    /// Add an entry to the audit trail, if there is one.
+    fn audit(&mut self, op: &'static str, detail: impl FnOnce() -> serde_json::Value) {
+        if let Some(audit) = &mut self.audit {
+            audit.log(op, detail());
+        }
+    }
+
+    /// Refuse `op` for lack of `flag`, noting it in the audit trail.
+    fn deny(&mut self, op: &'static str, flag: &str) -> GsError {
+        let e = permission_denied(op, flag);
+        self.audit(op, || serde_json::json!({ "denied": e.to_string() }));
+        e
+    }
+
+    /// A uniform number in `0..n`, for positive `n`, from the RNG.
+    fn draw(&mut self, n: &BigInt) -> BigInt {
+        let r = self.rng.below(n);
+        if let Some(journal) = &mut self.journal {
+            journal.draws.push(r.clone());
+        }
+        if self.log_draws {
+            self.log(format_args!("draw {}: {} (below {})", self.draws, r, n));
+        }
+        self.audit(
+            "draw",
+            || serde_json::json!({ "below": n.to_string(), "result": r.to_string() }),
+        );
+        self.draws += 1;
+        r
+    }
+
+    fn warn_unknown(&mut self, code: &[u8], name: &[u8]) {
+        // Warn once for a multibyte character, at its first byte.
+        let at = offset(code, name);
+        if !self.warnings || name.len() == 1 && continues_char(code, at) {
+            return;
+        }
+        let name = if name.len() == 1 {
+            char_at(code, at)
+        } else {
+            name
+        };
+        if !self.warned.insert(name.to_vec()) {
+            return;
+        }
+        let hint = if lookup(name).is_some_and(|b| !self.dialect().has(b)) {
+            " (it's an extension; enable it with --extensions or --dialect extended)"
+        } else {
+            ""
+        };
+        let message = format!(
+            "`{}` is not defined, so it does nothing{}",
+            String::from_utf8_lossy(name),
+            hint
+        );
+        match self.diagnostics {
+            DiagnosticFormat::Human => {
+                let snippet = snippet(code, at, name.len());
+                self.log(format_args!("warning: {}\n{}", message, snippet))
+            }
+            DiagnosticFormat::Json => {
+                let base = self.locate(code);
+                let diagnostic = Diagnostic::warning("unknown-symbol", message)
+                    .file(self.file.as_deref())
+                    .at(&self.program, base.map(|b| b + at));
+                self.log(format_args!("{}", diagnostic.to_json()));
+            }
+        }
+    }
+
+    fn print(&mut self, bytes: &[u8]) -> GsResult<()> {
+        self.audit("print", || serde_json::json!({ "bytes": bytes.len() }));
+        if let Some(max) = self.max_output {
+            let room = max.saturating_sub(self.written);
+            if bytes.len() as u64 > room {
+                self.written = max;
+                self.out
+                    .write_all(&bytes[..room as usize])
+                    .map_err(output_error)?;
+                return Err(GsError::Limit(format!(
+                    "output limit of {} bytes exceeded",
+                    max
+                )));
+            }
+        }
+        self.written += bytes.len() as u64;
+        self.out.write_all(bytes).map_err(output_error)
+    }
+
+    pub fn flush(&mut self) -> GsResult<()> {
+        self.out.flush().map_err(output_error)
+    }
+
+    /// Write a line to the log. Like `eprintln!`, but one that fails is
+    /// dropped rather than failing the program.
+    fn log(&self, line: std::fmt::Arguments) {
+        let _ = writeln!(self.log.borrow_mut(), "{}", line);
+    }
+
+    /// Write to `out` from now on, returning where output went before.
+    pub fn replace_output(&mut self, out: Box<dyn Write>) -> Box<dyn Write> {
+        std::mem::replace(&mut self.out, out)
+    }
+
+    /// Forget the variables programs have set, back to the builtin
+    /// definitions.
+    pub fn reset_vars(&mut self) {
+        self.vars = self.builtin_vars.clone();
+    }
+
+    /// Set the variable `name`, as `:name` would.
+    pub fn set_var(&mut self, name: Vec<u8>, v: Gval) {
+        self.vars.insert(name, v);
+    }
+
+    fn push(&mut self, val: Gval) {
+        self.stack.push(val)
+    }
+
+    fn top(&self) -> GsResult<&Gval> {
+        static EMPTY: Gval = Gval::Str(Vec::new());
+        match self.stack.last() {
+            None if self.lenient => Ok(&EMPTY),
+            top => top.ok_or(GsError::StackUnderflow(None)),
+        }
+    }
+
+    fn dup(&mut self) -> GsResult<()> {
+        let a = self.pop()?;
+        self.work(a.weight())?;
+        self.push(a.clone());
+        self.push(a);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> GsResult<Gval> {
+        let mut i = self.lb.len();
+        while i > 0 && self.lb[i - 1] >= self.stack.len() {
+            i -= 1;
+            if self.lb[i] > 0 {
+                self.lb[i] -= 1;
+            }
+        }
+        match self.stack.pop() {
+            None if self.lenient => Ok(Gval::Str(vec![])),
+            top => top.ok_or(GsError::StackUnderflow(None)),
+        }
+    }
+
+    fn tilde(&mut self) -> GsResult<()> {
+        match self.pop()? {
+            Gval::Int(n) => self.push(Gval::Int(!n)),
+            Gval::Arr(vs) => self.stack.extend(vs),
+            Gval::Str(bs) => self.run(&bs)?,
+            Gval::Blk(bs) => self.run(&bs)?,
+        }
+        Ok(())
+    }
+
+    fn backtick(&mut self) -> GsResult<()> {
+        let bs = self.pop()?.inspect();
+        self.check_size(Size::StringLen, bs.len())?;
+        self.push(Gval::Str(bs));
+        Ok(())
+    }
+
+    fn bang(&mut self) -> GsResult<()> {
+        let f = self.pop()?.falsey();
+        self.push(Gval::bool(f));
+        Ok(())
+    }
+
+    fn at_sign(&mut self) -> GsResult<()> {
+        let c = self.pop()?;
+        let b = self.pop()?;
+        let a = self.pop()?;
+        self.push(b);
+        self.push(c);
+        self.push(a);
+        Ok(())
+    }
+
+    fn dollar(&mut self) -> GsResult<()> {
+        match self.pop()? {
+            Gval::Int(n) => {
+                let len: BigInt = self.stack.len().into();
+                if n < (-1i32).into() {
+                    if let Some(i) = (-n - 2i32).to_usize() {
+                        if i < self.stack.len() {
+                            self.work(self.stack[i].weight())?;
+                            self.push(self.stack[i].clone());
+                        }
+                    }
+                } else if n >= 0i32.into() && n < len {
+                    if let Some(i) = (len - 1i32 - n).to_usize() {
+                        self.work(self.stack[i].weight())?;
+                        self.push(self.stack[i].clone());
+                    }
+                }
+            }
+            Gval::Arr(mut vs) => {
+                vs.sort();
+                self.push(Gval::Arr(vs));
+            }
+            Gval::Str(bs) if self.unicode => {
+                let mut cs = decode(&bs);
+                cs.sort();
+                self.push(Gval::Str(encode(&cs)));
+            }
+            Gval::Str(mut bs) => {
+                bs.sort();
+                self.push(Gval::Str(bs));
+            }
+            Gval::Blk(code) => match self.pop()? {
+                v @ Gval::Int(_) => return Err(type_error(format!("can't sort {}", v.describe()))),
+                Gval::Arr(vs) => {
+                    let sorted = self.sort_by(code, vs)?;
+                    self.push(Gval::Arr(sorted));
+                }
+                Gval::Str(vs) if self.unicode => {
+                    let cs = decode(&vs).into_iter().map(scalar).collect();
+                    let sorted = self.sort_by(code, cs)?;
+                    self.push(Gval::Str(flatten_chars(sorted)));
+                }
+                Gval::Str(vs) => {
+                    let sorted = self.sort_by(code, vs)?;
+                    self.push(Gval::Str(sorted));
+                }
+                Gval::Blk(vs) => {
+                    let sorted = self.sort_by(code, vs)?;
+                    self.push(Gval::Blk(sorted));
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn sort_by<T: Ord + Clone + Into<Gval>>(
+        &mut self,
+        code: Vec<u8>,
+        vs: Vec<T>,
+    ) -> GsResult<Vec<T>> {
+        let mut results: Vec<(Gval, T)> = vec![];
+        for v in vs {
+            self.push(v.clone().into());
+            self.run(&code)?;
+            results.push((self.pop()?, v));
+        }
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(results.into_iter().map(|x| x.1).collect())
+    }
+
+    fn plus(&mut self) -> GsResult<()> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        let c = self.coerce("+", a, b);
+        self.work(c.size())?;
+        let c = c.concat();
+        self.check_value(&c)?;
+        self.push(c);
+        Ok(())
+    }
+
+    fn minus(&mut self) -> GsResult<()> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        match self.coerce("-", a, b) {
+            Coerced::Ints(x, y) => self.push(Gval::Int(x - y)),
+            Coerced::Arrs(x, y) => self.push(Gval::Arr(set_subtract(x, y))),
+            Coerced::Strs(x, y) if self.unicode => {
+                self.push(Gval::Str(by_chars(&x, &y, set_subtract)))
+            }
+            Coerced::Strs(x, y) => self.push(Gval::Str(set_subtract(x, y))),
+            Coerced::Blks(x, y) => self.push(Gval::Blk(set_subtract(x, y))),
+        }
+        Ok(())
+    }
+
+    fn asterisk(&mut self) -> GsResult<()> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        use Gval::*;
+        match (a, b) {
+            // multiply
+            (Int(a), Int(b)) => {
+                self.work(((a.bits() + b.bits()) / 64) as usize)?;
+                let r = a * b;
+                self.check_value(&Int(r.clone()))?;
+                self.push(Int(r))
+            }
+            // join
+            (Arr(a), Arr(sep)) => {
+                let r = self.join(a, Arr(sep))?;
+                self.push(r)
+            }
+            (Arr(a), Str(sep)) | (Str(sep), Arr(a)) => {
+                let r = self.join(a, Str(sep))?;
+                self.push(r)
+            }
+            (Str(a), Str(sep)) if self.unicode => {
+                let a: Vec<Gval> = decode(&a).iter().map(|&c| Str(encode(&[c]))).collect();
+                let r = self.join(a, Str(sep))?;
+                self.push(r);
+            }
+            (Str(a), Str(sep)) => {
+                let a: Vec<Gval> = a.into_iter().map(|x| Gval::Str(vec![x])).collect();
+                let r = self.join(a, Str(sep))?;
+                self.push(r);
+            }
+
+            // fold
+            (Str(a), Blk(code)) | (Blk(code), Str(a)) if self.unicode => {
+                self.fold(code, decode(&a).into_iter().map(scalar).collect())?
+            }
+            (Blk(code), Blk(a)) | (Str(a), Blk(code)) | (Blk(code), Str(a)) => {
+                self.fold(code, a)?
+            }
+            (Arr(a), Blk(code)) | (Blk(code), Arr(a)) => self.fold(code, a)?,
+
+            // repeat
+            (Int(n), Arr(a)) | (Arr(a), Int(n)) => {
+                self.check_size(Size::ArrayLen, &n * a.len())?;
+                let units = a.iter().map(|v| 1 + v.weight()).sum();
+                let r = self.repeat(a, n, units)?;
+                self.push(Arr(r))
+            }
+            (Int(n), Str(a)) | (Str(a), Int(n)) => {
+                self.check_size(Size::StringLen, &n * a.len())?;
+                let units = a.len();
+                let r = self.repeat(a, n, units)?;
+                self.push(Str(r))
+            }
+
+            // times
+            (Int(mut n), Blk(f)) | (Blk(f), Int(mut n)) => {
+                while n.is_positive() {
+                    self.tick()?;
+                    self.run(&f)?;
+                    n -= 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn slash(&mut self) -> GsResult<()> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        use Gval::*;
+        match (a, b) {
+            // divide
+            (Int(_), Int(b)) if b.is_zero() => return Err(division_by_zero()),
+            (Int(a), Int(b)) => self.push(Int(a.div_floor(&b))),
+            // split
+            (Arr(a), Arr(sep)) => {
+                let s = split(a, sep, false);
+                self.push(Arr(s.into_iter().map(Arr).collect()));
+            }
+            (Str(a), Str(sep)) if self.unicode => {
+                let s = split(decode(&a), decode(&sep), false);
+                self.push(Arr(s.iter().map(|x| Str(encode(x))).collect()));
+            }
+            (Str(a), Str(sep)) => {
+                let s = split(a, sep, false);
+                self.push(Arr(s.into_iter().map(Str).collect()));
+            }
+            (Arr(a), Str(sep)) | (Str(sep), Arr(a)) => {
+                let s = split(a, sep.into_iter().map(|x| x.into()).collect(), false);
+                self.push(Arr(s.into_iter().map(Arr).collect()));
+            }
+
+            // each
+            (Str(a), Blk(code)) | (Blk(code), Str(a)) if self.unicode => {
+                self.each(code, decode(&a).into_iter().map(scalar).collect())?
+            }
+            (Str(a), Blk(code)) | (Blk(code), Str(a)) => self.each(code, a)?,
+            (Arr(a), Blk(code)) | (Blk(code), Arr(a)) => self.each(code, a)?,
+
+            // chunk
+            (Int(n), Arr(mut a)) | (Arr(mut a), Int(n)) => {
+                let c = chunk(&mut a, n)?;
+                self.push(Arr(c.into_iter().map(|x| Arr(x.to_owned())).collect()));
+            }
+            (Int(n), Str(a)) | (Str(a), Int(n)) if self.unicode => {
+                let mut cs = decode(&a);
+                let c = chunk(&mut cs, n)?;
+                self.push(Arr(c.into_iter().map(|x| Str(encode(x))).collect()));
+            }
+            (Int(n), Str(mut a)) | (Str(mut a), Int(n)) => {
+                let c = chunk(&mut a, n)?;
+                self.push(Arr(c.into_iter().map(|x| Str(x.to_owned())).collect()));
+            }
+
+            // unfold
+            (Blk(cond), Blk(step)) => {
+                let mut r = vec![];
+                loop {
+                    self.tick()?;
+                    self.work(2 * self.top()?.weight())?;
+                    self.push(self.top()?.clone());
+                    self.run(&cond)?;
+                    if self.pop()?.falsey() {
+                        break;
+                    }
+                    r.push(self.top()?.clone());
+                    self.run(&step)?;
+                }
+                self.pop()?;
+                self.push(Gval::Arr(r));
+            }
+
+            (a @ Blk(_), b @ Int(_)) | (a @ Int(_), b @ Blk(_)) => {
+                return Err(mismatch(a, b));
+            }
+        }
+        Ok(())
+    }
+
+    fn percent(&mut self) -> GsResult<()> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        use Gval::*;
+        match (a, b) {
+            // modulo
+            (Int(_), Int(b)) if b.is_zero() => return Err(division_by_zero()),
+            (Int(a), Int(b)) => self.push(Int(a.mod_floor(&b))),
+            // clean split
+            (Arr(a), Arr(sep)) => {
+                let s = split(a, sep, true);
+                self.push(Arr(s.into_iter().map(Arr).collect()));
+            }
+            (Str(a), Str(sep)) => {
+                let s = split(a, sep, true);
+                self.push(Arr(s.into_iter().map(Str).collect()));
+            }
+            (Arr(a), Str(sep)) | (Str(sep), Arr(a)) => {
+                let s = split(a, sep.into_iter().map(|x| x.into()).collect(), true);
+                self.push(Arr(s.into_iter().map(Arr).collect()));
+            }
+
+            // map
+            (Arr(a), Blk(code)) | (Blk(code), Arr(a)) => {
+                let r = self.gs_map(code, a)?;
+                self.push(Arr(r))
+            }
+            (Str(a), Blk(code)) | (Blk(code), Str(a)) if self.unicode => {
+                let cs = decode(&a).into_iter().map(scalar).collect();
+                let r = self.gs_map(code, cs)?;
+                self.push(Str(flatten_chars(r)))
+            }
+            (Str(a), Blk(code)) | (Blk(code), Str(a)) => {
+                let r = self.gs_map(code, a)?;
+                self.push(Str(flatten(r)))
+            }
+
+            // every nth
+            (Int(n), Arr(a)) | (Arr(a), Int(n)) => self.push(Arr(every_nth(a, n)?)),
+            (Int(n), Str(a)) | (Str(a), Int(n)) if self.unicode => {
+                self.push(Str(encode(&every_nth(decode(&a), n)?)))
+            }
+            (Int(n), Str(a)) | (Str(a), Int(n)) => self.push(Str(every_nth(a, n)?)),
+
+            // unimplemented
+            (a @ Int(_), b @ Blk(_)) | (a @ Blk(_), b @ Int(_)) | (a @ Blk(_), b @ Blk(_)) => {
+                return Err(mismatch(a, b))
+            }
+        }
+        Ok(())
+    }
+
+    fn vertical_bar(&mut self) -> GsResult<()> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        self.push(match self.coerce("|", a, b) {
+            Coerced::Ints(x, y) => Gval::Int(x | y),
+            Coerced::Arrs(x, y) => Gval::Arr(set_or(x, y)),
+            Coerced::Strs(x, y) if self.unicode => Gval::Str(by_chars(&x, &y, set_or)),
+            Coerced::Strs(x, y) => Gval::Str(set_or(x, y)),
+            Coerced::Blks(x, y) => Gval::Blk(set_or(x, y)),
+        });
+        Ok(())
+    }
+
+    fn ampersand(&mut self) -> GsResult<()> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        self.push(match self.coerce("&", a, b) {
+            Coerced::Ints(x, y) => Gval::Int(x & y),
+            Coerced::Arrs(x, y) => Gval::Arr(set_and(x, y)),
+            Coerced::Strs(x, y) if self.unicode => Gval::Str(by_chars(&x, &y, set_and)),
+            Coerced::Strs(x, y) => Gval::Str(set_and(x, y)),
+            Coerced::Blks(x, y) => Gval::Blk(set_and(x, y)),
+        });
+        Ok(())
+    }
+
+    fn caret(&mut self) -> GsResult<()> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        self.push(match self.coerce("^", a, b) {
+            Coerced::Ints(x, y) => Gval::Int(x ^ y),
+            Coerced::Arrs(x, y) => Gval::Arr(set_xor(x, y)),
+            Coerced::Strs(x, y) if self.unicode => Gval::Str(by_chars(&x, &y, set_xor)),
+            Coerced::Strs(x, y) => Gval::Str(set_xor(x, y)),
+            Coerced::Blks(x, y) => Gval::Blk(set_xor(x, y)),
+        });
+        Ok(())
+    }
+
+    fn lteqgt(&mut self, ordering: Ordering) -> GsResult<()> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        use Gval::*;
+        use Ordering::*;
+        match (ordering, a, b) {
+            (Equal, Int(i), Arr(a)) | (Equal, Arr(a), Int(i)) => {
+                if let Some(x) = index(&a, i) {
+                    self.work(x.weight())?;
+                    self.push(x.clone())
+                }
+            }
+            (Equal, Int(i), Str(a)) | (Equal, Str(a), Int(i)) if self.unicode => {
+                if let Some(&c) = index(&decode(&a), i) {
+                    self.push(scalar(c))
+                }
+            }
+            (Equal, Int(i), Str(a))
+            | (Equal, Str(a), Int(i))
+            | (Equal, Int(i), Blk(a))
+            | (Equal, Blk(a), Int(i)) => {
+                if let Some(x) = index(&a, i) {
+                    self.push((*x).into())
+                }
+            }
+            (o, Int(i), Arr(a)) | (o, Arr(a), Int(i)) => self.push(Arr(slice(o, a, i))),
+            (o, Int(i), Str(a)) | (o, Str(a), Int(i)) if self.unicode => {
+                self.push(Str(encode(&slice(o, decode(&a), i))))
+            }
+            (o, Int(i), Str(a)) | (o, Str(a), Int(i)) => self.push(Str(slice(o, a, i))),
+            (o, Int(i), Blk(a)) | (o, Blk(a), Int(i)) => self.push(Blk(slice(o, a, i))),
+            (o, x, y) => {
+                let c = self.compare(o, x, y);
+                self.push(Gval::bool(c == o))
+            }
+        }
+        Ok(())
+    }
+
+    /// Order two values for `<`, `=` and `>`. Values of different types are
+    /// ordered by their enum variant, unless `--compat` asks to coerce them
+    /// like the reference interpreter does.
+    fn compare(&self, o: Ordering, a: Gval, b: Gval) -> Ordering {
+        if self.compat && std::mem::discriminant(&a) != std::mem::discriminant(&b) {
+            let op = match o {
+                Ordering::Less => "<",
+                Ordering::Equal => "=",
+                Ordering::Greater => ">",
+            };
+            self.coerce(op, a, b).cmp()
+        } else {
+            a.cmp(&b)
+        }
+    }
+
+    fn comma(&mut self) -> GsResult<()> {
+        use Gval::*;
+        match self.pop()? {
+            Int(n) => {
+                self.check_size(Size::ArrayLen, n.clone())?;
+                let mut r = vec![];
+                let mut i = BigInt::zero();
+                while i < n {
+                    self.work(1)?;
+                    r.push(Int(i.clone()));
+                    i += 1i32;
+                }
+                self.push(Arr(r));
+            }
+            Arr(a) => self.push(a.len().into()),
+            Str(a) if self.unicode => self.push(decode(&a).len().into()),
+            Str(a) => self.push(a.len().into()),
+            Blk(code) => match self.pop()? {
+                v @ Int(_) => {
+                    return Err(type_error(format!("can't select from {}", v.describe())))
+                }
+                Arr(a) => {
+                    let r = self.select(code, a)?;
+                    self.push(Arr(r))
+                }
+                Str(a) if self.unicode => {
+                    let cs = decode(&a).into_iter().map(scalar).collect();
+                    let r = self.select(code, cs)?;
+                    self.push(Str(flatten_chars(r)))
+                }
+                Str(a) => {
+                    let r = self.select(code, a)?;
+                    self.push(Str(r))
+                }
+                Blk(a) => {
+                    let r = self.select(code, a)?;
+                    self.push(Blk(r))
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn question(&mut self) -> GsResult<()> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        use Gval::*;
+        match (a, b) {
+            // power
+            (Int(a), Int(b)) => {
+                let r = match b.to_u32() {
+                    Some(e) => {
+                        // |a| ≥ 2 has at least bits(a) - 1 bits per factor.
+                        if a.abs() > BigInt::one() {
+                            self.check_size(Size::IntBits, BigInt::from(a.bits() - 1) * e + 1)?;
+                        }
+                        self.power(a, e)?
+                    }
+                    None => BigInt::zero(),
+                };
+                self.push(Int(r))
+            }
+
+            // indexof
+            (Arr(h), n @ Int(_))
+            | (n @ Int(_), Arr(h))
+            | (Arr(h), n @ Str(_))
+            | (n @ Str(_), Arr(h))
+            | (Arr(h), n @ Arr(_)) => self.push(Gval::Int(
+                h.iter()
+                    .position(|x| *x == n)
+                    .map_or(-BigInt::one(), BigInt::from),
+            )),
+            (Str(h), Int(n)) | (Int(n), Str(h)) if self.unicode => self.push(Gval::Int(
+                decode(&h)
+                    .into_iter()
+                    .position(|c| n == BigInt::from(c as u32))
+                    .map_or(-BigInt::one(), BigInt::from),
+            )),
+            (Str(h), Int(n)) | (Int(n), Str(h)) => self.push(Gval::Int(match n.to_u8() {
+                None => -BigInt::one(),
+                Some(b) => h
+                    .iter()
+                    .position(|x| *x == b)
+                    .map_or(-BigInt::one(), BigInt::from),
+            })),
+            (Str(h), Str(n)) if self.unicode => {
+                self.push(Gval::Int(string_index(&decode(&h), &decode(&n))))
+            }
+            (Str(h), Str(n)) => self.push(Gval::Int(string_index(&h, &n))),
+
+            // find
+            (a @ Int(_), b @ Blk(_)) | (a @ Blk(_), b @ Int(_)) => return Err(mismatch(a, b)),
+            (Blk(code), Str(a)) | (Str(a), Blk(code)) if self.unicode => {
+                let cs: Vec<Gval> = decode(&a).into_iter().map(scalar).collect();
+                self.find(code, cs)?
+            }
+            (Blk(code), Blk(a)) | (Blk(code), Str(a)) | (Str(a), Blk(code)) => {
+                self.find(code, a)?
+            }
+            (Blk(code), Arr(a)) | (Arr(a), Blk(code)) => self.find(code, a)?,
+        }
+        Ok(())
+    }
+
+    fn left_paren(&mut self) -> GsResult<()> {
+        use Gval::*;
+        let empty = || empty_sequence("(");
+        match self.pop()? {
+            Int(n) => self.push(Int(n - 1i32)),
+            Arr(mut a) => {
+                if a.is_empty() {
+                    return Err(empty());
+                }
+                let x = a.remove(0);
+                self.push(Arr(a));
+                self.push(x);
+            }
+            Str(a) if self.unicode => {
+                let cs = decode(&a);
+                let (&x, rest) = cs.split_first().ok_or_else(empty)?;
+                self.push(Str(encode(rest)));
+                self.push(scalar(x));
+            }
+            Str(a) => {
+                let (&x, rest) = a.split_first().ok_or_else(empty)?;
+                self.push(Str(rest.to_vec()));
+                self.push(x.into());
+            }
+            Blk(a) => {
+                let (&x, rest) = a.split_first().ok_or_else(empty)?;
+                self.push(Blk(rest.to_vec()));
+                self.push(x.into());
+            }
+        }
+        Ok(())
+    }
+
+    fn right_paren(&mut self) -> GsResult<()> {
+        use Gval::*;
+        let empty = || empty_sequence(")");
+        match self.pop()? {
+            Int(n) => self.push(Int(n + 1i32)),
+            Arr(mut a) => {
+                let l = a.pop().ok_or_else(empty)?;
+                self.push(Arr(a));
+                self.push(l);
+            }
+            Str(a) if self.unicode => {
+                let mut cs = decode(&a);
+                let l = cs.pop().ok_or_else(empty)?;
+                self.push(Str(encode(&cs)));
+                self.push(scalar(l));
+            }
+            Str(mut a) => {
+                let l = a.pop().ok_or_else(empty)?;
+                self.push(Str(a));
+                self.push(l.into());
+            }
+            Blk(mut a) => {
+                let l = a.pop().ok_or_else(empty)?;
+                self.push(Blk(a));
+                self.push(l.into());
+            }
+        }
+        Ok(())
+    }
+
+    fn rand(&mut self) -> GsResult<()> {
+        let r = match self.pop()? {
+            Gval::Int(n) if n.is_positive() => self.draw(&n),
+            _ => BigInt::zero(),
+        };
+        self.push(Gval::Int(r));
+        Ok(())
+    }
+
+    fn do_loop(&mut self) -> GsResult<()> {
+        let a = self.pop()?;
+        loop {
+            self.tick()?;
+            self.go(a.clone())?;
+            if self.pop()?.falsey() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn while_loop(&mut self, which: bool) -> GsResult<()> {
+        let b = self.pop()?;
+        let a = self.pop()?;
+        loop {
+            self.tick()?;
+            self.go(a.clone())?;
+            if self.pop()?.falsey() == which {
+                break;
+            }
+            self.go(b.clone())?;
+        }
+        Ok(())
+    }
+
+    fn zip(&mut self) -> GsResult<()> {
+        let a = self.pop()?.unwrap_arr()?;
+        let mut r = vec![];
+        let blank = a.first().map_or(Gval::Arr(vec![]), |x| x.factory());
+        for row in a {
+            let elems = match row {
+                Gval::Str(s) if self.unicode => decode(&s).into_iter().map(scalar).collect(),
+                row => row.into_arr()?,
+            };
+            for (y, elem) in elems.into_iter().enumerate() {
+                while r.len() < y + 1 {
+                    r.push(blank.clone())
+                }
+                match &mut r[y] {
+                    Gval::Str(s) if self.unicode => s.extend(flatten_chars(vec![elem])),
+                    column => column.push(elem)?,
+                }
+            }
+        }
+        self.push(Gval::Arr(r));
+        Ok(())
+    }
+
+    fn base(&mut self) -> GsResult<()> {
+        if self.extensions && matches!(self.top()?, Gval::Str(_)) {
+            return self.alphabet_base();
+        }
+        let b = self.pop()?.unwrap_int()?;
+        match self.pop()? {
+            Gval::Int(_) if b < BigInt::from(2) => {
+                return Err(GsError::Runtime(format!(
+                    "can't write digits in base {}",
+                    b
+                )));
+            }
+            Gval::Int(n) => {
+                let mut digits = vec![];
+                let mut i = n.abs();
+                while !i.is_zero() {
+                    let (j, k) = i.div_mod_floor(&b);
+                    i = j;
+                    digits.push(Gval::Int(k));
+                }
+                digits.reverse();
+                self.push(Gval::Arr(digits))
+            }
+            n => {
+                let mut total = BigInt::zero();
+                for digit in n.into_arr()? {
+                    total = total * b.clone() + digit.unwrap_int()?;
+                }
+                self.push(Gval::Int(total))
+            }
+        }
+        Ok(())
+    }
+
+    /// Coerce the operands of `op` to a common type, logging it under
+    /// `--explain-coercions`.
+    fn coerce(&self, op: &str, a: Gval, b: Gval) -> Coerced {
+        if !self.explain_coercions {
+            return coerce(a, b);
+        }
+        let before = format!("{} and {}", a.describe(), b.describe());
+        let same = std::mem::discriminant(&a) == std::mem::discriminant(&b);
+        let c = coerce(a, b);
+        if same {
+            self.log(format_args!("coerce in `{}`: {}, unchanged", op, before));
+        } else {
+            let (x, y) = c.clone().pair();
+            self.log(format_args!(
+                "coerce in `{}`: {} became {} and {}",
+                op,
+                before,
+                x.describe(),
+                y.describe()
+            ));
+        }
+        c
+    }
+
+    /// Join `a` with `sep` between elements, for `*`.
+    fn join(&mut self, a: Vec<Gval>, sep: Gval) -> GsResult<Gval> {
+        let mut a = a.into_iter();
+        Ok(match a.next() {
+            None => match sep {
+                Gval::Arr(_) => Gval::Arr(vec![]),
+                _ => Gval::Str(vec![]),
+            },
+            Some(first) => {
+                let sep_len = match &sep {
+                    Gval::Arr(s) => s.len(),
+                    Gval::Str(s) | Gval::Blk(s) => s.len(),
+                    Gval::Int(_) => 1,
+                };
+                let mut r = self.coerce("*", first, sep.clone()).left();
+                for i in a {
+                    self.work(1 + sep_len)?;
+                    r = self.coerce("*", r, sep.clone()).concat();
+                    r = self.coerce("*", r, i).concat();
+                    self.check_value(&r)?;
+                }
+                r
+            }
+        })
+    }
+
+    fn fold<T: Into<Gval>>(&mut self, code: Vec<u8>, vs: Vec<T>) -> GsResult<()> {
+        for (i, v) in vs.into_iter().enumerate() {
+            self.push(v.into());
+            if i >= 1 {
+                self.run(&code)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn each<T: Into<Gval>>(&mut self, code: Vec<u8>, vs: Vec<T>) -> GsResult<()> {
+        for v in vs {
+            self.push(v.into());
+            self.run(&code)?;
+        }
+        Ok(())
+    }
+
+    fn gs_map<T: Into<Gval>>(&mut self, code: Vec<u8>, vs: Vec<T>) -> GsResult<Vec<Gval>> {
+        let mut r: Vec<Gval> = vec![];
+        for v in vs {
+            let lb = self.stack.len();
+            self.push(v.into());
+            self.run(&code)?;
+            r.extend(self.stack.drain(lb.min(self.stack.len())..));
+        }
+        Ok(r)
+    }
+
+    fn select<T: Clone + Into<Gval>>(&mut self, code: Vec<u8>, vs: Vec<T>) -> GsResult<Vec<T>> {
+        let mut r: Vec<T> = vec![];
+        for v in vs {
+            self.push(v.clone().into());
+            self.run(&code)?;
+            if self.pop()?.truthy() {
+                r.push(v)
+            }
+        }
+        Ok(r)
+    }
+
+    fn find<T: Clone + Into<Gval>>(&mut self, code: Vec<u8>, vs: Vec<T>) -> GsResult<()> {
+        for v in vs {
+            self.push(v.clone().into());
+            self.run(&code)?;
+            if self.pop()?.truthy() {
+                self.push(v.into());
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn go(&mut self, val: Gval) -> GsResult<()> {
+        match val {
+            Gval::Blk(s) => self.run(&s)?,
+            _ => self.push(val),
+        }
+        Ok(())
+    }
+
+    /// Count a step: a token, or a loop iteration, which may run none.
+    fn tick(&mut self) -> GsResult<()> {
+        self.steps += 1;
+        if self.max_steps.is_some_and(|max| self.steps > max) {
+            return Err(GsError::Limit("step limit exceeded".into()));
+        }
+        // Reading the clock every step would slow down short tokens.
+        if self.steps.is_multiple_of(256) {
+            self.check_deadline(Duration::ZERO)?;
+        }
+        Ok(())
+    }
+
+    /// Fail if the time limit will have run out `ahead` from now.
+    fn check_deadline(&self, ahead: Duration) -> GsResult<()> {
+        if let Some((deadline, timeout)) = self.deadline {
+            if Instant::now()
+                .checked_add(ahead)
+                .is_none_or(|t| t > deadline)
+            {
+                return Err(GsError::Limit(format!(
+                    "time limit of {:?} exceeded",
+                    timeout
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Count `units` of work within one operation, like elements built, so
+    /// limits can stop it partway.
+    fn work(&mut self, units: usize) -> GsResult<()> {
+        self.work += units;
+        while self.work >= WORK_PER_STEP {
+            self.work -= WORK_PER_STEP;
+            self.tick()?;
+        }
+        Ok(())
+    }
+
+    /// `a` repeated `n` times, for `*`, each copy `units` of work.
+    fn repeat<T: Clone>(&mut self, a: Vec<T>, n: BigInt, units: usize) -> GsResult<Vec<T>> {
+        let mut r = vec![];
+        if a.is_empty() {
+            return Ok(r);
+        }
+        let mut n = n;
+        while n.is_positive() {
+            self.work(units)?;
+            r.extend_from_slice(&a);
+            n -= 1;
+        }
+        Ok(r)
+    }
+
+    /// `a` to the power `e`, by squaring.
+    fn power(&mut self, a: BigInt, e: u32) -> GsResult<BigInt> {
+        // Multiplying n-word numbers takes about n^1.5 word operations.
+        let cost = |x: &BigInt, y: &BigInt| {
+            let words = (x.bits().max(y.bits()) / 64 + 1) as f64;
+            words.powf(1.5) as usize
+        };
+        let (mut r, mut a, mut e) = (BigInt::one(), a, e);
+        while e > 0 {
+            if e & 1 == 1 {
+                self.work(cost(&r, &a))?;
+                r *= &a;
+            }
+            e >>= 1;
+            if e > 0 {
+                self.work(cost(&a, &a))?;
+                a = &a * &a;
+            }
+        }
+        Ok(r)
+    }
+
+    fn run_token(&mut self, token: Gtoken) -> GsResult<()> {
+        self.tick()?;
+        let has = self.stack.len();
+        let block_on_top = matches!(self.stack.last(), Some(Gval::Blk(_)));
+        let lexeme = token.lexeme();
+        let assignment = matches!(token, Gtoken::Assignment(_));
+        let name = || {
+            let op = if assignment {
+                [b":", lexeme].concat()
+            } else {
+                lexeme.to_vec()
+            };
+            String::from_utf8_lossy(&op).into_owned()
+        };
+        self.execute(token).map_err(|e| match e {
+            GsError::Type(TypeError { op: None, message }) => GsError::Type(TypeError {
+                op: Some(name()),
+                message,
+            }),
+            GsError::StackUnderflow(None) => GsError::StackUnderflow(Some(Underflow {
+                op: name(),
+                needs: if assignment {
+                    Some(1)
+                } else {
+                    arity(lexeme, block_on_top)
+                },
+                has,
+            })),
+            e => e,
+        })
+    }
+
+    fn execute(&mut self, token: Gtoken) -> GsResult<()> {
+        match token {
+            Gtoken::Assignment(name) => {
+                let t = self.top()?.clone();
+                self.vars.insert(name.to_owned(), t);
+                return Ok(());
+            }
+            // A block's lexeme is its body, which isn't a name: `{p}` is a block.
+            Gtoken::Block(_, src) => {
+                self.push(Gval::Blk(src.to_owned()));
+                return Ok(());
+            }
+            _ => {}
+        }
+        if let Some(v) = self.vars.get(token.lexeme()).cloned() {
+            self.work(v.weight())?;
+            self.go(v)?;
+            return Ok(());
+        }
+        match token {
+            Gtoken::IntLiteral(bs) => {
+                let n = BigInt::parse_bytes(bs, 10).unwrap();
+                self.push(Gval::Int(n));
+            }
+            Gtoken::SingleQuotedString(bs) => self.push(Gval::Str(unescape(bs, true))),
+            Gtoken::DoubleQuotedString(bs) => self.push(Gval::Str(unescape(bs, false))),
+            Gtoken::Symbol(b"~") => self.tilde()?,
+            Gtoken::Symbol(b"`") => self.backtick()?,
+            Gtoken::Symbol(b"!") => self.bang()?,
+            Gtoken::Symbol(b"@") => self.at_sign()?,
+            Gtoken::Symbol(b"$") => self.dollar()?,
+            Gtoken::Symbol(b"+") => self.plus()?,
+            Gtoken::Symbol(b"-") => self.minus()?,
+            Gtoken::Symbol(b"*") => self.asterisk()?,
+            Gtoken::Symbol(b"/") => self.slash()?,
+            Gtoken::Symbol(b"%") => self.percent()?,
+            Gtoken::Symbol(b"|") => self.vertical_bar()?,
+            Gtoken::Symbol(b"&") => self.ampersand()?,
+            Gtoken::Symbol(b"^") => self.caret()?,
+            Gtoken::Symbol(b"[") => self.lb.push(self.stack.len()),
+            Gtoken::Symbol(b"]") => {
+                let lb = self.lb.pop().unwrap_or(0).min(self.stack.len());
+                let vs = self.stack.drain(lb..).collect();
+                self.push(Gval::Arr(vs));
+            }
+            Gtoken::Symbol(b"\\") => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.push(b);
+                self.push(a);
+            }
+            Gtoken::Symbol(b";") => {
+                let _ = self.pop()?;
+            }
+            Gtoken::Symbol(b"<") => self.lteqgt(Ordering::Less)?,
+            Gtoken::Symbol(b"=") => self.lteqgt(Ordering::Equal)?,
+            Gtoken::Symbol(b">") => self.lteqgt(Ordering::Greater)?,
+            Gtoken::Symbol(b",") => self.comma()?,
+            Gtoken::Symbol(b".") => self.dup()?,
+            Gtoken::Symbol(b"?") => self.question()?,
+            Gtoken::Symbol(b"(") => self.left_paren()?,
+            Gtoken::Symbol(b")") => self.right_paren()?,
+            Gtoken::Symbol(b"print") => {
+                let a = self.pop()?;
+                self.print(&a.into_gs())?;
+            }
+            Gtoken::Symbol(b"rand") => self.rand()?,
+            Gtoken::Symbol(b"do") => self.do_loop()?,
+            Gtoken::Symbol(b"while") => self.while_loop(true)?,
+            Gtoken::Symbol(b"until") => self.while_loop(false)?,
+            Gtoken::Symbol(b"if") => {
+                let c = self.pop()?;
+                let b = self.pop()?;
+                let a = self.pop()?;
+                if a.truthy() {
+                    self.go(b)?;
+                } else {
+                    self.go(c)?;
+                }
+            }
+            Gtoken::Symbol(b"abs") => {
+                let a = self.pop()?;
+                self.push(Gval::Int(a.unwrap_int()?.abs()));
+            }
+            Gtoken::Symbol(b"zip") => self.zip()?,
+            Gtoken::Symbol(b"base") => self.base()?,
+            Gtoken::Block(_, src) => self.push(Gval::Blk(src.to_owned())),
+            Gtoken::Symbol(s) => self.extension(s)?,
+            Gtoken::Comment(_) | Gtoken::Assignment(_) => {}
+        }
+        Ok(())
+    }
+}
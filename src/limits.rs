@@ -0,0 +1,62 @@
+//! Caps on how big values may grow, so that a program like `9 9?.?` fails
+//! with an error instead of exhausting memory. There are none by default.
+//! Operators that grow values quickly check the size before building the
+//! result; `+` and integer `*` can at most double it, so they check after.
+
+use crate::error::{GsError, GsResult};
+use crate::value::Gval;
+use crate::Gs;
+use num::BigInt;
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct Limits {
+    /// The most elements an array may have.
+    pub array_len: Option<usize>,
+    /// The most bytes a string or block may have.
+    pub string_len: Option<usize>,
+    /// The most bits an integer may have, besides its sign.
+    pub int_bits: Option<u64>,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Size {
+    ArrayLen,
+    StringLen,
+    IntBits,
+}
+
+impl Gs {
+    /// Fail if a value of `kind` and `size` would be over its limit.
+    pub(crate) fn check_size(&self, kind: Size, size: impl Into<BigInt>) -> GsResult<()> {
+        let (max, what, unit) = match kind {
+            Size::ArrayLen => (self.limits.array_len.map(BigInt::from), "array length", ""),
+            Size::StringLen => (
+                self.limits.string_len.map(BigInt::from),
+                "string length",
+                "",
+            ),
+            Size::IntBits => (
+                self.limits.int_bits.map(BigInt::from),
+                "integer size",
+                " bits",
+            ),
+        };
+        let size = size.into();
+        match max {
+            Some(max) if size > max => Err(GsError::Limit(format!(
+                "{} limit of {}{} exceeded: {}{}",
+                what, max, unit, size, unit
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fail if `v` itself is over a limit; its elements aren't checked.
+    pub(crate) fn check_value(&self, v: &Gval) -> GsResult<()> {
+        match v {
+            Gval::Int(n) => self.check_size(Size::IntBits, n.bits()),
+            Gval::Arr(a) => self.check_size(Size::ArrayLen, a.len()),
+            Gval::Str(s) | Gval::Blk(s) => self.check_size(Size::StringLen, s.len()),
+        }
+    }
+}
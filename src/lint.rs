@@ -0,0 +1,144 @@
+//! Warnings about code that parses, but likely not as meant: the classic
+//! tokenizing traps of GolfScript, like `1-1` being two numbers.
+
+use crate::builtins::lookup;
+use crate::parse::{diagnose, escape_controls, offset, parse_code, Gtoken};
+
+pub struct Lint {
+    /// Where the problem is in the program, and how many bytes it spans.
+    pub offset: usize,
+    pub len: usize,
+    /// A stable name for the kind of problem, like `negative-literal`.
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Whether `token` leaves a value that a following `-` could be meant to
+/// subtract from.
+fn ends_in_value(token: &Gtoken) -> bool {
+    match token {
+        Gtoken::IntLiteral(_)
+        | Gtoken::SingleQuotedString(_)
+        | Gtoken::DoubleQuotedString(_)
+        | Gtoken::Block(..) => true,
+        Gtoken::Symbol(s) => s[0].is_ascii_alphabetic() || s[0] == b'_' || *s == b"]",
+        _ => false,
+    }
+}
+
+/// Where `token` starts and ends in `program`, counting a block's braces.
+fn span(program: &[u8], token: &Gtoken) -> (usize, usize) {
+    let lexeme = token.lexeme();
+    let at = offset(program, lexeme);
+    match token {
+        Gtoken::Block(..) => (at - 1, at + lexeme.len() + 1),
+        _ => (at, at + lexeme.len()),
+    }
+}
+
+fn walk(program: &[u8], tokens: &[Gtoken], top: bool, out: &mut Vec<Lint>) {
+    for (i, token) in tokens.iter().enumerate() {
+        let lexeme = token.lexeme();
+        let at = offset(program, lexeme);
+        match token {
+            Gtoken::Block(inner, _) => walk(program, inner, false, out),
+            Gtoken::IntLiteral(n) if n[0] == b'-' => {
+                let previous = i.checked_sub(1).map(|i| &tokens[i]);
+                if let Some(previous) = previous.filter(|p| span(program, p).1 == at) {
+                    if ends_in_value(previous) {
+                        let before = &program[span(program, previous).0..at];
+                        out.push(Lint {
+                            offset: at,
+                            len: n.len(),
+                            code: "negative-literal",
+                            message: format!(
+                                "`{}{}` is `{}` then the number `{}`, not a subtraction; \
+                                 put a space after the `-` to subtract",
+                                escape_controls(before),
+                                escape_controls(n),
+                                escape_controls(before),
+                                escape_controls(n)
+                            ),
+                        });
+                    }
+                }
+            }
+            Gtoken::Assignment(name) if name.iter().all(u8::is_ascii_whitespace) => {
+                out.push(Lint {
+                    offset: at - 1,
+                    len: 1 + name.len(),
+                    code: "whitespace-assignment",
+                    message: format!(
+                        "`:` takes the next character as the name, so this assigns to `{}`",
+                        escape_controls(name)
+                    ),
+                });
+            }
+            Gtoken::Assignment(name) if lookup(name).is_some() => {
+                out.push(Lint {
+                    offset: at - 1,
+                    len: 1 + name.len(),
+                    code: "shadowed-builtin",
+                    message: format!(
+                        "assigning to `{}` replaces the builtin from here on",
+                        escape_controls(name)
+                    ),
+                });
+            }
+            Gtoken::Symbol(b":") => out.push(Lint {
+                offset: at,
+                len: 1,
+                code: "dangling-assignment",
+                message: "`:` at the end of the code has no name to assign to".to_string(),
+            }),
+            _ => {}
+        }
+    }
+    // Nothing runs after an assignment that ends the program, except the
+    // output of what's left on the stack.
+    let last = tokens.iter().rev().find(|t| match t {
+        Gtoken::Comment(_) => false,
+        Gtoken::Symbol(s) => !s.iter().all(u8::is_ascii_whitespace),
+        _ => true,
+    });
+    if let (true, Some(Gtoken::Assignment(name))) = (top, last) {
+        if !name.iter().all(u8::is_ascii_whitespace) && lookup(name).is_none() {
+            out.push(Lint {
+                offset: offset(program, name) - 1,
+                len: 1 + name.len(),
+                code: "trailing-assignment",
+                message: format!(
+                    "nothing runs after `:{}`, so the variable is never used",
+                    escape_controls(name)
+                ),
+            });
+        }
+    }
+}
+
+/// The problems found in `program`, in order.
+pub fn lint(program: &[u8]) -> Vec<Lint> {
+    let mut out = vec![];
+    let Ok((rest, tokens)) = parse_code(program) else {
+        return out;
+    };
+    walk(program, &tokens, true, &mut out);
+    if let (at, "unmatched `}`") = diagnose(rest) {
+        let after = offset(program, at) + 1;
+        let start = after
+            + program[after..]
+                .iter()
+                .take_while(|b| b.is_ascii_whitespace())
+                .count();
+        if start < program.len() {
+            out.push(Lint {
+                offset: start,
+                len: program.len() - start,
+                code: "unreachable-code",
+                message: "code after an unmatched `}` is never run".to_string(),
+            });
+        }
+    }
+    out.sort_by_key(|l| l.offset);
+    out
+}
@@ -0,0 +1,307 @@
+//! A language server for GolfScript over stdin and stdout: diagnostics for
+//! parse errors, unknown symbols and lints, hover documentation for
+//! builtins, and document symbols for variable assignments.
+
+use crate::builtins::lookup;
+use crate::lint::lint;
+use crate::parse::{diagnose, offset, parse_code, Gtoken};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+
+/// Read one message, or `None` at the end of input.
+fn receive(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn send(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// An LSP position: a line and a UTF-16 column, both 0-based.
+fn position(text: &[u8], offset: usize) -> Value {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    let line = before.iter().filter(|&&b| b == b'\n').count();
+    let character: usize = String::from_utf8_lossy(&before[line_start..])
+        .chars()
+        .map(char::len_utf16)
+        .sum();
+    json!({ "line": line, "character": character })
+}
+
+/// The byte offset of an LSP position.
+fn offset_of(text: &[u8], position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or(0) as usize;
+    let character = position["character"].as_u64().unwrap_or(0) as usize;
+    let mut start = 0;
+    for _ in 0..line {
+        match text[start..].iter().position(|&b| b == b'\n') {
+            Some(i) => start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let rest = String::from_utf8_lossy(&text[start..]);
+    let mut units = 0;
+    for (i, c) in rest.char_indices() {
+        if units >= character || c == '\n' {
+            return start + i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+fn range(text: &[u8], start: usize, len: usize) -> Value {
+    json!({ "start": position(text, start), "end": position(text, start + len) })
+}
+
+/// A token and where it starts; an assignment starts at its `:`.
+struct Token<'a> {
+    offset: usize,
+    lexeme: &'a [u8],
+    assignment: bool,
+}
+
+/// Every token outside comments and strings, including those inside blocks.
+fn tokens(text: &[u8]) -> Vec<Token<'_>> {
+    fn walk<'a>(text: &'a [u8], tokens: Vec<Gtoken<'a>>, out: &mut Vec<Token<'a>>) {
+        for token in tokens {
+            match token {
+                Gtoken::Block(inner, _) => walk(text, inner, out),
+                Gtoken::Assignment(name) => out.push(Token {
+                    offset: offset(text, name) - 1,
+                    lexeme: name,
+                    assignment: true,
+                }),
+                Gtoken::Symbol(s) => {
+                    let at = offset(text, s);
+                    // Each byte of a multibyte character is its own symbol;
+                    // keep the character whole.
+                    match out.last_mut() {
+                        Some(last)
+                            if !s.is_ascii()
+                                && !last.assignment
+                                && !last.lexeme.is_ascii()
+                                && last.offset + last.lexeme.len() == at =>
+                        {
+                            last.lexeme = &text[last.offset..at + s.len()];
+                        }
+                        _ => out.push(Token {
+                            offset: at,
+                            lexeme: s,
+                            assignment: false,
+                        }),
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    let mut out = vec![];
+    if let Ok((_, parsed)) = parse_code(text) {
+        walk(text, parsed, &mut out);
+    }
+    out
+}
+
+fn diagnostics(text: &[u8]) -> Vec<Value> {
+    let mut found = vec![];
+    if let Ok((rest, _)) = parse_code(text) {
+        if !rest.is_empty() {
+            let (at, message) = diagnose(rest);
+            found.push(json!({
+                "range": range(text, offset(text, at), 1),
+                "severity": 1,
+                "source": "golfscript",
+                "code": "parse-error",
+                "message": message,
+            }));
+        }
+    }
+    let tokens = tokens(text);
+    let assigned: HashSet<&[u8]> = tokens
+        .iter()
+        .filter(|t| t.assignment)
+        .map(|t| t.lexeme)
+        .collect();
+    for t in tokens.iter().filter(|t| !t.assignment) {
+        if lookup(t.lexeme).is_none()
+            && !assigned.contains(t.lexeme)
+            && !t.lexeme.iter().all(u8::is_ascii_whitespace)
+        {
+            let message = format!(
+                "`{}` is not defined, so it does nothing",
+                String::from_utf8_lossy(t.lexeme)
+            );
+            found.push(json!({
+                "range": range(text, t.offset, t.lexeme.len()),
+                "severity": 2,
+                "source": "golfscript",
+                "code": "unknown-symbol",
+                "message": message,
+            }));
+        }
+    }
+    for l in lint(text) {
+        found.push(json!({
+            "range": range(text, l.offset, l.len),
+            "severity": 2,
+            "source": "golfscript",
+            "code": l.code,
+            "message": l.message,
+        }));
+    }
+    found
+}
+
+fn hover(text: &[u8], at: usize) -> Value {
+    let tokens = tokens(text);
+    let Some(token) = tokens.iter().find(|t| {
+        let len = t.lexeme.len() + t.assignment as usize;
+        (t.offset..t.offset + len).contains(&at)
+    }) else {
+        return Value::Null;
+    };
+    let name = String::from_utf8_lossy(token.lexeme);
+    let assignment = tokens
+        .iter()
+        .find(|t| t.assignment && t.lexeme == token.lexeme);
+    let contents = match (assignment, lookup(token.lexeme)) {
+        (Some(a), builtin) => {
+            let line = position(text, a.offset)["line"].as_u64().unwrap_or(0) + 1;
+            let shadows = if builtin.is_some() {
+                " (shadowing the builtin)"
+            } else {
+                ""
+            };
+            format!(
+                "`{}`: variable, first assigned on line {}{}",
+                name, line, shadows
+            )
+        }
+        (None, Some(b)) => format!(
+            "`{}`: pops {} value{}\n\n{}",
+            name,
+            b.arity,
+            if b.arity == 1 { "" } else { "s" },
+            b.doc
+        ),
+        (None, None) => return Value::Null,
+    };
+    let len = token.lexeme.len() + token.assignment as usize;
+    json!({
+        "contents": { "kind": "markdown", "value": contents },
+        "range": range(text, token.offset, len),
+    })
+}
+
+fn document_symbols(uri: &Value, text: &[u8]) -> Value {
+    let symbols: Vec<Value> = tokens(text)
+        .iter()
+        .filter(|t| t.assignment)
+        .map(|t| {
+            json!({
+                "name": String::from_utf8_lossy(t.lexeme),
+                "kind": 13,
+                "location": { "uri": uri, "range": range(text, t.offset, t.lexeme.len() + 1) },
+            })
+        })
+        .collect();
+    Value::Array(symbols)
+}
+
+/// Serve requests from `input` until it ends or the client says `exit`.
+pub fn serve(mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut documents: HashMap<String, Vec<u8>> = HashMap::new();
+    while let Some(message) = receive(&mut input)? {
+        let method = message["method"].as_str().unwrap_or("");
+        let params = &message["params"];
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+        let result = match method {
+            "initialize" => Some(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "golfscript-rs", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "shutdown" => Some(Value::Null),
+            "exit" => return Ok(()),
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let text = match method {
+                    "textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+                    // Full sync: the last change is the whole document.
+                    _ => params["contentChanges"]
+                        .as_array()
+                        .and_then(|c| c.last())
+                        .and_then(|c| c["text"].as_str()),
+                };
+                let text = text.unwrap_or("").as_bytes().to_vec();
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/publishDiagnostics",
+                    "params": { "uri": uri, "diagnostics": diagnostics(&text) },
+                });
+                send(&mut output, &notification)?;
+                documents.insert(uri.to_string(), text);
+                None
+            }
+            "textDocument/didClose" => {
+                documents.remove(uri);
+                None
+            }
+            "textDocument/hover" => {
+                let text = documents.get(uri).map_or(&[][..], |t| t);
+                Some(hover(text, offset_of(text, &params["position"])))
+            }
+            "textDocument/documentSymbol" => {
+                let text = documents.get(uri).map_or(&[][..], |t| t);
+                Some(document_symbols(&params["textDocument"]["uri"], text))
+            }
+            _ => None,
+        };
+        // Notifications have no id and get no response.
+        let Some(id) = message.get("id") else {
+            continue;
+        };
+        let response = match result {
+            Some(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            None => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32601, "message": format!("unsupported method `{}`", method) },
+            }),
+        };
+        send(&mut output, &response)?;
+    }
+    Ok(())
+}
@@ -1,761 +1,1498 @@
-use crate::coerce::flatten;
-use crate::parse::parse_code;
-use crate::util::chunk;
-use crate::util::every_nth;
-use crate::util::index;
-use crate::util::slice;
-use crate::util::split;
-use crate::util::string_index;
-use crate::value::join;
-use clap::Parser;
-use num::BigInt;
-use num::Integer;
-use num::One;
-use num::Signed;
-use num::ToPrimitive;
-use num::Zero;
-use std::cmp::Ordering;
-use std::io::Read;
-use std::io::Write;
-
-use std::collections::HashMap;
-
-mod coerce;
-mod parse;
-mod unescape;
-mod util;
-mod value;
-
-use crate::coerce::{coerce, Coerced};
-use crate::parse::Gtoken;
-use crate::unescape::unescape;
-use crate::util::{repeat, set_and, set_or, set_subtract, set_xor};
-use crate::value::Gval;
-
-fn print(bytes: &[u8]) {
-    std::io::stdout().write_all(bytes).unwrap();
-}
-
-struct Gs {
-    pub stack: Vec<Gval>,
-    vars: HashMap<Vec<u8>, Gval>,
-    lb: Vec<usize>,
-    rng_state: u64,
-}
-
-impl Gs {
-    pub fn new() -> Gs {
-        Gs {
-            stack: vec![],
-            vars: HashMap::new(),
-            lb: vec![],
-            rng_state: 123456789u64,
-        }
-    }
-
-    pub fn run(&mut self, code: &[u8]) {
-        let (rest, tokens) = parse_code(code).expect("parse error");
-        if !rest.is_empty() {
-            panic!("parse error: has remainder")
-        }
-        // println!("parse: {:?}", tokens);
-        let mut tokens = tokens.into_iter();
-        while let Some(token) = tokens.next() {
-            match token {
-                Gtoken::Symbol(b":") => {
-                    let name = tokens.next().expect("parse error: assignment");
-                    let t = self.top().clone();
-                    self.vars.insert(name.lexeme().to_owned(), t);
-                }
-                t => {
-                    self.run_token(t);
-                }
-            }
-        }
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use golfscript_rs::builtins::{reference, Dialect};
+use golfscript_rs::debug::Debugger;
+use golfscript_rs::diagnostic::{Diagnostic, DiagnosticFormat, Severity};
+use golfscript_rs::error::{install_panic_hook, GsError, GsResult, ParseError};
+use golfscript_rs::expect::{check, expectations};
+use golfscript_rs::ext::{http_get, Permissions};
+use golfscript_rs::formatter::format;
+use golfscript_rs::generate::Generator;
+use golfscript_rs::golf::suggest;
+use golfscript_rs::graph::dot;
+use golfscript_rs::highlight::{self, HighlightFormat};
+use golfscript_rs::hook::Hook;
+use golfscript_rs::incremental::Incremental;
+#[cfg(feature = "jupyter")]
+use golfscript_rs::jupyter;
+use golfscript_rs::learn::{self, LESSONS};
+use golfscript_rs::limits::Limits;
+use golfscript_rs::lint::lint;
+use golfscript_rs::minify::minify;
+use golfscript_rs::parse::{diagnose, offset, parse_code, snippet};
+use golfscript_rs::profile::{HeatmapFormat, Profiler};
+use golfscript_rs::record::{Journal, Record};
+use golfscript_rs::reference::{compare, run_ours, suspects, Outcome, Reference};
+use golfscript_rs::repl;
+use golfscript_rs::rng::{entropy_seed, Mt19937, Rng};
+use golfscript_rs::score;
+use golfscript_rs::state::State;
+use golfscript_rs::stats::Stats;
+use golfscript_rs::timeline::Timeline;
+use golfscript_rs::trace::{locate, render};
+use golfscript_rs::transpile::{transpile, Target};
+use golfscript_rs::utf8::{Utf8Mode, Utf8Writer};
+use golfscript_rs::value::Gval;
+use golfscript_rs::{Gs, GsBuilder, DEFAULT_MAX_DEPTH, STD_PRELUDE};
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, BufWriter, Cursor};
+use std::io::{IsTerminal, Read, Write};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How often `watch` looks at the program's file.
+const WATCH_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum RngKind {
+    /// A fast 64-bit LCG.
+    Lcg,
+    /// MT19937 seeded like Ruby's `srand`, matching the reference interpreter.
+    Mt,
+}
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum Prelude {
+    /// Common helpers like `sum`, `max`, `transpose` and `lines`.
+    Std,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Run a language server on stdin and stdout.
+    Lsp,
+    /// Run a program under an interactive debugger, reading commands from
+    /// stdin.
+    Debug(Box<RunArgs>),
+    /// Run lines from stdin interactively, showing the stack after each.
+    Repl(Box<RunArgs>),
+    /// Run a program each time its file changes, resuming from where it was
+    /// edited.
+    Watch(Box<RunArgs>),
+    /// Report parse errors and likely mistakes in a program without running
+    /// it.
+    Check(Box<RunArgs>),
+    /// Print a program with tidy spacing and indentation.
+    Fmt(Box<RunArgs>),
+    /// Print a program without comments or whitespace it doesn't need.
+    Minify(Box<RunArgs>),
+    /// Print a program's blocks and variables as a Graphviz graph.
+    Graph(Box<RunArgs>),
+    /// Translate a program into another language.
+    Transpile(Box<TranspileArgs>),
+    /// Print a program with its tokens colored.
+    Highlight(Box<HighlightArgs>),
+    /// Count a program's bytes as golf sites do, and show where they go.
+    Score(Box<RunArgs>),
+    /// Suggest rewrites that make a program shorter.
+    Golf(Box<RunArgs>),
+    /// Compare programs' output with the reference interpreter's.
+    Differential(Box<DifferentialArgs>),
+    /// Print random programs that parse, or run them to look for crashes.
+    Generate(Box<GenerateArgs>),
+    /// Describe a builtin, or list them all, or those in a dialect.
+    Doc {
+        name: Option<String>,
+        #[clap(long, value_enum)]
+        dialect: Option<Dialect>,
+    },
+    /// Learn GolfScript with lessons and exercises, from lesson N if given.
+    Learn {
+        #[clap(value_name = "N")]
+        lesson: Option<usize>,
+    },
+    /// Run a Jupyter kernel, or install one with `--install`.
+    #[cfg(feature = "jupyter")]
+    Kernel(Box<KernelArgs>),
+}
+
+#[derive(clap::Args, Debug)]
+struct HighlightArgs {
+    #[clap(long, value_enum, default_value = "ansi")]
+    format: HighlightFormat,
+    #[clap(flatten)]
+    run: RunArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct TranspileArgs {
+    #[clap(long, value_enum, default_value = "rust")]
+    to: Target,
+    #[clap(flatten)]
+    run: RunArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct DifferentialArgs {
+    /// Programs to run, each with the input in the file beside it with the
+    /// extension `.in`, if there is one.
+    #[clap(required = true)]
+    programs: Vec<String>,
+    /// `golfscript.rb`, if the `GOLFSCRIPT_RB` environment variable doesn't
+    /// name it.
+    #[clap(long, value_name = "PATH")]
+    reference: Option<String>,
+    #[clap(long, takes_value = false)]
+    compat: bool,
+    #[clap(long, value_name = "SECONDS", default_value_t = 10.0)]
+    timeout: f64,
+}
+
+#[derive(clap::Args, Debug)]
+struct GenerateArgs {
+    #[clap(long, value_name = "N", default_value_t = 1)]
+    count: usize,
+    /// About how many literals, operators, arrays and blocks each has.
+    #[clap(long, value_name = "N", default_value_t = 20)]
+    size: usize,
+    #[clap(long, value_name = "N", default_value_t = 3)]
+    max_depth: usize,
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Write each program to a file in this directory instead of printing
+    /// them a line each.
+    #[clap(long, value_name = "DIR")]
+    out: Option<String>,
+    /// Run each program with step, time and size limits, and print those that
+    /// crash the interpreter instead.
+    #[clap(long, takes_value = false, conflicts_with = "out")]
+    stress: bool,
+}
+
+#[cfg(feature = "jupyter")]
+#[derive(clap::Args, Debug)]
+struct KernelArgs {
+    /// The connection file Jupyter passes a kernel it starts.
+    #[clap(short = 'f', long, value_name = "FILE")]
+    connection_file: Option<String>,
+    /// Tell Jupyter how to start this kernel, with the flags given here.
+    #[clap(long, takes_value = false, conflicts_with = "connection-file")]
+    install: bool,
+    #[clap(flatten)]
+    run: RunArgs,
+}
+
+#[derive(PartialEq, Eq)]
+enum Mode {
+    Run,
+    Debug,
+    Repl,
+    Watch,
+    /// A Jupyter kernel, with its connection file.
+    #[cfg(feature = "jupyter")]
+    Kernel(String),
+}
+
+#[derive(clap::Parser, Debug)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+    #[clap(flatten)]
+    run: RunArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct RunArgs {
+    #[clap(long)]
+    code_path: Vec<String>,
+    #[clap(short = 'e', long, allow_hyphen_values = true)]
+    code: Vec<String>,
+    /// The `--code` and `--code-path` programs in the order given, when
+    /// running.
+    #[clap(skip)]
+    pipeline: Vec<Source>,
+    #[clap(long)]
+    input_path: Option<String>,
+    #[clap(short = 'i', long, allow_hyphen_values = true)]
+    input: Option<String>,
+    #[clap(short = 'q', long, takes_value = false)]
+    no_implicit_output: bool,
+    #[clap(long, value_name = "SEP", allow_hyphen_values = true)]
+    output_separator: Option<String>,
+    #[clap(long, takes_value = false, conflicts_with = "output-separator")]
+    output_lines: bool,
+    #[clap(long, takes_value = false)]
+    no_trailing_newline: bool,
+    #[clap(short = 's', long, takes_value = false)]
+    input_from_stdin: bool,
+    #[clap(
+        short = 'n',
+        long,
+        takes_value = false,
+        conflicts_with_all = &["input-from-stdin", "args", "record", "replay", "verify"]
+    )]
+    each_line: bool,
+    #[clap(short = 'p', long, takes_value = false, conflicts_with = "each-line")]
+    print_lines: bool,
+    #[clap(long, takes_value = false)]
+    args: bool,
+    #[clap(long, takes_value = false, conflicts_with_all = &["each-line", "print-lines"])]
+    eval_input: bool,
+    #[clap(short = 'x', long, takes_value = false)]
+    extensions: bool,
+    #[clap(long, takes_value = false)]
+    compat: bool,
+    #[clap(long, value_enum, conflicts_with_all = &["extensions", "compat"])]
+    dialect: Option<Dialect>,
+    #[clap(long, takes_value = false)]
+    explain_coercions: bool,
+    #[clap(long, takes_value = false)]
+    log_draws: bool,
+    #[clap(long, takes_value = false)]
+    unicode: bool,
+    #[clap(long, takes_value = false)]
+    lenient: bool,
+    #[clap(long, takes_value = false)]
+    pretty: bool,
+    #[clap(long, takes_value = false)]
+    no_warnings: bool,
+    #[clap(long, value_enum, default_value = "human")]
+    diagnostics: DiagnosticFormat,
+    #[clap(long, value_enum, value_name = "MODE")]
+    utf8_output: Option<Utf8Mode>,
+    #[clap(long, value_name = "DIR")]
+    allow_fs: Option<String>,
+    #[clap(long, takes_value = false)]
+    allow_env: bool,
+    #[clap(long, takes_value = false)]
+    allow_exec: bool,
+    #[clap(long, takes_value = false)]
+    allow_net: bool,
+    #[clap(long, value_name = "N")]
+    max_steps: Option<u64>,
+    #[clap(long, value_name = "SECONDS")]
+    timeout: Option<f64>,
+    #[clap(long, value_name = "BYTES")]
+    max_output: Option<u64>,
+    #[clap(long, value_name = "N", default_value_t = DEFAULT_MAX_DEPTH)]
+    max_depth: usize,
+    #[clap(long, value_name = "N")]
+    max_array_len: Option<usize>,
+    #[clap(long, value_name = "N")]
+    max_string_len: Option<usize>,
+    #[clap(long, value_name = "N")]
+    max_int_bits: Option<u64>,
+    #[clap(long, value_enum)]
+    prelude: Option<Prelude>,
+    #[clap(long, value_name = "CODE", allow_hyphen_values = true)]
+    prologue: Option<String>,
+    #[clap(long, value_name = "CODE", allow_hyphen_values = true)]
+    epilogue: Option<String>,
+    #[clap(long, value_enum, default_value = "lcg")]
+    rng: RngKind,
+    #[clap(long)]
+    seed: Option<u64>,
+    #[clap(long, takes_value = false, conflicts_with = "utf8-output")]
+    check_expectations: bool,
+    #[clap(long, takes_value = false)]
+    stats: bool,
+    #[clap(long, takes_value = false)]
+    post_mortem: bool,
+    #[clap(long, takes_value = false)]
+    coverage: bool,
+    #[clap(long, value_enum, value_name = "FORMAT")]
+    heatmap: Option<HeatmapFormat>,
+    #[clap(long, value_name = "FILE")]
+    flamegraph: Option<String>,
+    #[clap(long, value_name = "FILE")]
+    timeline: Option<String>,
+    #[clap(long, value_name = "FILE")]
+    audit: Option<String>,
+    #[clap(long, value_name = "FILE")]
+    record: Option<String>,
+    #[clap(long, value_name = "FILE", conflicts_with_all = &["record", "code", "code-path"])]
+    replay: Option<String>,
+    #[clap(long, value_name = "FILE", conflicts_with = "replay")]
+    load_state: Option<String>,
+    #[clap(long, value_name = "FILE")]
+    save_state: Option<String>,
+    #[clap(long, value_name = "FILE")]
+    tee: Vec<String>,
+    #[clap(
+        long,
+        takes_value = false,
+        conflicts_with_all = &["args", "utf8-output", "output-separator", "output-lines", "no-trailing-newline"]
+    )]
+    verify: bool,
+    #[clap(long, value_name = "PATH")]
+    reference: Option<String>,
+    args_vec: Vec<String>,
+}
+
+/// Output kept for `--check-expectations` to compare.
+#[derive(Clone, Default)]
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
     }
 
-    fn push(&mut self, val: Gval) {
-        self.stack.push(val)
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
+}
+
+/// Output written to two places: printed, and kept for `--verify` or written
+/// to a `--tee` file.
+struct Tee<A: Write, B: Write>(A, B);
 
-    fn top(&self) -> &Gval {
-        self.stack.last().expect("stack underflow")
+impl<A: Write, B: Write> Write for Tee<A, B> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write_all(buf)?;
+        self.1.write_all(buf)?;
+        Ok(buf.len())
     }
 
-    fn dup(&mut self) {
-        let a = self.pop();
-        self.push(a.clone());
-        self.push(a);
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()?;
+        self.1.flush()
     }
+}
 
-    fn pop(&mut self) -> Gval {
-        let mut i = self.lb.len();
-        while i > 0 && self.lb[i - 1] >= self.stack.len() {
-            i -= 1;
-            if self.lb[i] > 0 {
-                self.lb[i] -= 1;
-            }
+/// The flags that change what a program does, to record with it.
+fn semantic_flags(cli: &RunArgs) -> Vec<String> {
+    let mut flags = vec![];
+    for (on, flag) in [
+        (cli.extensions, "--extensions"),
+        (cli.compat, "--compat"),
+        (cli.unicode, "--unicode"),
+        (cli.lenient, "--lenient"),
+        (cli.pretty, "--pretty"),
+        (cli.no_implicit_output, "--no-implicit-output"),
+        (cli.eval_input, "--eval-input"),
+        (cli.output_lines, "--output-lines"),
+        (cli.no_trailing_newline, "--no-trailing-newline"),
+        (matches!(cli.prelude, Some(Prelude::Std)), "--prelude=std"),
+    ] {
+        if on {
+            flags.push(flag.to_string());
+        }
+    }
+    for (flag, value) in [
+        ("--output-separator", &cli.output_separator),
+        ("--prologue", &cli.prologue),
+        ("--epilogue", &cli.epilogue),
+    ] {
+        if let Some(value) = value {
+            flags.push(format!("{}={}", flag, value));
         }
-        self.stack.pop().expect("stack underflow")
     }
+    flags
+}
+
+/// Set the flags in `semantic_flags` from a record, keeping the others.
+fn apply_flags(cli: &mut RunArgs, flags: &[String]) {
+    let argv = std::iter::once("golfscript-rs").chain(flags.iter().map(String::as_str));
+    let recorded = Cli::parse_from(argv).run;
+    cli.extensions = recorded.extensions;
+    cli.compat = recorded.compat;
+    cli.unicode = recorded.unicode;
+    cli.lenient = recorded.lenient;
+    cli.pretty = recorded.pretty;
+    cli.no_implicit_output = recorded.no_implicit_output;
+    cli.eval_input = recorded.eval_input;
+    cli.output_separator = recorded.output_separator;
+    cli.output_lines = recorded.output_lines;
+    cli.no_trailing_newline = recorded.no_trailing_newline;
+    cli.prologue = recorded.prologue;
+    cli.epilogue = recorded.epilogue;
+    cli.prelude = recorded.prelude;
+}
 
-    fn tilde(&mut self) {
-        match self.pop() {
-            Gval::Int(n) => self.push(Gval::Int(!n)),
-            Gval::Arr(vs) => self.stack.extend(vs),
-            Gval::Str(bs) => self.run(&bs),
-            Gval::Blk(bs) => self.run(&bs),
+fn main() {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if cli.command.is_none() {
+        cli.run.pipeline = pipeline(&matches);
+    }
+    // `golfscript-rs file.gs`, as a `#!` line runs a script.
+    if let Some(args) = run_args(&mut cli) {
+        let unnamed = args.code.is_empty() && args.code_path.is_empty() && args.replay.is_none();
+        if unnamed && !args.args_vec.is_empty() {
+            args.code_path.push(args.args_vec.remove(0));
+        }
+        // A dialect is its flags, as records and states note them.
+        if let Some(dialect) = args.dialect {
+            args.extensions = dialect.extensions();
+            args.compat = dialect.compat();
+        }
+    }
+    install_panic_hook();
+    // Blocks and `~` recurse natively, so make room for `--max-depth` levels
+    // even in a debug build.
+    let max_depth = match &cli.command {
+        Some(Command::Debug(args) | Command::Repl(args) | Command::Watch(args)) => args.max_depth,
+        #[cfg(feature = "jupyter")]
+        Some(Command::Kernel(args)) => args.run.max_depth,
+        _ => cli.run.max_depth,
+    };
+    let stack_size = (64 << 20) + max_depth.saturating_mul(64 << 10);
+    let interpreter = std::thread::Builder::new()
+        .stack_size(stack_size)
+        .spawn(move || run(cli));
+    match interpreter.map(|t| t.join()) {
+        Ok(Ok(())) => {}
+        Ok(Err(_)) => std::process::exit(101),
+        Err(e) => {
+            eprintln!("couldn't start the interpreter: {}", e);
+            std::process::exit(1)
         }
     }
+}
 
-    fn backtick(&mut self) {
-        let bs = self.pop().inspect();
-        self.push(Gval::Str(bs));
+/// Run `code` once for each line of `lines`, without its newline, as
+/// `--each-line` and `--print-lines` do: each line starts on an empty stack
+/// but keeps the variables the last left. With `print`, the stack is printed
+/// after each line as it would be at the end of a program.
+fn each_line(gs: &mut Gs, code: &[u8], mut lines: impl BufRead, cli: &RunArgs) -> GsResult<()> {
+    loop {
+        let mut line = vec![];
+        match lines.read_until(b'\n', &mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {}
+            Err(e) => return Err(GsError::Runtime(format!("can't read input: {}", e))),
+        }
+        if line.ends_with(b"\n") {
+            line.pop();
+        }
+        gs.stack = vec![Gval::Str(line)];
+        run_snippet(gs, cli, "--prologue", &cli.prologue);
+        gs.run(code)?;
+        run_snippet(gs, cli, "--epilogue", &cli.epilogue);
+        if cli.print_lines {
+            print_stack(gs, cli)?;
+        }
+        gs.stack.clear();
+        gs.flush()?;
     }
+}
 
-    fn bang(&mut self) {
-        let f = self.pop().falsey();
-        self.push(Gval::bool(f));
+/// The arguments of the command, if it takes a program.
+fn run_args(cli: &mut Cli) -> Option<&mut RunArgs> {
+    match &mut cli.command {
+        None => Some(&mut cli.run),
+        Some(
+            Command::Debug(args)
+            | Command::Repl(args)
+            | Command::Watch(args)
+            | Command::Check(args)
+            | Command::Fmt(args)
+            | Command::Minify(args)
+            | Command::Graph(args)
+            | Command::Score(args)
+            | Command::Golf(args),
+        ) => Some(args),
+        Some(Command::Transpile(args)) => Some(&mut args.run),
+        Some(Command::Highlight(args)) => Some(&mut args.run),
+        #[cfg(feature = "jupyter")]
+        Some(Command::Kernel(args)) => Some(&mut args.run),
+        _ => None,
     }
+}
 
-    fn at_sign(&mut self) {
-        let c = self.pop();
-        let b = self.pop();
-        let a = self.pop();
-        self.push(b);
-        self.push(c);
-        self.push(a);
+/// A program given with `--code` or `--code-path`.
+#[derive(Clone, Debug)]
+enum Source {
+    Code(String),
+    Path(String),
+}
+
+impl Source {
+    fn read(&self, allow_net: bool) -> Vec<u8> {
+        match self {
+            Source::Code(code) => code.as_bytes().to_vec(),
+            Source::Path(path) => read_path(path, allow_net),
+        }
     }
 
-    fn dollar(&mut self) {
-        match self.pop() {
-            Gval::Int(n) => {
-                let len: BigInt = self.stack.len().into();
-                if n < (-1i32).into() {
-                    if let Some(i) = (-n - 2i32).to_usize() {
-                        if i < self.stack.len() {
-                            self.push(self.stack[i].clone());
-                        }
-                    }
-                } else if n >= 0i32.into() && n < len {
-                    if let Some(i) = (len - 1i32 - n).to_usize() {
-                        self.push(self.stack[i].clone());
-                    }
-                }
-            }
-            Gval::Arr(mut vs) => {
-                vs.sort();
-                self.push(Gval::Arr(vs));
-            }
-            Gval::Str(mut bs) => {
-                bs.sort();
-                self.push(Gval::Str(bs));
+    fn path(&self) -> Option<&str> {
+        match self {
+            Source::Code(_) => None,
+            Source::Path(path) => Some(path),
+        }
+    }
+}
+
+/// The contents of the file at `path`, or with `--allow-net`, of an
+/// `https://` URL.
+fn read_path(path: &str, allow_net: bool) -> Vec<u8> {
+    if !path.starts_with("https://") && !path.starts_with("http://") {
+        return match std::fs::read(path) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1)
             }
-            Gval::Blk(code) => match self.pop() {
-                Gval::Int(_) => panic!("can't sort an integer"),
-                Gval::Arr(vs) => {
-                    let sorted = self.sort_by(code, vs);
-                    self.push(Gval::Arr(sorted));
-                }
-                Gval::Str(vs) => {
-                    let sorted = self.sort_by(code, vs);
-                    self.push(Gval::Str(sorted));
-                }
-                Gval::Blk(vs) => {
-                    let sorted = self.sort_by(code, vs);
-                    self.push(Gval::Blk(sorted));
-                }
-            },
+        };
+    }
+    if !path.starts_with("https://") {
+        eprintln!("{}: only https:// URLs can be read", path);
+        std::process::exit(1)
+    }
+    if !allow_net {
+        eprintln!("{}: reading a URL needs --allow-net", path);
+        std::process::exit(1)
+    }
+    match http_get(path, None) {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(1)
         }
     }
+}
 
-    fn sort_by<T: Ord + Clone + Into<Gval>>(&mut self, code: Vec<u8>, vs: Vec<T>) -> Vec<T> {
-        let mut results: Vec<(Gval, T)> = vec![];
-        for v in vs {
-            self.push(v.clone().into());
-            self.run(&code);
-            results.push((self.pop(), v));
+/// The `--code` and `--code-path` programs, in the order they were given.
+fn pipeline(matches: &ArgMatches) -> Vec<Source> {
+    let mut stages = vec![];
+    for (id, source) in [
+        ("code", Source::Code as fn(String) -> Source),
+        ("code-path", Source::Path),
+    ] {
+        if let (Some(indices), Some(values)) =
+            (matches.indices_of(id), matches.get_many::<String>(id))
+        {
+            stages.extend(indices.zip(values).map(|(i, v)| (i, source(v.clone()))));
         }
-        results.sort_by(|a, b| a.0.cmp(&b.0));
-        results.into_iter().map(|x| x.1).collect()
     }
+    stages.sort_by_key(|&(i, _)| i);
+    stages.into_iter().map(|(_, source)| source).collect()
+}
 
-    fn plus(&mut self) {
-        let b = self.pop();
-        let a = self.pop();
-        self.push(a.plus(b));
+/// The programs given, in order if they're to run as a pipeline.
+fn sources(cli: &RunArgs) -> Vec<Source> {
+    if !cli.pipeline.is_empty() {
+        return cli.pipeline.clone();
     }
+    let paths = cli.code_path.iter().cloned().map(Source::Path);
+    paths
+        .chain(cli.code.iter().cloned().map(Source::Code))
+        .collect()
+}
 
-    fn minus(&mut self) {
-        let b = self.pop();
-        let a = self.pop();
-        match coerce(a, b) {
-            Coerced::Ints(x, y) => self.push(Gval::Int(x - y)),
-            Coerced::Arrs(x, y) => self.push(Gval::Arr(set_subtract(x, y))),
-            Coerced::Strs(x, y) => self.push(Gval::Str(set_subtract(x, y))),
-            Coerced::Blks(x, y) => self.push(Gval::Blk(set_subtract(x, y))),
-        }
+/// The file the program, or the last in a pipeline, came from.
+fn code_file(cli: &RunArgs) -> Option<&str> {
+    match cli.pipeline.last() {
+        Some(source) => source.path(),
+        None => cli.code_path.last().map(String::as_str),
     }
+}
 
-    fn asterisk(&mut self) {
-        let b = self.pop();
-        let a = self.pop();
-        use Gval::*;
-        match (a, b) {
-            // multiply
-            (Int(a), Int(b)) => self.push(Int(a * b)),
-            // join
-            (Arr(a), Arr(sep)) => self.push(join(a, Arr(sep))),
-            (Arr(a), Str(sep)) | (Str(sep), Arr(a)) => self.push(join(a, Str(sep))),
-            (Str(a), Str(sep)) => {
-                let a: Vec<Gval> = a.into_iter().map(|x| Gval::Str(vec![x])).collect();
-                self.push(join(a, Str(sep)));
-            }
+/// The program named by `--code` or `--code-path`, or `None` with a message
+/// if there isn't exactly one.
+fn program(cli: &RunArgs) -> Option<Vec<u8>> {
+    let sources = sources(cli);
+    if sources.len() > 1 {
+        eprintln!("only running takes more than one --code or --code-path");
+        None
+    } else if let Some(source) = sources.first() {
+        Some(source.read(cli.allow_net))
+    } else {
+        eprintln!(
+            r"No code provided. Try:
 
-            // fold
-            (Blk(code), Blk(a)) | (Str(a), Blk(code)) | (Blk(code), Str(a)) => self.fold(code, a),
-            (Arr(a), Blk(code)) | (Blk(code), Arr(a)) => self.fold(code, a),
+    golfscript-rs --help
+    golfscript-rs --code '~{{.@\%.}}do;'   --input '140 150'
+    golfscript-rs --code 'n*~{{.@\%.}}do;' --args 140 150   # code.golf style
+    golfscript-rs --code-path file.gs    --input-file input.txt
+    golfscript-rs --code-path file.gs    --input-from-stdin
+    golfscript-rs file.gs                 # same as --code-path file.gs
+"
+        );
+        None
+    }
+}
 
-            // repeat
-            (Int(n), Arr(a)) | (Arr(a), Int(n)) => self.push(Arr(repeat(a, n))),
-            (Int(n), Str(a)) | (Str(a), Int(n)) => self.push(Str(repeat(a, n))),
+/// Print what `tool` makes of the program.
+fn rewrite(cli: &RunArgs, name: &str, tool: fn(&[u8]) -> Result<Vec<u8>, String>) {
+    let Some(code) = program(cli) else {
+        std::process::exit(1)
+    };
+    match tool(&code) {
+        Ok(rewritten) => std::io::stdout().write_all(&rewritten).unwrap(),
+        Err(e) => {
+            eprintln!("{}: {}", name, e);
+            std::process::exit(1)
+        }
+    }
+}
 
-            // times
-            (Int(mut n), Blk(f)) | (Blk(f), Int(mut n)) => {
-                while n.is_positive() {
-                    self.run(&f);
-                    n -= 1;
-                }
+fn check_program(cli: RunArgs) {
+    let Some(code) = program(&cli) else {
+        std::process::exit(1)
+    };
+    let mut failed = false;
+    let report = |diagnostic: Diagnostic, len: usize| match cli.diagnostics {
+        DiagnosticFormat::Human => {
+            let severity = match diagnostic.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+            };
+            eprintln!("{}: {}", severity, diagnostic.message);
+            if let Some(at) = diagnostic.offset {
+                eprintln!("{}", snippet(&code, at, len));
             }
         }
+        DiagnosticFormat::Json => eprintln!("{}", diagnostic.to_json()),
+    };
+    if let Ok((rest, _)) = parse_code(&code) {
+        if !rest.is_empty() {
+            let (at, message) = diagnose(rest);
+            let e = GsError::Parse(ParseError::new(&code, offset(&code, at), message));
+            let diagnostic = Diagnostic::error(&e)
+                .file(code_file(&cli))
+                .at(&code, Some(offset(&code, at)));
+            report(diagnostic, 1);
+            failed = true;
+        }
+    }
+    for l in lint(&code) {
+        let diagnostic = Diagnostic::warning(l.code, l.message)
+            .file(code_file(&cli))
+            .at(&code, Some(l.offset));
+        report(diagnostic, l.len);
     }
+    if failed {
+        std::process::exit(1)
+    }
+}
 
-    fn slash(&mut self) {
-        let b = self.pop();
-        let a = self.pop();
-        use Gval::*;
-        match (a, b) {
-            // divide
-            (Int(a), Int(b)) => self.push(Int(a.div_floor(&b))),
-            // split
-            (Arr(a), Arr(sep)) => {
-                let s = split(a, sep, false);
-                self.push(Arr(s.into_iter().map(Arr).collect()));
-            }
-            (Str(a), Str(sep)) => {
-                let s = split(a, sep, false);
-                self.push(Arr(s.into_iter().map(Str).collect()));
-            }
-            (Arr(a), Str(sep)) | (Str(sep), Arr(a)) => {
-                let s = split(a, sep.into_iter().map(|x| x.into()).collect(), false);
-                self.push(Arr(s.into_iter().map(Arr).collect()));
+/// Run the program under the reference interpreter too and report whether
+/// its output matches what this run printed.
+fn verify(
+    reference: &Reference,
+    cli: &RunArgs,
+    code: &[u8],
+    input: &Gval,
+    printed: &Capture,
+    failed: bool,
+) -> bool {
+    let Gval::Str(input) = input else {
+        eprintln!("--verify: the reference interpreter only takes input as a string");
+        return false;
+    };
+    for flag in semantic_flags(cli) {
+        if flag != "--compat" && flag != "--eval-input" {
+            eprintln!("--verify: note: the reference interpreter has no {}", flag);
+        }
+    }
+    // The reference interpreter has no --eval-input, so its `~` is written out.
+    let run = if cli.eval_input {
+        [b"~", code].concat()
+    } else {
+        code.to_vec()
+    };
+    let theirs = match reference.run(&run, input) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            eprintln!("--verify: {}", e);
+            return false;
+        }
+    };
+    let ours = Outcome {
+        output: printed.0.borrow().clone(),
+        failed,
+    };
+    match compare(&theirs, &ours) {
+        None => {
+            eprintln!("output matches the reference interpreter");
+            true
+        }
+        Some(diff) => {
+            eprintln!("output differs from the reference interpreter (-) here (+):");
+            eprintln!("{}", diff);
+            for d in suspects(code, cli.compat) {
+                eprintln!("possibly a known divergence, {}: {}", d.name, d.note);
             }
+            false
+        }
+    }
+}
 
-            // each
-            (Str(a), Blk(code)) | (Blk(code), Str(a)) => self.each(code, a),
-            (Arr(a), Blk(code)) | (Blk(code), Arr(a)) => self.each(code, a),
-
-            // chunk
-            (Int(n), Arr(mut a)) | (Arr(mut a), Int(n)) => {
-                let c = chunk(&mut a, n);
-                self.push(Arr(c.into_iter().map(|x| Arr(x.to_owned())).collect()));
-            }
-            (Int(n), Str(mut a)) | (Str(mut a), Int(n)) => {
-                let c = chunk(&mut a, n);
-                self.push(Arr(c.into_iter().map(|x| Str(x.to_owned())).collect()));
+/// Compare each program's output here with the reference's, and fail if any
+/// differ.
+fn differential(args: DifferentialArgs) {
+    let timeout = match Duration::try_from_secs_f64(args.timeout) {
+        Ok(timeout) => timeout,
+        Err(e) => {
+            eprintln!("--timeout {}: {}", args.timeout, e);
+            std::process::exit(1)
+        }
+    };
+    let reference = match &args.reference {
+        Some(path) => Reference::new(path),
+        None => match Reference::from_env() {
+            Some(reference) => reference,
+            None => {
+                eprintln!("differential: pass --reference or set GOLFSCRIPT_RB");
+                std::process::exit(1)
             }
-
-            // unfold
-            (Blk(cond), Blk(step)) => {
-                let mut r = vec![];
-                loop {
-                    self.push(self.top().clone());
-                    self.run(&cond);
-                    if self.pop().falsey() {
-                        break;
-                    }
-                    r.push(self.top().clone());
-                    self.run(&step);
-                }
-                self.pop();
-                self.push(Gval::Arr(r));
+        },
+    };
+    let reference = reference.timeout(timeout);
+    let (mut same, mut known, mut differ, mut skipped) = (0, 0, 0, 0);
+    for path in &args.programs {
+        let code = match std::fs::read(path) {
+            Ok(code) => code,
+            Err(e) => {
+                println!("skip {}: {}", path, e);
+                skipped += 1;
+                continue;
             }
-
-            (Blk(_), Int(_)) | (Int(_), Blk(_)) => {
-                panic!("int-block /")
+        };
+        let input_path = std::path::Path::new(path).with_extension("in");
+        let input = std::fs::read(input_path).unwrap_or_default();
+        let theirs = match reference.run(&code, &input) {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                println!("skip {}: the reference {}", path, e);
+                skipped += 1;
+                continue;
             }
+        };
+        let ours = run_ours(&code, &input, args.compat, timeout);
+        let Some(diff) = compare(&theirs, &ours) else {
+            println!("ok   {}", path);
+            same += 1;
+            continue;
+        };
+        let causes = suspects(&code, args.compat);
+        match causes.is_empty() {
+            true => differ += 1,
+            false => known += 1,
         }
+        println!("DIFF {}", path);
+        for line in diff.lines() {
+            println!("     {}", line);
+        }
+        for d in causes {
+            println!("     possibly a known divergence, {}: {}", d.name, d.note);
+        }
+    }
+    println!(
+        "{} same, {} different, {} of them possibly known, {} skipped",
+        same,
+        differ + known,
+        known,
+        skipped
+    );
+    if differ > 0 || skipped > 0 {
+        std::process::exit(1)
     }
+}
 
-    fn percent(&mut self) {
-        let b = self.pop();
-        let a = self.pop();
-        use Gval::*;
-        match (a, b) {
-            // modulo
-            (Int(a), Int(b)) => self.push(Int(a.mod_floor(&b))),
-            // clean split
-            (Arr(a), Arr(sep)) => {
-                let s = split(a, sep, true);
-                self.push(Arr(s.into_iter().map(Arr).collect()));
-            }
-            (Str(a), Str(sep)) => {
-                let s = split(a, sep, true);
-                self.push(Arr(s.into_iter().map(Str).collect()));
+fn generate(args: GenerateArgs) {
+    let seed = args.seed.unwrap_or_else(|| {
+        let seed = entropy_seed();
+        eprintln!("generate: --seed {}", seed);
+        seed
+    });
+    let mut generator = Generator::new(seed).max_depth(args.max_depth);
+    if let Some(dir) = &args.out {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            eprintln!("--out {}: {}", dir, e);
+            std::process::exit(1)
+        }
+    }
+    let mut crashes = 0;
+    for i in 0..args.count {
+        let code = generator.program(args.size);
+        if let Some(dir) = &args.out {
+            let path = std::path::Path::new(dir).join(format!("gen{:05}.gs", i));
+            if let Err(e) = std::fs::write(&path, &code) {
+                eprintln!("--out {}: {}", path.display(), e);
+                std::process::exit(1)
             }
-            (Arr(a), Str(sep)) | (Str(sep), Arr(a)) => {
-                let s = split(a, sep.into_iter().map(|x| x.into()).collect(), true);
-                self.push(Arr(s.into_iter().map(Arr).collect()));
+        } else if args.stress {
+            let mut gs = Gs::builder()
+                .max_steps(10_000)
+                .timeout(Duration::from_secs(2))
+                .limits(Limits {
+                    array_len: Some(10_000),
+                    string_len: Some(10_000),
+                    int_bits: Some(10_000),
+                })
+                .input(Box::new(std::io::empty()))
+                .output(Box::new(std::io::sink()))
+                .build();
+            gs.stack.push(Gval::Str(vec![]));
+            if let Err(GsError::Internal(e)) = gs.run(&code) {
+                println!("internal error: {}", e);
+                println!("  {}", String::from_utf8_lossy(&code));
+                crashes += 1;
             }
+        } else {
+            println!("{}", String::from_utf8_lossy(&code));
+        }
+    }
+    if args.stress {
+        eprintln!("{} programs, {} crashed", args.count, crashes);
+        if crashes > 0 {
+            std::process::exit(1)
+        }
+    }
+}
 
-            // map
-            (Arr(a), Blk(code)) | (Blk(code), Arr(a)) => {
-                let r = self.gs_map(code, a);
-                self.push(Arr(r))
+fn golf_program(cli: RunArgs) {
+    let Some(code) = program(&cli) else {
+        std::process::exit(1)
+    };
+    let suggestions = suggest(&code);
+    let plural = |n: usize| if n == 1 { "" } else { "s" };
+    for s in &suggestions {
+        let message = format!("{} (saves {} byte{})", s.message, s.saves, plural(s.saves));
+        match cli.diagnostics {
+            DiagnosticFormat::Human => {
+                println!("{}", message);
+                println!("{}", snippet(&code, s.offset, s.len.max(1)));
             }
-            (Str(a), Blk(code)) | (Blk(code), Str(a)) => {
-                let r = self.gs_map(code, a);
-                self.push(Str(flatten(r)))
+            DiagnosticFormat::Json => {
+                let diagnostic = Diagnostic::warning(s.code, message)
+                    .file(code_file(&cli))
+                    .at(&code, Some(s.offset));
+                println!("{}", diagnostic.to_json());
             }
+        }
+    }
+    if cli.diagnostics == DiagnosticFormat::Human {
+        let saved: usize = suggestions.iter().map(|s| s.saves).sum();
+        match suggestions.len() {
+            0 => println!("no suggestions"),
+            n => println!(
+                "{} suggestion{}, saving {} byte{}",
+                n,
+                plural(n),
+                saved,
+                plural(saved)
+            ),
+        }
+    }
+}
 
-            // every nth
-            (Int(n), Arr(a)) | (Arr(a), Int(n)) => self.push(Arr(every_nth(a, n))),
-            (Int(n), Str(a)) | (Str(a), Int(n)) => self.push(Str(every_nth(a, n))),
-
-            // unimplemented
-            (Int(_), Blk(_)) | (Blk(_), Int(_)) | (Blk(_), Blk(_)) => panic!("%"),
+fn run(cli: Cli) {
+    match cli.command {
+        Some(Command::Lsp) => serve_lsp(),
+        Some(Command::Doc {
+            name: None,
+            dialect,
+        }) => list_builtins(dialect),
+        Some(Command::Doc {
+            name: Some(name), ..
+        }) => document(&name),
+        Some(Command::Learn { lesson }) => teach(lesson),
+        Some(Command::Check(args)) => check_program(*args),
+        Some(Command::Fmt(args)) => rewrite(&args, "fmt", format),
+        Some(Command::Minify(args)) => rewrite(&args, "minify", minify),
+        Some(Command::Graph(args)) => {
+            rewrite(&args, "graph", |code| dot(code).map(String::into_bytes))
         }
+        Some(Command::Transpile(args)) => transpile_program(&args),
+        Some(Command::Highlight(args)) => highlight_program(&args),
+        Some(Command::Differential(args)) => differential(*args),
+        Some(Command::Generate(args)) => generate(*args),
+        Some(Command::Golf(args)) => golf_program(*args),
+        Some(Command::Score(args)) => score_program(&args),
+        Some(Command::Debug(args)) => run_program(*args, Mode::Debug),
+        Some(Command::Repl(args)) => run_program(*args, Mode::Repl),
+        Some(Command::Watch(args)) => run_program(*args, Mode::Watch),
+        #[cfg(feature = "jupyter")]
+        Some(Command::Kernel(args)) if args.install => install_kernel(),
+        #[cfg(feature = "jupyter")]
+        Some(Command::Kernel(args)) => {
+            match args.connection_file {
+                Some(path) => run_program(args.run, Mode::Kernel(path)),
+                None => {
+                    eprintln!("kernel needs --connection-file, which Jupyter passes; --install sets that up");
+                    std::process::exit(1)
+                }
+            }
+        }
+        None => run_program(cli.run, Mode::Run),
     }
+}
 
-    fn vertical_bar(&mut self) {
-        let b = self.pop();
-        let a = self.pop();
-        self.push(match coerce(a, b) {
-            Coerced::Ints(x, y) => Gval::Int(x | y),
-            Coerced::Arrs(x, y) => Gval::Arr(set_or(x, y)),
-            Coerced::Strs(x, y) => Gval::Str(set_or(x, y)),
-            Coerced::Blks(x, y) => Gval::Blk(set_or(x, y)),
-        })
+fn serve_lsp() {
+    let stdin = std::io::stdin();
+    if let Err(e) = golfscript_rs::lsp::serve(stdin.lock(), std::io::stdout()) {
+        eprintln!("lsp: {}", e);
+        std::process::exit(1)
     }
+}
 
-    fn ampersand(&mut self) {
-        let b = self.pop();
-        let a = self.pop();
-        self.push(match coerce(a, b) {
-            Coerced::Ints(x, y) => Gval::Int(x & y),
-            Coerced::Arrs(x, y) => Gval::Arr(set_and(x, y)),
-            Coerced::Strs(x, y) => Gval::Str(set_and(x, y)),
-            Coerced::Blks(x, y) => Gval::Blk(set_and(x, y)),
-        })
+/// List the builtins of `dialect`, the extended one unless it's given.
+fn list_builtins(dialect: Option<Dialect>) {
+    let dialect = dialect.unwrap_or(Dialect::Extended);
+    for b in dialect.builtins() {
+        println!("{:<10} {}", b.name, b.doc);
     }
+}
 
-    fn caret(&mut self) {
-        let b = self.pop();
-        let a = self.pop();
-        self.push(match coerce(a, b) {
-            Coerced::Ints(x, y) => Gval::Int(x ^ y),
-            Coerced::Arrs(x, y) => Gval::Arr(set_xor(x, y)),
-            Coerced::Strs(x, y) => Gval::Str(set_xor(x, y)),
-            Coerced::Blks(x, y) => Gval::Blk(set_xor(x, y)),
-        })
+/// Print the reference for the builtin `name`.
+fn document(name: &str) {
+    match reference(name) {
+        Some(text) => println!("{}", text),
+        None => {
+            eprintln!("no builtin named `{}`; `doc` lists them", name);
+            std::process::exit(1)
+        }
     }
+}
 
-    fn lteqgt(&mut self, ordering: Ordering) {
-        let b = self.pop();
-        let a = self.pop();
-        use Gval::*;
-        use Ordering::*;
-        match (ordering, a, b) {
-            (Equal, Int(i), Arr(a)) | (Equal, Arr(a), Int(i)) => {
-                if let Some(x) = index(&a, i) {
-                    self.push(x.clone())
-                }
-            }
-            (Equal, Int(i), Str(a))
-            | (Equal, Str(a), Int(i))
-            | (Equal, Int(i), Blk(a))
-            | (Equal, Blk(a), Int(i)) => {
-                if let Some(x) = index(&a, i) {
-                    self.push((*x).into())
-                }
-            }
-            (o, Int(i), Arr(a)) | (o, Arr(a), Int(i)) => self.push(Arr(slice(o, a, i))),
-            (o, Int(i), Str(a)) | (o, Str(a), Int(i)) => self.push(Str(slice(o, a, i))),
-            (o, Int(i), Blk(a)) | (o, Blk(a), Int(i)) => self.push(Blk(slice(o, a, i))),
-            (o, x, y) => self.push(Gval::bool(x.cmp(&y) == o)),
+/// Go through `lesson`, the first unless it's given, on the terminal.
+fn teach(lesson: Option<usize>) {
+    let lesson = lesson.unwrap_or(1);
+    if !(1..=LESSONS.len()).contains(&lesson) {
+        eprintln!("there are lessons 1 to {}", LESSONS.len());
+        std::process::exit(1)
+    }
+    let stdin = std::io::stdin();
+    if let Err(e) = learn::run(lesson, stdin.lock(), std::io::stdout()) {
+        eprintln!("learn: {}", e);
+        std::process::exit(1)
+    }
+}
+
+fn transpile_program(args: &TranspileArgs) {
+    let Some(code) = program(&args.run) else {
+        std::process::exit(1)
+    };
+    let name = code_file(&args.run).unwrap_or("program");
+    match transpile(&code, name, args.to) {
+        Ok(source) => print!("{}", source),
+        Err(e) => {
+            eprintln!("transpile: {}", e);
+            std::process::exit(1)
         }
     }
+}
 
-    fn comma(&mut self) {
-        use Gval::*;
-        match self.pop() {
-            Int(n) => {
-                let mut r = vec![];
-                let mut i = BigInt::zero();
-                while i < n {
-                    r.push(Int(i.clone()));
-                    i += 1i32;
-                }
-                self.push(Arr(r));
-            }
-            Arr(a) => self.push(a.len().into()),
-            Str(a) => self.push(a.len().into()),
-            Blk(code) => match self.pop() {
-                Int(_) => panic!("select on integer"),
-                Arr(a) => {
-                    let r = self.select(code, a);
-                    self.push(Arr(r))
-                }
-                Str(a) => {
-                    let r = self.select(code, a);
-                    self.push(Str(r))
-                }
-                Blk(a) => {
-                    let r = self.select(code, a);
-                    self.push(Blk(r))
-                }
-            },
+fn highlight_program(args: &HighlightArgs) {
+    let Some(code) = program(&args.run) else {
+        std::process::exit(1)
+    };
+    match args.format {
+        HighlightFormat::Ansi => print!("{}", highlight::ansi(&code)),
+        HighlightFormat::Html => {
+            let title = code_file(&args.run).unwrap_or("GolfScript");
+            print!("{}", highlight::html(&code, title))
         }
     }
+}
 
-    fn question(&mut self) {
-        let b = self.pop();
-        let a = self.pop();
-        use Gval::*;
-        match (a, b) {
-            // power
-            (Int(a), Int(b)) => self.push(Int(match b.to_u32() {
-                Some(e) => a.pow(e),
-                None => BigInt::zero(),
-            })),
-
-            // indexof
-            (Arr(h), n @ Int(_))
-            | (n @ Int(_), Arr(h))
-            | (Arr(h), n @ Str(_))
-            | (n @ Str(_), Arr(h))
-            | (Arr(h), n @ Arr(_)) => self.push(Gval::Int(
-                h.iter()
-                    .position(|x| *x == n)
-                    .map_or(-BigInt::one(), BigInt::from),
-            )),
-            (Str(h), Int(n)) | (Int(n), Str(h)) => self.push(Gval::Int(match n.to_u8() {
-                None => -BigInt::one(),
-                Some(b) => h
-                    .iter()
-                    .position(|x| *x == b)
-                    .map_or(-BigInt::one(), BigInt::from),
-            })),
-            (Str(h), Str(n)) => self.push(Gval::Int(string_index(&h, &n))),
-
-            // find
-            (Int(_), Blk(_)) | (Blk(_), Int(_)) => panic!(),
-            (Blk(code), Blk(a)) | (Blk(code), Str(a)) | (Str(a), Blk(code)) => self.find(code, a),
-            (Blk(code), Arr(a)) | (Arr(a), Blk(code)) => self.find(code, a),
-        }
-    }
-
-    fn left_paren(&mut self) {
-        use Gval::*;
-        match self.pop() {
-            Int(n) => self.push(Int(n - 1i32)),
-            Arr(a) => {
-                self.push(Arr(a[1..].to_vec()));
-                self.push(a[0].clone());
-            }
-            Str(a) => {
-                self.push(Str(a[1..].to_vec()));
-                self.push(a[0].into());
-            }
-            Blk(a) => {
-                self.push(Blk(a[1..].to_vec()));
-                self.push(a[0].into());
-            }
-        }
-    }
-
-    fn right_paren(&mut self) {
-        use Gval::*;
-        match self.pop() {
-            Int(n) => self.push(Int(n + 1i32)),
-            Arr(mut a) => {
-                let l = a.pop().unwrap();
-                self.push(Arr(a.to_vec()));
-                self.push(l);
-            }
-            Str(mut a) => {
-                let l = a.pop().unwrap();
-                self.push(Str(a.to_vec()));
-                self.push(l.into());
-            }
-            Blk(mut a) => {
-                let l = a.pop().unwrap();
-                self.push(Blk(a.to_vec()));
-                self.push(l.into());
-            }
-        }
-    }
-
-    fn rng(&mut self) -> u64 {
-        let (m, _) = self.rng_state.overflowing_mul(1664525);
-        let (m, _) = m.overflowing_add(1013904223);
-        self.rng_state = m;
-        self.rng_state
-    }
-
-    fn rand(&mut self) {
-        let r = match self.pop() {
-            Gval::Int(n) if n.is_positive() => self.rng() % n,
-            _ => BigInt::zero(),
-        };
-        self.push(Gval::Int(r));
+fn score_program(cli: &RunArgs) {
+    let Some(code) = program(cli) else {
+        std::process::exit(1)
+    };
+    println!("{}", score::report(&code));
+}
+
+/// Tell Jupyter how to start the kernel with the flags given after
+/// `kernel`.
+#[cfg(feature = "jupyter")]
+fn install_kernel() {
+    // Everything after `kernel` but `--install` is for the kernel.
+    let flags: Vec<String> = std::env::args()
+        .skip_while(|a| a != "kernel")
+        .skip(1)
+        .filter(|a| a != "--install")
+        .collect();
+    match jupyter::install(&flags) {
+        Ok(path) => println!("installed the GolfScript kernel in {}", path.display()),
+        Err(e) => {
+            eprintln!("kernel --install: {}", e);
+            std::process::exit(1)
+        }
     }
+}
 
-    fn do_loop(&mut self) {
-        let a = self.pop();
-        loop {
-            self.go(a.clone());
-            if self.pop().falsey() {
-                break;
+/// Run the program, or a session taking code as it comes, in `mode`.
+fn run_program(cli: RunArgs, mode: Mode) {
+    let mut session = Session::new(cli, mode);
+    match &session.mode {
+        #[cfg(feature = "jupyter")]
+        Mode::Kernel(path) => {
+            let path = path.clone();
+            session.begin();
+            if let Err(e) = jupyter::serve(&mut session.gs, session.kernel_output.clone(), &path) {
+                eprintln!("kernel: {}", e);
+                std::process::exit(1)
+            }
+            save_state(&session.cli, &session.gs, &session.state_flags);
+        }
+        Mode::Repl => {
+            session.begin();
+            let stdin = std::io::stdin();
+            let result = if cfg!(feature = "editor") && stdin.is_terminal() {
+                repl::edit(&mut session.gs, std::io::stdout())
+            } else {
+                repl::run(&mut session.gs, stdin.lock(), std::io::stdout())
+            };
+            if let Err(e) = result {
+                eprintln!("repl: {}", e);
+                std::process::exit(1)
             }
+            save_state(&session.cli, &session.gs, &session.state_flags);
+        }
+        Mode::Watch => {
+            session.begin();
+            watch(session.gs, &session.cli);
         }
+        Mode::Run | Mode::Debug => session.run_to_end(),
     }
+}
 
-    fn while_loop(&mut self, which: bool) {
-        let b = self.pop();
-        let a = self.pop();
-        loop {
-            self.go(a.clone());
-            if self.pop().falsey() == which {
-                break;
+/// An interpreter set up as the flags ask, with what's needed to run a
+/// program on it and report on the run.
+struct Session {
+    cli: RunArgs,
+    mode: Mode,
+    gs: Gs,
+    replay: Option<Record>,
+    /// The flags that change what the program does, and those of them that
+    /// matter to a state.
+    flags: Vec<String>,
+    state_flags: Vec<String>,
+    input: Gval,
+    /// What the first program starts with on top of any loaded stack.
+    start: Vec<Gval>,
+    prelude: &'static [u8],
+    /// What the program printed, for `--check-expectations` and `--verify`.
+    capture: Capture,
+    reference: Option<Reference>,
+    #[cfg(feature = "jupyter")]
+    kernel_output: jupyter::Output,
+}
+
+impl Session {
+    /// Set up to run in `mode` as `cli` asks, exiting if the flags don't go
+    /// together or what they name can't be used.
+    fn new(mut cli: RunArgs, mode: Mode) -> Session {
+        let replay = cli.replay.as_ref().map(|path| match Record::load(path) {
+            Ok(record) => record,
+            Err(e) => {
+                eprintln!("--replay {}: {}", path, e);
+                std::process::exit(1)
             }
-            self.go(b.clone());
+        });
+        if let Some(record) = &replay {
+            apply_flags(&mut cli, &record.flags);
+        }
+        let flags = semantic_flags(&cli);
+        if mode != Mode::Run && cli.input_from_stdin {
+            eprintln!("stdin is read interactively, so input can't come from it too");
+            std::process::exit(1)
+        }
+        if mode != Mode::Run && (cli.each_line || cli.print_lines) {
+            eprintln!("--each-line and --print-lines only work when running a program");
+            std::process::exit(1)
+        }
+        let mut builder = builder(&cli, &mode, replay.as_ref());
+        let reference = reference_for(&cli);
+        let capture = Capture::default();
+        if cli.check_expectations {
+            builder = builder.output(Box::new(capture.clone()));
+        } else if cli.verify {
+            let out = BufWriter::new(std::io::stdout());
+            builder = builder.output(Box::new(Tee(out, capture.clone())));
+        }
+        if let Some(mode) = cli.utf8_output {
+            let out = BufWriter::new(std::io::stdout());
+            builder = builder.output(Box::new(Utf8Writer::new(out, mode)));
+        }
+        #[cfg(feature = "jupyter")]
+        let kernel_output = jupyter::Output::default();
+        #[cfg(feature = "jupyter")]
+        if let Mode::Kernel(_) = mode {
+            builder = builder
+                .input(Box::new(std::io::empty()))
+                .output(Box::new(kernel_output.clone()));
+        }
+        let mut gs = builder.build();
+        tee(&mut gs, &cli);
+        // How values are printed doesn't matter to a state.
+        let printing = [
+            "--no-implicit-output",
+            "--pretty",
+            "--output-lines",
+            "--no-trailing-newline",
+        ];
+        let state_flags: Vec<String> = flags
+            .iter()
+            .filter(|f| !printing.contains(&f.as_str()) && !f.starts_with("--output-separator="))
+            .cloned()
+            .collect();
+        load_state(&mut gs, &cli, &state_flags);
+        let input = input(&cli, replay.as_ref());
+        let start = if cli.eval_input {
+            let below = gs.stack.len();
+            gs.stack.push(input.clone());
+            if let Err(e) = gs.run(b"~") {
+                eprintln!("--eval-input: the input failed as code");
+                report_error(&cli, None, b"~", &gs, &e);
+                std::process::exit(1)
+            }
+            gs.stack.split_off(below.min(gs.stack.len()))
+        } else {
+            vec![input.clone()]
+        };
+        let prelude = match cli.prelude {
+            Some(Prelude::Std) => STD_PRELUDE,
+            None => b"",
+        };
+        Session {
+            cli,
+            mode,
+            gs,
+            replay,
+            flags,
+            state_flags,
+            input,
+            start,
+            prelude,
+            capture,
+            reference,
+            #[cfg(feature = "jupyter")]
+            kernel_output,
         }
     }
 
-    fn zip(&mut self) {
-        let a = self.pop().unwrap_arr();
-        let mut r = vec![];
-        let blank = a.first().map_or(Gval::Arr(vec![]), |x| x.factory());
-        for row in a {
-            for (y, elem) in row.into_arr().into_iter().enumerate() {
-                while r.len() < y + 1 {
-                    r.push(blank.clone())
-                }
-                r[y].push(elem.clone());
-            }
+    /// Get ready to run code as it comes: put the start on the stack, then
+    /// run the prelude and `--prologue`, exiting if they fail.
+    fn begin(&mut self) {
+        self.gs.stack.extend(std::mem::take(&mut self.start));
+        if let Err(e) = self.gs.run(self.prelude) {
+            eprintln!("error in the prelude: {}", e);
+            std::process::exit(1)
         }
-        self.push(Gval::Arr(r))
+        run_snippet(&mut self.gs, &self.cli, "--prologue", &self.cli.prologue);
     }
 
-    fn base(&mut self) {
-        let b = self.pop().unwrap_int();
-        match self.pop() {
-            Gval::Int(n) => {
-                let mut digits = vec![];
-                let mut i = n.abs();
-                while !i.is_zero() {
-                    let (j, k) = i.div_mod_floor(&b);
-                    i = j;
-                    digits.push(Gval::Int(k));
-                }
-                digits.reverse();
-                self.push(Gval::Arr(digits))
+    /// Run the program, after the stages of a pipeline before it, and
+    /// report on the run, exiting if it failed or didn't check out.
+    fn run_to_end(self) {
+        let Session {
+            cli,
+            mode,
+            mut gs,
+            replay,
+            flags,
+            state_flags,
+            input,
+            mut start,
+            prelude,
+            capture,
+            reference,
+            ..
+        } = self;
+        let per_line = cli.each_line || cli.print_lines;
+        let sources = sources(&cli);
+        if let [stages @ .., _] = sources.as_slice() {
+            let alone = [
+                per_line,
+                cli.record.is_some(),
+                cli.verify,
+                cli.check_expectations,
+                cli.load_state.is_some() || cli.save_state.is_some(),
+            ];
+            if !stages.is_empty() && (mode != Mode::Run || alone.contains(&true)) {
+                eprintln!(
+                    "a pipeline of programs can't be debugged, recorded, verified, checked, \
+                     run line by line or have its state loaded or saved"
+                );
+                std::process::exit(1)
             }
-            n => {
-                let mut total = BigInt::zero();
-                for digit in n.into_arr() {
-                    total = total * b.clone() + digit.unwrap_int();
+            for stage in stages {
+                let code = stage.read(cli.allow_net);
+                match pipe(&mut gs, prelude, &code, start, &cli) {
+                    Ok(output) => start = vec![Gval::Str(output)],
+                    Err(e) => {
+                        report_error(&cli, stage.path(), &code, &gs, &e);
+                        std::process::exit(1)
+                    }
                 }
-                self.push(Gval::Int(total))
             }
         }
+        let code = match (&replay, sources.last()) {
+            (Some(record), _) => record.code.clone(),
+            (None, Some(source)) => source.read(cli.allow_net),
+            (None, None) => match program(&cli) {
+                Some(code) => code,
+                None => std::process::exit(1),
+            },
+        };
+        gs.stack.extend(start);
+        let mut journal = None;
+        let profiler = Rc::new(RefCell::new(Profiler::default()));
+        let stats = Rc::new(RefCell::new(Stats::default()));
+        let timeline = Rc::new(RefCell::new(Timeline::default()));
+        let mut timeline_json = None;
+        let result = gs.run(prelude).and_then(|()| {
+            let mut hooks: Vec<Box<dyn Hook>> = vec![];
+            if mode == Mode::Debug {
+                let commands = Box::new(BufReader::new(std::io::stdin()));
+                let debugger =
+                    Debugger::new(&code, commands).highlight(std::io::stderr().is_terminal());
+                hooks.push(Box::new(debugger));
+            } else if cli.coverage || cli.heatmap.is_some() || cli.flamegraph.is_some() {
+                hooks.push(Box::new(profiler.clone()));
+            }
+            if cli.stats {
+                hooks.push(Box::new(stats.clone()));
+            }
+            if cli.timeline.is_some() {
+                hooks.push(Box::new(timeline.clone()));
+            }
+            if cli.record.is_some() || replay.is_some() {
+                gs.start_journal();
+            }
+            if cli.audit.is_some() {
+                gs.start_audit();
+            }
+            if !per_line {
+                run_snippet(&mut gs, &cli, "--prologue", &cli.prologue);
+            }
+            if !hooks.is_empty() {
+                gs.set_hook(Some(Box::new(hooks)));
+            }
+            let result = if per_line {
+                let lines: Box<dyn BufRead> = if let Some(path) = &cli.input_path {
+                    Box::new(Cursor::new(read_path(path, cli.allow_net)))
+                } else if let Some(string) = &cli.input {
+                    Box::new(Cursor::new(string.clone().into_bytes()))
+                } else {
+                    Box::new(std::io::stdin().lock())
+                };
+                each_line(&mut gs, &code, lines, &cli)
+            } else {
+                gs.run(&code)
+            };
+            gs.set_hook(None);
+            if result.is_ok() && !per_line {
+                run_snippet(&mut gs, &cli, "--epilogue", &cli.epilogue);
+            }
+            if result.is_ok() {
+                save_state(&cli, &gs, &state_flags);
+            }
+            if cli.timeline.is_some() {
+                timeline_json = Some(timeline.borrow().to_json(&code, &gs, result.as_ref().err()));
+            }
+            profiler.borrow_mut().finish();
+            stats.borrow_mut().finish(&gs);
+            journal = gs.take_journal();
+            result
+        });
+        let result = result.and_then(|()| {
+            if cli.no_implicit_output || per_line {
+                return Ok(());
+            }
+            print_stack(&mut gs, &cli)
+        });
+        let result = result.and_then(|()| gs.flush());
+        if let (Some(path), Some(audit)) = (&cli.audit, gs.take_audit()) {
+            let mut trail = audit.to_json_lines(&code);
+            if !trail.is_empty() {
+                trail.push('\n');
+            }
+            if let Err(e) = std::fs::write(path, trail) {
+                eprintln!("--audit {}: {}", path, e);
+            }
+        }
+        if cli.stats {
+            eprintln!("{}", stats.borrow().report());
+        }
+        let expectations_met = !cli.check_expectations || meets_expectations(&code, &capture);
+        let mut verified = true;
+        if let Some(reference) = &reference {
+            verified = verify(reference, &cli, &code, &input, &capture, result.is_err());
+        }
+        if cli.coverage {
+            eprintln!("{}", profiler.borrow().coverage(&code));
+        }
+        if let Some(format) = cli.heatmap {
+            eprintln!("{}", profiler.borrow().heatmap(&code, format));
+        }
+        if let (Some(path), Some(json)) = (&cli.timeline, &timeline_json) {
+            if let Err(e) = std::fs::write(path, json.to_string() + "\n") {
+                eprintln!("--timeline {}: {}", path, e);
+            }
+        }
+        if let Some(path) = &cli.flamegraph {
+            if let Err(e) = std::fs::write(path, profiler.borrow().flamegraph(&code) + "\n") {
+                eprintln!("--flamegraph {}: {}", path, e);
+            }
+        }
+        if let (Some(path), Some(journal)) = (&cli.record, journal.take()) {
+            let record = Record {
+                code: code.clone(),
+                input,
+                flags,
+                journal,
+            };
+            if let Err(e) = record.save(path) {
+                eprintln!("--record {}: {}", path, e);
+            }
+        }
+        if let (Some(record), Some(journal)) = (&replay, &journal) {
+            warn_divergence(record, journal);
+        }
+        if let Err(e) = result {
+            let _ = gs.flush();
+            report_error(&cli, code_file(&cli), &code, &gs, &e);
+            std::process::exit(1)
+        }
+        if !expectations_met || !verified {
+            std::process::exit(1)
+        }
     }
+}
 
-    fn fold<T: Into<Gval>>(&mut self, code: Vec<u8>, vs: Vec<T>) {
-        for (i, v) in vs.into_iter().enumerate() {
-            self.push(v.into());
-            if i >= 1 {
-                self.run(&code);
+/// An interpreter builder set up as the flags ask, but for where the
+/// program's output goes.
+fn builder(cli: &RunArgs, mode: &Mode, replay: Option<&Record>) -> GsBuilder {
+    let mut permissions = Permissions {
+        env: cli.allow_env,
+        exec: cli.allow_exec,
+        net: cli.allow_net,
+        ..Permissions::default()
+    };
+    if let Some(dir) = &cli.allow_fs {
+        match std::fs::canonicalize(dir) {
+            Ok(root) => permissions.fs = Some(root),
+            Err(e) => {
+                eprintln!("--allow-fs {}: {}", dir, e);
+                std::process::exit(1)
             }
         }
     }
-
-    fn each<T: Into<Gval>>(&mut self, code: Vec<u8>, vs: Vec<T>) {
-        for v in vs {
-            self.push(v.into());
-            self.run(&code);
+    let seed = cli.seed.unwrap_or_else(entropy_seed);
+    if cli.log_draws {
+        // Enough to get the same draws again.
+        match (replay, &cli.rng) {
+            (Some(record), _) => eprintln!("rng: {} recorded draws", record.journal.draws.len()),
+            (None, rng) => eprintln!(
+                "rng: --rng {} --seed {}",
+                rng.to_possible_value().map_or("", |v| v.get_name()),
+                seed
+            ),
         }
     }
-
-    fn gs_map<T: Into<Gval>>(&mut self, code: Vec<u8>, vs: Vec<T>) -> Vec<Gval> {
-        let mut r: Vec<Gval> = vec![];
-        for v in vs {
-            let lb = self.stack.len();
-            self.push(v.into());
-            self.run(&code);
-            r.extend(self.stack.drain(lb..));
+    let rng = match (replay, &cli.rng) {
+        (Some(record), _) => Rng::Replay(record.journal.draws.iter().cloned().collect()),
+        (None, RngKind::Lcg) => Rng::Lcg(seed),
+        (None, RngKind::Mt) => Rng::Mt(Box::new(Mt19937::new(&seed.into()))),
+    };
+    let mut builder = Gs::builder()
+        .extensions(cli.extensions)
+        .compat(cli.compat)
+        .explain_coercions(cli.explain_coercions)
+        .log_draws(cli.log_draws)
+        .unicode(cli.unicode)
+        .lenient(cli.lenient)
+        .pretty(cli.pretty)
+        .color(std::io::stdout().is_terminal())
+        .warnings(!cli.no_warnings)
+        .diagnostics(cli.diagnostics)
+        .max_depth(cli.max_depth)
+        .limits(Limits {
+            array_len: cli.max_array_len,
+            string_len: cli.max_string_len,
+            int_bits: cli.max_int_bits,
+        })
+        .permissions(permissions)
+        .rng(rng);
+    if let Some(path) = code_file(cli) {
+        builder = builder.file(path);
+    }
+    if let Some(record) = replay {
+        let reads = record.journal.reads.concat();
+        builder = builder.input(Box::new(Cursor::new(reads)));
+    } else if *mode == Mode::Repl || *mode == Mode::Watch || cli.each_line || cli.print_lines {
+        builder = builder.input(Box::new(std::io::empty()));
+    }
+    if let Some(max) = cli.max_output {
+        builder = builder.max_output(max);
+    }
+    if let Some(max) = cli.max_steps {
+        builder = builder.max_steps(max);
+    }
+    if let Some(seconds) = cli.timeout {
+        match Duration::try_from_secs_f64(seconds) {
+            Ok(timeout) => builder = builder.timeout(timeout),
+            Err(e) => {
+                eprintln!("--timeout {}: {}", seconds, e);
+                std::process::exit(1)
+            }
         }
-        r
     }
+    builder
+}
 
-    fn select<T: Clone + Into<Gval>>(&mut self, code: Vec<u8>, vs: Vec<T>) -> Vec<T> {
-        let mut r: Vec<T> = vec![];
-        for v in vs {
-            self.push(v.clone().into());
-            self.run(&code);
-            if self.pop().truthy() {
-                r.push(v)
+/// The reference interpreter to `--verify` with, if asked to.
+fn reference_for(cli: &RunArgs) -> Option<Reference> {
+    match (cli.verify, &cli.reference) {
+        (false, _) => None,
+        (true, Some(path)) => Some(Reference::new(path)),
+        (true, None) => match Reference::from_env() {
+            Some(reference) => Some(reference),
+            None => {
+                eprintln!("--verify needs golfscript.rb: pass --reference or set GOLFSCRIPT_RB");
+                std::process::exit(1)
             }
-        }
-        r
+        },
     }
+}
 
-    fn find<T: Clone + Into<Gval>>(&mut self, code: Vec<u8>, vs: Vec<T>) {
-        for v in vs {
-            self.push(v.clone().into());
-            self.run(&code);
-            if self.pop().truthy() {
-                self.push(v.into());
-                break;
-            }
-        }
-    }
-
-    fn go(&mut self, val: Gval) {
-        match val {
-            Gval::Blk(s) => self.run(&s),
-            _ => self.push(val),
-        }
-    }
-
-    fn run_token(&mut self, token: Gtoken) {
-        if let Some(v) = self.vars.get(token.lexeme()).cloned() {
-            self.go(v);
-            return;
-        }
-        match token {
-            Gtoken::IntLiteral(bs) => {
-                let n = BigInt::parse_bytes(bs, 10).unwrap();
-                self.push(Gval::Int(n));
-            }
-            Gtoken::SingleQuotedString(bs) => self.push(Gval::Str(unescape(bs, true))),
-            Gtoken::DoubleQuotedString(bs) => self.push(Gval::Str(unescape(bs, false))),
-            Gtoken::Symbol(b"~") => self.tilde(),
-            Gtoken::Symbol(b"`") => self.backtick(),
-            Gtoken::Symbol(b"!") => self.bang(),
-            Gtoken::Symbol(b"@") => self.at_sign(),
-            Gtoken::Symbol(b"$") => self.dollar(),
-            Gtoken::Symbol(b"+") => self.plus(),
-            Gtoken::Symbol(b"-") => self.minus(),
-            Gtoken::Symbol(b"*") => self.asterisk(),
-            Gtoken::Symbol(b"/") => self.slash(),
-            Gtoken::Symbol(b"%") => self.percent(),
-            Gtoken::Symbol(b"|") => self.vertical_bar(),
-            Gtoken::Symbol(b"&") => self.ampersand(),
-            Gtoken::Symbol(b"^") => self.caret(),
-            Gtoken::Symbol(b"[") => self.lb.push(self.stack.len()),
-            Gtoken::Symbol(b"]") => {
-                let vs = self.stack.drain(self.lb.pop().unwrap_or(0)..).collect();
-                self.push(Gval::Arr(vs));
-            }
-            Gtoken::Symbol(b"\\") => {
-                let b = self.pop();
-                let a = self.pop();
-                self.push(b);
-                self.push(a);
-            }
-            Gtoken::Symbol(b";") => {
-                let _ = self.pop();
-            }
-            Gtoken::Symbol(b"<") => self.lteqgt(Ordering::Less),
-            Gtoken::Symbol(b"=") => self.lteqgt(Ordering::Equal),
-            Gtoken::Symbol(b">") => self.lteqgt(Ordering::Greater),
-            Gtoken::Symbol(b",") => self.comma(),
-            Gtoken::Symbol(b".") => self.dup(),
-            Gtoken::Symbol(b"?") => self.question(),
-            Gtoken::Symbol(b"(") => self.left_paren(),
-            Gtoken::Symbol(b")") => self.right_paren(),
-            Gtoken::Symbol(b"and") => {
-                let b = self.pop();
-                let a = self.pop();
-                self.go(if a.truthy() { b } else { a });
-            }
-            Gtoken::Symbol(b"or") => {
-                let b = self.pop();
-                let a = self.pop();
-                self.go(if a.falsey() { b } else { a });
-            }
-            Gtoken::Symbol(b"xor") => {
-                let b = self.pop();
-                let a = self.pop();
-                self.push(Gval::bool(a.truthy() ^ b.truthy()));
-            }
-            Gtoken::Symbol(b"n") => self.push(Gval::Str(b"\n".to_vec())),
-            Gtoken::Symbol(b"print") => {
-                let a = self.pop();
-                print(&a.into_gs());
-            }
-            Gtoken::Symbol(b"p") => {
-                let a = self.pop();
-                print(&a.inspect());
-                print(b"\n");
-            }
-            Gtoken::Symbol(b"puts") => {
-                let a = self.pop();
-                print(&a.into_gs());
-                print(b"\n");
-            }
-            Gtoken::Symbol(b"rand") => self.rand(),
-            Gtoken::Symbol(b"do") => self.do_loop(),
-            Gtoken::Symbol(b"while") => self.while_loop(true),
-            Gtoken::Symbol(b"until") => self.while_loop(false),
-            Gtoken::Symbol(b"if") => {
-                let c = self.pop();
-                let b = self.pop();
-                let a = self.pop();
-                if a.truthy() {
-                    self.go(b);
-                } else {
-                    self.go(c);
-                }
+/// Copy what `gs` prints to the `--tee` files too.
+fn tee(gs: &mut Gs, cli: &RunArgs) {
+    for path in &cli.tee {
+        match std::fs::File::create(path) {
+            Ok(file) => {
+                let out = gs.replace_output(Box::new(std::io::sink()));
+                gs.replace_output(Box::new(Tee(out, BufWriter::new(file))));
             }
-            Gtoken::Symbol(b"abs") => {
-                let a = self.pop();
-                self.push(Gval::Int(a.unwrap_int().abs()));
+            Err(e) => {
+                eprintln!("--tee {}: {}", path, e);
+                std::process::exit(1)
             }
-            Gtoken::Symbol(b"zip") => self.zip(),
-            Gtoken::Symbol(b"base") => self.base(),
-            Gtoken::Block(_, src) => self.push(Gval::Blk(src.to_owned())),
-            Gtoken::Symbol(_) => {}
-            Gtoken::Comment(_) => {}
         }
     }
 }
 
-#[derive(clap::Parser, Debug)]
-struct Cli {
-    #[clap(long)]
-    code_path: Option<String>,
-    #[clap(short = 'e', long, allow_hyphen_values = true)]
-    code: Option<String>,
-    #[clap(long)]
-    input_path: Option<String>,
-    #[clap(short = 'i', long, allow_hyphen_values = true)]
-    input: Option<String>,
-    #[clap(short = 'q', long, takes_value = false)]
-    no_implicit_output: bool,
-    #[clap(short = 's', long, takes_value = false)]
-    input_from_stdin: bool,
-    #[clap(long, takes_value = false)]
-    args: bool,
-    args_vec: Vec<String>,
+/// Restore the `--load-state` file's stack and variables, if there is one,
+/// warning if it was saved with other `flags`.
+fn load_state(gs: &mut Gs, cli: &RunArgs, flags: &[String]) {
+    let Some(path) = &cli.load_state else {
+        return;
+    };
+    match State::load(path) {
+        Ok(state) => {
+            if state.flags != flags {
+                eprintln!(
+                    "warning: --load-state {}: saved with flags [{}], not [{}]",
+                    path,
+                    state.flags.join(" "),
+                    flags.join(" ")
+                );
+            }
+            state.restore(gs);
+        }
+        Err(e) => {
+            eprintln!("--load-state {}: {}", path, e);
+            std::process::exit(1)
+        }
+    }
 }
 
-fn main() {
-    let cli = Cli::parse();
-    let mut gs = Gs::new();
-    let input = if cli.args {
+/// The program's input, as the flags or the replayed record give it.
+fn input(cli: &RunArgs, replay: Option<&Record>) -> Gval {
+    if let Some(record) = replay {
+        record.input.clone()
+    } else if cli.each_line || cli.print_lines {
+        // The lines are read as the program runs.
+        Gval::Str(vec![])
+    } else if cli.args {
         Gval::Arr(
             cli.args_vec
                 .iter()
@@ -766,34 +1503,231 @@ fn main() {
         let mut bytes = vec![];
         std::io::stdin().read_to_end(&mut bytes).unwrap();
         Gval::Str(bytes)
-    } else if let Some(path) = cli.input_path {
-        Gval::Str(std::fs::read(path).unwrap())
-    } else if let Some(string) = cli.input {
+    } else if let Some(path) = &cli.input_path {
+        Gval::Str(read_path(path, cli.allow_net))
+    } else if let Some(string) = &cli.input {
         Gval::Str(string.as_bytes().to_vec())
     } else {
         Gval::Str(vec![])
-    };
-    let code = if let Some(path) = cli.code_path {
-        std::fs::read(path).unwrap()
-    } else if let Some(code) = cli.code {
-        code.as_bytes().to_vec()
-    } else {
-        eprintln!(
-            r"No code provided. Try:
+    }
+}
 
-    golfscript-rs --help
-    golfscript-rs --code '~{{.@\%.}}do;'   --input '140 150'
-    golfscript-rs --code 'n*~{{.@\%.}}do;' --args 140 150   # code.golf style
-    golfscript-rs --code-path file.gs    --input-file input.txt
-    golfscript-rs --code-path file.gs    --input-from-stdin
-"
+/// Report whether what the program printed matches its `#=>` lines.
+fn meets_expectations(code: &[u8], capture: &Capture) -> bool {
+    let expected = expectations(code);
+    if expected.is_empty() {
+        eprintln!("no `#=>` expectations in the program");
+        return false;
+    }
+    if let Err(diff) = check(&expected, &capture.0.borrow()) {
+        eprintln!("output doesn't match the `#=>` expectations:\n{}", diff);
+        return false;
+    }
+    let plural = if expected.len() == 1 { "" } else { "s" };
+    eprintln!("output matches the {} `#=>` line{}", expected.len(), plural);
+    true
+}
+
+/// Warn if a replay didn't run the tokens the recorded run did.
+fn warn_divergence(record: &Record, journal: &Journal) {
+    if let Some(i) = record.journal.divergence(journal) {
+        let show = |t: Option<&Option<usize>>| match t {
+            Some(Some(at)) => format!("offset {}", at),
+            Some(None) => "code outside the program".to_string(),
+            None => "the end".to_string(),
+        };
+        eprintln!(
+            "warning: replay diverged at token {}: recorded {}, replayed {}",
+            i,
+            show(record.journal.tokens.get(i)),
+            show(journal.tokens.get(i))
         );
+    }
+}
+
+/// Run the `--code-path` program each time the file changes, until
+/// interrupted, printing what it prints and its stack as a run would. Each
+/// run after the first resumes from where the program was edited; see
+/// [`Incremental`].
+fn watch(gs: Gs, cli: &RunArgs) {
+    let (None, [path]) = (cli.pipeline.last(), cli.code_path.as_slice()) else {
+        eprintln!("watch needs one program in a file, with --code-path");
+        std::process::exit(1)
+    };
+    let mut runs = Incremental::new(gs);
+    let mut last: Option<Vec<u8>> = None;
+    loop {
+        let code = match std::fs::read(path) {
+            Ok(code) => code,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(1)
+            }
+        };
+        if last.as_ref() != Some(&code) {
+            let started = Instant::now();
+            let rerun = runs.run(&code);
+            let gs = runs.gs();
+            let result = rerun.result.and_then(|()| {
+                if let Some(epilogue) = &cli.epilogue {
+                    gs.run(epilogue.as_bytes())?;
+                }
+                if cli.no_implicit_output {
+                    return Ok(());
+                }
+                print_stack(gs, cli)
+            });
+            if rerun.resumed_at.is_some() {
+                let mut stdout = std::io::stdout();
+                let _ = stdout
+                    .write_all(&runs.output())
+                    .and_then(|()| stdout.flush());
+            }
+            if let Err(e) = &result {
+                report_error(cli, Some(path), &code, runs.gs(), e);
+            }
+            let ran = match rerun.resumed_at {
+                None => String::new(),
+                Some(0) => format!("ran in {:.3}s; ", started.elapsed().as_secs_f64()),
+                Some(at) => format!(
+                    "ran in {:.3}s, resuming at byte {} of {}; ",
+                    started.elapsed().as_secs_f64(),
+                    at,
+                    code.len()
+                ),
+            };
+            eprintln!("-- {}watching {} for changes --", ran, path);
+            last = Some(code);
+        }
+        std::thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+/// Write the stack and variables to the `--save-state` file, if there is
+/// one.
+fn save_state(cli: &RunArgs, gs: &Gs, flags: &[String]) {
+    if let Some(path) = &cli.save_state {
+        if let Err(e) = State::of(gs, flags.to_vec()).save(path) {
+            eprintln!("--save-state {}: {}", path, e);
+        }
+    }
+}
+
+/// Run the `--prologue` or `--epilogue` code, if given, exiting with its
+/// error if it fails.
+fn run_snippet(gs: &mut Gs, cli: &RunArgs, flag: &str, code: &Option<String>) {
+    let Some(code) = code else {
+        return;
+    };
+    if let Err(e) = gs.run(code.as_bytes()) {
+        let _ = gs.flush();
+        eprintln!("{} failed", flag);
+        report_error(cli, None, code.as_bytes(), gs, &e);
         std::process::exit(1)
+    }
+}
+
+/// Print the stack as a program's implicit output: flattened, like `puts`
+/// of an array of it, but with `--output-separator` or `--output-lines`
+/// between the values, and no final newline with `--no-trailing-newline`.
+/// Whether `v` prints as nothing, like `""` or `[[] ""]`.
+fn prints_nothing(v: &Gval) -> bool {
+    match v {
+        Gval::Str(s) => s.is_empty(),
+        Gval::Arr(vs) => vs.iter().all(prints_nothing),
+        Gval::Int(_) | Gval::Blk(_) => false,
+    }
+}
+
+fn print_stack(gs: &mut Gs, cli: &RunArgs) -> GsResult<()> {
+    let separator = match (&cli.output_separator, cli.output_lines) {
+        (Some(separator), _) => Some(separator.as_bytes()),
+        (None, true) => Some(&b"\n"[..]),
+        (None, false) => None,
     };
-    gs.stack.push(input);
-    gs.run(&code);
-    if !cli.no_implicit_output {
-        gs.stack = vec![Gval::Arr(gs.stack)];
-        gs.run(b"puts");
+    let stack = std::mem::take(&mut gs.stack);
+    let values = match separator {
+        Some(separator) => {
+            // Separate only what prints something, so the empty input left at
+            // the bottom of the stack doesn't start the output with one.
+            let mut values: Vec<Gval> = vec![];
+            for v in stack {
+                if prints_nothing(&v) {
+                    continue;
+                }
+                if !values.is_empty() {
+                    values.push(Gval::Str(separator.to_vec()));
+                }
+                values.push(v);
+            }
+            values
+        }
+        None => stack,
+    };
+    gs.stack = vec![Gval::Arr(values)];
+    if cli.no_trailing_newline {
+        gs.run(b"print")
+    } else {
+        gs.run(b"puts")
+    }
+}
+
+/// Run `code` on the stack `input` as a stage of a pipeline, returning
+/// what it printed for the next stage. The variables it set are forgotten
+/// after.
+fn pipe(
+    gs: &mut Gs,
+    prelude: &[u8],
+    code: &[u8],
+    input: Vec<Gval>,
+    cli: &RunArgs,
+) -> GsResult<Vec<u8>> {
+    let capture = Capture::default();
+    let out = gs.replace_output(Box::new(capture.clone()));
+    gs.stack = input;
+    let result = gs.run(prelude).and_then(|()| {
+        run_snippet(gs, cli, "--prologue", &cli.prologue);
+        gs.run(code)?;
+        run_snippet(gs, cli, "--epilogue", &cli.epilogue);
+        Ok(())
+    });
+    let result = result.and_then(|()| {
+        if cli.no_implicit_output {
+            return Ok(());
+        }
+        print_stack(gs, cli)
+    });
+    let result = result.and_then(|()| gs.flush());
+    gs.replace_output(out);
+    gs.reset_vars();
+    gs.stack.clear();
+    result.map(|()| capture.0.take())
+}
+
+/// Report an error `code` from `file` stopped with, as `--diagnostics` and
+/// `--post-mortem` ask.
+fn report_error(cli: &RunArgs, file: Option<&str>, code: &[u8], gs: &Gs, e: &GsError) {
+    match cli.diagnostics {
+        DiagnosticFormat::Human => {
+            eprintln!("error: {}", e);
+            if !gs.trace().is_empty() {
+                eprintln!("{}", render(code, gs.trace()));
+            }
+            if let (true, Some(state)) = (cli.post_mortem, gs.post_mortem()) {
+                eprintln!("{}", state.report());
+            }
+        }
+        DiagnosticFormat::Json => {
+            // A parse error in code run by `~` is located at the `~`.
+            let at = match e {
+                GsError::Parse(p) if gs.trace().is_empty() => Some(p.offset),
+                _ => locate(code, gs.trace()),
+            };
+            let diagnostic = Diagnostic::error(e).file(file).at(code, at);
+            eprintln!("{}", diagnostic.to_json());
+            if let (true, Some(state)) = (cli.post_mortem, gs.post_mortem()) {
+                eprintln!("{}", serde_json::json!({ "post_mortem": state.to_json() }));
+            }
+        }
     }
 }
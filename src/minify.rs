@@ -0,0 +1,47 @@
+//! Squeezing a program down to its tokens: comments go, and so does every
+//! space or newline that isn't needed to keep two tokens apart. The result
+//! is checked by tokenizing it. As with formatting, the text of blocks
+//! changes, which a program that prints a block can tell.
+
+use crate::parse::{assigns_whitespace, shebang_len, significant_tokens};
+
+/// What has to go between `a` and `b` for them to stay two tokens: nothing,
+/// a space, or a newline to end an assignment to a comment like `:#x`.
+fn separator(a: &[u8], b: &[u8]) -> Option<&'static [u8]> {
+    // Nothing runs into a brace, and a brace doesn't parse on its own.
+    if [a, b].iter().any(|t| *t == b"{" || *t == b"}") {
+        return Some(b"");
+    }
+    let pair = Some(vec![a.to_vec(), b.to_vec()]);
+    [&b""[..], b" ", b"\n"]
+        .into_iter()
+        .find(|sep| significant_tokens(&[a, sep, b].concat(), false) == pair)
+}
+
+/// `program` without comments and needless whitespace, or why it can't be
+/// minified. A program that assigns to whitespace keeps its spacing.
+pub fn minify(program: &[u8]) -> Result<Vec<u8>, String> {
+    // A `#!` line stays, so a script stays executable.
+    let (shebang, program) = program.split_at(shebang_len(program));
+    let Some(tokens) = significant_tokens(program, false) else {
+        return Err("the program doesn't parse".to_string());
+    };
+    if assigns_whitespace(program) {
+        return Err("the program assigns to whitespace, so its spacing matters".to_string());
+    }
+    let mut out: Vec<u8> = shebang.to_vec();
+    let start = out.len();
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 {
+            match separator(&tokens[i - 1], token) {
+                Some(sep) => out.extend_from_slice(sep),
+                None => return Err("tokens can't be kept apart; this is a bug".to_string()),
+            }
+        }
+        out.extend_from_slice(token);
+    }
+    if significant_tokens(&out[start..], false) != Some(tokens) {
+        return Err("minifying would change the tokens; this is a bug".to_string());
+    }
+    Ok(out)
+}
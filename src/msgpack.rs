@@ -0,0 +1,161 @@
+//! Values and stacks as MessagePack, for programs that embed the interpreter
+//! and talk to services in a compact binary format. Numbers that fit in 64
+//! bits are MessagePack integers and bigger ones an extension type holding
+//! their two's complement bytes; strings are binary, since they're bytes;
+//! arrays are arrays; and blocks are another extension type holding their
+//! code. A stack is an array, bottom first. Decoding also takes UTF-8
+//! strings, as other encoders write text.
+
+use crate::record::invalid;
+use crate::value::Gval;
+use crate::DEFAULT_MAX_DEPTH;
+use num::{BigInt, ToPrimitive};
+use rmp::encode;
+use rmp::Marker;
+use std::io::{self, Write};
+
+/// The extension types of numbers too big for 64 bits, and of blocks.
+const BIG_INT: i8 = 1;
+const BLOCK: i8 = 2;
+
+/// `v` as MessagePack.
+pub fn encode(v: &Gval) -> io::Result<Vec<u8>> {
+    let mut out = vec![];
+    write(&mut out, v)?;
+    Ok(out)
+}
+
+/// A stack as MessagePack, bottom first.
+pub fn encode_stack(stack: &[Gval]) -> io::Result<Vec<u8>> {
+    let mut out = vec![];
+    write_array(&mut out, stack)?;
+    Ok(out)
+}
+
+/// The value `bytes` holds, which must be all of them.
+pub fn decode(mut bytes: &[u8]) -> io::Result<Gval> {
+    let v = read(&mut bytes, 0)?;
+    if !bytes.is_empty() {
+        return Err(invalid("trailing bytes after the value"));
+    }
+    Ok(v)
+}
+
+/// The stack `bytes` holds, as [`encode_stack`] writes it.
+pub fn decode_stack(bytes: &[u8]) -> io::Result<Vec<Gval>> {
+    match decode(bytes)? {
+        Gval::Arr(vs) => Ok(vs),
+        _ => Err(invalid("a stack should be an array")),
+    }
+}
+
+/// A length as MessagePack stores it, which is at most 32 bits.
+fn len32(len: usize) -> io::Result<u32> {
+    u32::try_from(len).map_err(|_| invalid("too long for MessagePack"))
+}
+
+fn write(out: &mut impl Write, v: &Gval) -> io::Result<()> {
+    match v {
+        Gval::Int(n) => {
+            if let Some(n) = n.to_i64() {
+                encode::write_sint(out, n)?;
+            } else if let Some(n) = n.to_u64() {
+                encode::write_uint(out, n)?;
+            } else {
+                let bytes = n.to_signed_bytes_be();
+                encode::write_ext_meta(out, len32(bytes.len())?, BIG_INT)?;
+                out.write_all(&bytes)?;
+            }
+        }
+        Gval::Arr(vs) => write_array(out, vs)?,
+        Gval::Str(bs) => {
+            encode::write_bin_len(out, len32(bs.len())?)?;
+            out.write_all(bs)?;
+        }
+        Gval::Blk(bs) => {
+            encode::write_ext_meta(out, len32(bs.len())?, BLOCK)?;
+            out.write_all(bs)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_array(out: &mut impl Write, vs: &[Gval]) -> io::Result<()> {
+    encode::write_array_len(out, len32(vs.len())?)?;
+    for v in vs {
+        write(out, v)?;
+    }
+    Ok(())
+}
+
+/// Take the next `n` bytes of `input`.
+fn take<'a>(input: &mut &'a [u8], n: usize) -> io::Result<&'a [u8]> {
+    if input.len() < n {
+        return Err(invalid("unexpected end of MessagePack data"));
+    }
+    let (taken, rest) = input.split_at(n);
+    *input = rest;
+    Ok(taken)
+}
+
+/// Take a big-endian unsigned integer of `n` bytes from `input`.
+fn uint(input: &mut &[u8], n: usize) -> io::Result<u64> {
+    Ok(take(input, n)?
+        .iter()
+        .fold(0, |acc, &b| acc << 8 | u64::from(b)))
+}
+
+/// Take a value from `input`, nested in `depth` arrays.
+fn read(input: &mut &[u8], depth: usize) -> io::Result<Gval> {
+    let int = |n: i64| Ok(Gval::Int(BigInt::from(n)));
+    let marker = Marker::from_u8(take(input, 1)?[0]);
+    let (len, ext) = match marker {
+        Marker::FixPos(n) => return int(n.into()),
+        Marker::FixNeg(n) => return int(n.into()),
+        Marker::U8 => return int(uint(input, 1)? as i64),
+        Marker::U16 => return int(uint(input, 2)? as i64),
+        Marker::U32 => return int(uint(input, 4)? as i64),
+        Marker::U64 => return Ok(Gval::Int(BigInt::from(uint(input, 8)?))),
+        Marker::I8 => return int(uint(input, 1)? as u8 as i8 as i64),
+        Marker::I16 => return int(uint(input, 2)? as u16 as i16 as i64),
+        Marker::I32 => return int(uint(input, 4)? as u32 as i32 as i64),
+        Marker::I64 => return int(uint(input, 8)? as i64),
+        Marker::FixArray(n) => return read_array(n.into(), input, depth),
+        Marker::Array16 => return read_array(uint(input, 2)?, input, depth),
+        Marker::Array32 => return read_array(uint(input, 4)?, input, depth),
+        Marker::FixStr(n) => (n.into(), None),
+        Marker::Bin8 | Marker::Str8 => (uint(input, 1)?, None),
+        Marker::Bin16 | Marker::Str16 => (uint(input, 2)?, None),
+        Marker::Bin32 | Marker::Str32 => (uint(input, 4)?, None),
+        Marker::FixExt1 => (1, Some(take(input, 1)?[0] as i8)),
+        Marker::FixExt2 => (2, Some(take(input, 1)?[0] as i8)),
+        Marker::FixExt4 => (4, Some(take(input, 1)?[0] as i8)),
+        Marker::FixExt8 => (8, Some(take(input, 1)?[0] as i8)),
+        Marker::FixExt16 => (16, Some(take(input, 1)?[0] as i8)),
+        Marker::Ext8 => (uint(input, 1)?, Some(take(input, 1)?[0] as i8)),
+        Marker::Ext16 => (uint(input, 2)?, Some(take(input, 1)?[0] as i8)),
+        Marker::Ext32 => (uint(input, 4)?, Some(take(input, 1)?[0] as i8)),
+        _ => return Err(invalid("nil, booleans, floats and maps aren't values")),
+    };
+    let bytes = take(input, len as usize)?.to_vec();
+    match ext {
+        None => Ok(Gval::Str(bytes)),
+        Some(BIG_INT) => Ok(Gval::Int(BigInt::from_signed_bytes_be(&bytes))),
+        Some(BLOCK) => Ok(Gval::Blk(bytes)),
+        Some(_) => Err(invalid("unknown MessagePack extension type")),
+    }
+}
+
+/// Take an array of `len` values from `input`, nested in `depth` arrays.
+fn read_array(len: u64, input: &mut &[u8], depth: usize) -> io::Result<Gval> {
+    if depth >= DEFAULT_MAX_DEPTH {
+        return Err(invalid("arrays nested too deeply"));
+    }
+    // Each element takes a byte at least, so a bad length can't make this
+    // allocate much more than the input.
+    let mut vs = Vec::with_capacity((len as usize).min(input.len()));
+    for _ in 0..len {
+        vs.push(read(input, depth + 1)?);
+    }
+    Ok(Gval::Arr(vs))
+}
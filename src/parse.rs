@@ -1,3 +1,4 @@
+use crate::error::{GsError, GsResult, ParseError};
 use nom::branch::alt;
 use nom::bytes::complete::take;
 use nom::bytes::complete::{take_while, take_while1, take_while_m_n};
@@ -6,6 +7,7 @@ use nom::combinator::{consumed, recognize};
 use nom::multi::many0;
 use nom::sequence::{delimited, pair};
 use nom::IResult;
+use std::io::BufRead;
 
 #[derive(Clone, Debug)]
 pub enum Gtoken<'a> {
@@ -14,6 +16,7 @@ pub enum Gtoken<'a> {
     DoubleQuotedString(&'a [u8]), // "(?:\\.|[^"])*"?
     IntLiteral(&'a [u8]),         // -?[0-9]+
     Comment(&'a [u8]),            // #[^\n\r]*
+    Assignment(&'a [u8]),         // :<any token>, holding the name
     Block(Vec<Gtoken<'a>>, &'a [u8]),
 }
 
@@ -25,6 +28,7 @@ impl<'a> Gtoken<'a> {
             | &Gtoken::DoubleQuotedString(s)
             | &Gtoken::IntLiteral(s)
             | &Gtoken::Comment(s)
+            | &Gtoken::Assignment(s)
             | &Gtoken::Block(_, s) => s,
         }
     }
@@ -36,7 +40,7 @@ fn single<'a, Error: nom::error::ParseError<&'a [u8]>>(
     take_while_m_n(1, 1, move |c| c == b)
 }
 
-fn parse_identifier(i: &[u8]) -> IResult<&[u8], Gtoken> {
+fn parse_identifier(i: &[u8]) -> IResult<&[u8], Gtoken<'_>> {
     let head = take_while_m_n(1, 1, |c| is_alphabetic(c) || c == b'_');
     let tail = take_while(|c| is_alphabetic(c) || is_digit(c) || c == b'_');
     let (i, s) = recognize(pair(head, tail))(i)?;
@@ -55,17 +59,17 @@ fn parse_string(delimiter: u8, i: &[u8]) -> IResult<&[u8], &[u8]> {
     ))(i)
 }
 
-fn parse_single_quoted_string(i: &[u8]) -> IResult<&[u8], Gtoken> {
+fn parse_single_quoted_string(i: &[u8]) -> IResult<&[u8], Gtoken<'_>> {
     let (i, s) = parse_string(b'\'', i)?;
     Ok((i, Gtoken::SingleQuotedString(s)))
 }
 
-fn parse_double_quoted_string(i: &[u8]) -> IResult<&[u8], Gtoken> {
+fn parse_double_quoted_string(i: &[u8]) -> IResult<&[u8], Gtoken<'_>> {
     let (i, s) = parse_string(b'"', i)?;
     Ok((i, Gtoken::DoubleQuotedString(s)))
 }
 
-fn parse_int_literal(i: &[u8]) -> IResult<&[u8], Gtoken> {
+fn parse_int_literal(i: &[u8]) -> IResult<&[u8], Gtoken<'_>> {
     let (i, s) = recognize(pair(
         take_while_m_n(0, 1, |b| b == b'-'),
         take_while1(is_digit),
@@ -73,25 +77,41 @@ fn parse_int_literal(i: &[u8]) -> IResult<&[u8], Gtoken> {
     Ok((i, Gtoken::IntLiteral(s)))
 }
 
-fn parse_comment(i: &[u8]) -> IResult<&[u8], Gtoken> {
+fn parse_comment(i: &[u8]) -> IResult<&[u8], Gtoken<'_>> {
     let (i, s) = recognize(pair(single(b'#'), take_while(|b| b != b'\r' && b != b'\n')))(i)?;
     Ok((i, Gtoken::Comment(s)))
 }
 
-fn parse_block(i: &[u8]) -> IResult<&[u8], Gtoken> {
+fn parse_block(i: &[u8]) -> IResult<&[u8], Gtoken<'_>> {
     let (i, _) = single(b'{')(i)?;
     let (i, (src, tokens)) = consumed(parse_code)(i)?;
     let (i, _) = single(b'}')(i)?;
     Ok((i, Gtoken::Block(tokens, src)))
 }
 
-fn parse_symbol(i: &[u8]) -> IResult<&[u8], Gtoken> {
+/// `:` takes the next token verbatim as a name, exactly like the reference
+/// tokenizer: `:+`, `:{`, `:"s"`, or even `:` followed by a newline.
+fn parse_assignment(i: &[u8]) -> IResult<&[u8], Gtoken<'_>> {
+    let (i, _) = single(b':')(i)?;
+    let (i, name) = alt((
+        recognize(parse_identifier),
+        |i| parse_string(b'\'', i),
+        |i| parse_string(b'"', i),
+        recognize(parse_int_literal),
+        recognize(parse_comment),
+        take(1usize),
+    ))(i)?;
+    Ok((i, Gtoken::Assignment(name)))
+}
+
+fn parse_symbol(i: &[u8]) -> IResult<&[u8], Gtoken<'_>> {
     let (i, s) = take_while_m_n(1, 1, |b| b != b'{' && b != b'}' && b != b'"' && b != b'\'')(i)?;
     Ok((i, Gtoken::Symbol(s)))
 }
 
-pub fn parse_token(i: &[u8]) -> IResult<&[u8], Gtoken> {
+pub fn parse_token(i: &[u8]) -> IResult<&[u8], Gtoken<'_>> {
     alt((
+        parse_assignment,
         parse_identifier,
         parse_single_quoted_string,
         parse_double_quoted_string,
@@ -102,6 +122,578 @@ pub fn parse_token(i: &[u8]) -> IResult<&[u8], Gtoken> {
     ))(i)
 }
 
-pub fn parse_code(i: &[u8]) -> IResult<&[u8], Vec<Gtoken>> {
+pub fn parse_code(i: &[u8]) -> IResult<&[u8], Vec<Gtoken<'_>>> {
     many0(parse_token)(i)
 }
+
+/// Where `token` starts in `code`, which it must have been parsed from.
+pub fn offset(code: &[u8], token: &[u8]) -> usize {
+    token.as_ptr() as usize - code.as_ptr() as usize
+}
+
+/// A 1-based line and column; columns count characters, not bytes.
+#[derive(Clone, Copy, Debug)]
+pub struct Location {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Location {
+    pub fn new(code: &[u8], offset: usize) -> Location {
+        let before = &code[..offset.min(code.len())];
+        let line_start = before
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        Location {
+            line: before.iter().filter(|&&b| b == b'\n').count() + 1,
+            col: String::from_utf8_lossy(&before[line_start..])
+                .chars()
+                .count()
+                + 1,
+        }
+    }
+}
+
+/// The line of `code` containing `offset`, with `len` bytes underlined:
+///
+/// ```text
+///   3 | {1 2 "abc}
+///     |      ^
+/// ```
+pub fn snippet(code: &[u8], offset: usize, len: usize) -> String {
+    snippet_with(code, offset, len, |start, end| {
+        String::from_utf8_lossy(&code[start..end]).into_owned()
+    })
+}
+
+/// Like [`snippet`], with the line's bytes `start..end` shown by `show`.
+pub fn snippet_with(
+    code: &[u8],
+    offset: usize,
+    len: usize,
+    show: impl Fn(usize, usize) -> String,
+) -> String {
+    snippet_from(code, offset, len, 1, show)
+}
+
+/// Like [`snippet_with`], for code starting on line `first` of a program.
+fn snippet_from(
+    code: &[u8],
+    offset: usize,
+    len: usize,
+    first: usize,
+    show: impl Fn(usize, usize) -> String,
+) -> String {
+    let offset = offset.min(code.len());
+    let mut loc = Location::new(code, offset);
+    loc.line += first - 1;
+    let start = code[..offset]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    let end = code[offset..]
+        .iter()
+        .position(|&b| b == b'\n' || b == b'\r')
+        .map_or(code.len(), |i| offset + i);
+    let text = show(start, end).replace('\t', " ");
+    let underlined = &code[offset..(offset + len).min(end)];
+    let width = String::from_utf8_lossy(underlined).chars().count().max(1);
+    let gutter = loc.line.to_string().len();
+    format!(
+        "{:>g$} | {}\n{:>g$} | {}{}",
+        loc.line,
+        text,
+        "",
+        " ".repeat(loc.col - 1),
+        "^".repeat(width),
+        g = gutter
+    )
+}
+
+/// `bytes` as text, with control characters like newlines escaped.
+pub(crate) fn escape_controls(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .chars()
+        .map(|c| match c {
+            c if c.is_control() => c.escape_default().to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Why tokenizing stopped at `rest`, and where the problem really is.
+pub fn diagnose(mut rest: &[u8]) -> (&[u8], &'static str) {
+    loop {
+        match rest.first() {
+            Some(b'{') => match parse_code(&rest[1..]) {
+                Ok((inner, _)) if !inner.is_empty() && inner[0] != b'}' => rest = inner,
+                _ => return (rest, "unclosed `{`"),
+            },
+            Some(b'}') => return (rest, "unmatched `}`"),
+            Some(b'"') | Some(b'\'') => return (rest, "unterminated string"),
+            _ => return (rest, "unexpected input"),
+        }
+    }
+}
+
+/// The tokens of `code`, or the error `run` gives for code that doesn't
+/// parse.
+pub(crate) fn parse_program(code: &[u8]) -> GsResult<Vec<Gtoken<'_>>> {
+    let (rest, tokens) =
+        parse_code(code).map_err(|e| GsError::Parse(ParseError::new(code, 0, &e.to_string())))?;
+    if !rest.is_empty() {
+        let (at, message) = diagnose(rest);
+        return Err(GsError::Parse(ParseError::new(
+            code,
+            offset(code, at),
+            message,
+        )));
+    }
+    if let Some(Gtoken::Symbol(colon @ b":")) = tokens.last() {
+        let message = "`:` at end of code has no name to assign to";
+        return Err(GsError::Parse(ParseError::new(
+            code,
+            offset(code, colon),
+            message,
+        )));
+    }
+    Ok(tokens)
+}
+
+/// Whether code is a whole program, for a REPL deciding whether to run it or
+/// read more.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Completeness {
+    Complete,
+    /// More code could finish it: it stops at an unclosed `{` or string.
+    Incomplete,
+    /// No more code could make it parse, as with an unmatched `}`.
+    Invalid,
+}
+
+pub fn completeness(code: &[u8]) -> Completeness {
+    match parse_code(code) {
+        Ok((b"", _)) => Completeness::Complete,
+        Ok((rest, tokens)) if rest[0] == b'}' && settled(code, &tokens) == offset(code, rest) => {
+            Completeness::Invalid
+        }
+        Ok(_) => Completeness::Incomplete,
+        Err(_) => Completeness::Invalid,
+    }
+}
+
+/// How much of `code`, which may be cut short, is `tokens` that no code
+/// after it could change, `tokens` being what it parsed to. A token running
+/// to the end could go on, like `ab` into `abc`, and an assignment to a
+/// lone quote is one to a string cut short, as `:"ab` would be `:"ab"`.
+fn settled(code: &[u8], tokens: &[Gtoken]) -> usize {
+    fn cut_short(token: &Gtoken) -> bool {
+        match token {
+            Gtoken::Block(inner, _) => inner.iter().any(cut_short),
+            Gtoken::Assignment(name) => *name == b"\"" || *name == b"'",
+            _ => false,
+        }
+    }
+    let mut end = 0;
+    for token in tokens {
+        let lexeme = token.lexeme();
+        let token_end = match token {
+            Gtoken::Block(..) => offset(code, lexeme) + lexeme.len() + 1,
+            _ => offset(code, lexeme) + lexeme.len(),
+        };
+        if cut_short(token) || token_end == code.len() {
+            break;
+        }
+        end = token_end;
+    }
+    end
+}
+
+/// Where a [`Scanner`] is within a token.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scan {
+    /// Between tokens: the next byte starts one.
+    Between,
+    /// After the `:` of an assignment, before its name.
+    Colon,
+    /// After a `-`, which starts an integer if a digit follows.
+    Minus,
+    Int,
+    Word,
+    Comment,
+    /// In a string quoted by `delimiter`, just after a `\` if `escaped`.
+    String {
+        delimiter: u8,
+        escaped: bool,
+    },
+}
+
+/// Follows the tokenizer a byte at a time, so it can pick up where it left
+/// off as more of a program arrives, finding where top-level tokens start.
+#[derive(Clone, Copy, Debug)]
+struct Scanner {
+    scan: Scan,
+    /// How many blocks the scanner is inside.
+    depth: usize,
+}
+
+impl Scanner {
+    fn new() -> Scanner {
+        Scanner {
+            scan: Scan::Between,
+            depth: 0,
+        }
+    }
+
+    /// Scan `b`, returning whether a top-level token starts there.
+    fn feed(&mut self, b: u8) -> bool {
+        let word = |b: u8| is_alphabetic(b) || is_digit(b) || b == b'_';
+        match self.scan {
+            Scan::Between => {
+                let starts = self.depth == 0;
+                self.scan = match b {
+                    b'{' => {
+                        self.depth += 1;
+                        Scan::Between
+                    }
+                    b'}' => {
+                        self.depth = self.depth.saturating_sub(1);
+                        Scan::Between
+                    }
+                    _ => Scanner::start(b),
+                };
+                return starts;
+            }
+            // A name is any token, or any one byte, even a brace.
+            Scan::Colon => {
+                self.scan = match b {
+                    b'{' | b'}' | b':' => Scan::Between,
+                    _ => Scanner::start(b),
+                }
+            }
+            Scan::Minus | Scan::Int if is_digit(b) => self.scan = Scan::Int,
+            Scan::Word if word(b) => {}
+            Scan::Comment if b != b'\r' && b != b'\n' => {}
+            Scan::String { delimiter, escaped } => {
+                self.scan = if escaped {
+                    Scan::String {
+                        delimiter,
+                        escaped: false,
+                    }
+                } else if b == delimiter {
+                    Scan::Between
+                } else {
+                    Scan::String {
+                        delimiter,
+                        escaped: b == b'\\',
+                    }
+                }
+            }
+            // The token ended before `b`.
+            _ => {
+                self.scan = Scan::Between;
+                return self.feed(b);
+            }
+        }
+        false
+    }
+
+    /// What a token starting with `b` is, other than a block.
+    fn start(b: u8) -> Scan {
+        match b {
+            b':' => Scan::Colon,
+            b'-' => Scan::Minus,
+            b'#' => Scan::Comment,
+            b'"' | b'\'' => Scan::String {
+                delimiter: b,
+                escaped: false,
+            },
+            b if is_digit(b) => Scan::Int,
+            b if is_alphabetic(b) || b == b'_' => Scan::Word,
+            _ => Scan::Between,
+        }
+    }
+}
+
+/// Whole top-level tokens of a program read a piece at a time, so only the
+/// biggest of them, like a long block, need be in memory at once. Run one
+/// after another, the pieces do what the program would. A piece is handed
+/// out once the next token has started, so an error like an unmatched `}`
+/// comes after the pieces before it, and shows only the part of its line
+/// not handed out yet.
+pub struct Pieces<R> {
+    input: R,
+    /// What's been read but not handed out, from `offset` in the program,
+    /// which is at the column `col` of the line `line` there.
+    buffer: Vec<u8>,
+    offset: usize,
+    line: usize,
+    col: usize,
+    /// How much of the buffer has been scanned, where in it the last
+    /// top-level token started, and whether that was an unmatched `}`.
+    scanner: Scanner,
+    scanned: usize,
+    start: usize,
+    unmatched: bool,
+    /// Whether `input` has ended, and whether the pieces have.
+    ended: bool,
+    done: bool,
+}
+
+/// Part of a program, and where it starts.
+pub struct Piece {
+    pub offset: usize,
+    pub code: Vec<u8>,
+}
+
+impl<R: BufRead> Pieces<R> {
+    pub fn new(input: R) -> Pieces<R> {
+        Pieces {
+            input,
+            buffer: vec![],
+            offset: 0,
+            line: 1,
+            col: 1,
+            scanner: Scanner::new(),
+            scanned: 0,
+            start: 0,
+            unmatched: false,
+            ended: false,
+            done: false,
+        }
+    }
+
+    /// Scan what's been read and not scanned yet, stopping at an unmatched
+    /// `}`.
+    fn scan(&mut self) {
+        while self.scanned < self.buffer.len() && !self.unmatched {
+            let b = self.buffer[self.scanned];
+            let at_top = self.scanner.depth == 0;
+            if self.scanner.feed(b) {
+                self.start = self.scanned;
+                self.unmatched = at_top && b == b'}';
+            }
+            self.scanned += 1;
+        }
+    }
+
+    /// Hand out the first `len` bytes of the buffer.
+    fn take(&mut self, len: usize) -> Piece {
+        let code: Vec<u8> = self.buffer.drain(..len).collect();
+        self.scanned -= len;
+        self.start = self.start.saturating_sub(len);
+        let after = Location::new(&code, code.len());
+        if after.line > 1 {
+            self.col = after.col;
+        } else {
+            self.col += after.col - 1;
+        }
+        self.line += after.line - 1;
+        let piece = Piece {
+            offset: self.offset,
+            code,
+        };
+        self.offset += len;
+        piece
+    }
+
+    /// `e`, about the buffer, as about the whole program.
+    fn locate(&self, mut e: GsError) -> GsError {
+        if let GsError::Parse(e) = &mut e {
+            e.snippet = snippet_from(&self.buffer, e.offset, 1, self.line, |start, end| {
+                String::from_utf8_lossy(&self.buffer[start..end]).into_owned()
+            });
+            if e.location.line == 1 {
+                e.location.col += self.col - 1;
+            }
+            e.location.line += self.line - 1;
+            e.offset += self.offset;
+        }
+        e
+    }
+}
+
+impl<R: BufRead> Iterator for Pieces<R> {
+    type Item = GsResult<Piece>;
+
+    fn next(&mut self) -> Option<GsResult<Piece>> {
+        while !self.done {
+            self.scan();
+            if self.start > 0 {
+                return Some(Ok(self.take(self.start)));
+            }
+            if self.unmatched {
+                self.done = true;
+                let e = ParseError::new(&self.buffer, 0, "unmatched `}`");
+                return Some(Err(self.locate(GsError::Parse(e))));
+            }
+            if self.ended {
+                self.done = true;
+                if let Err(e) = parse_program(&self.buffer) {
+                    return Some(Err(self.locate(e)));
+                }
+                if self.buffer.is_empty() {
+                    return None;
+                }
+                return Some(Ok(self.take(self.buffer.len())));
+            }
+            match self.input.fill_buf() {
+                Ok([]) => self.ended = true,
+                Ok(bytes) => {
+                    let n = bytes.len();
+                    self.buffer.extend_from_slice(bytes);
+                    self.input.consume(n);
+                }
+                Err(e) => {
+                    self.done = true;
+                    let message = format!("can't read the program: {}", e);
+                    return Some(Err(GsError::Runtime(message)));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The tokens of `code`, flattened, with blocks as their braces around their
+/// contents and an assignment as `:` and its name, or `None` if it doesn't
+/// all parse. Whitespace is left out, and so are comments unless `comments`,
+/// so programs that differ only in those give the same.
+pub fn significant_tokens(code: &[u8], comments: bool) -> Option<Vec<Vec<u8>>> {
+    fn walk(tokens: Vec<Gtoken>, comments: bool, out: &mut Vec<Vec<u8>>) {
+        for token in tokens {
+            match token {
+                Gtoken::Block(inner, _) => {
+                    out.push(b"{".to_vec());
+                    walk(inner, comments, out);
+                    out.push(b"}".to_vec());
+                }
+                Gtoken::Assignment(name) => out.push([b":", name].concat()),
+                Gtoken::Comment(_) if !comments => {}
+                Gtoken::Symbol(s) if s.iter().all(u8::is_ascii_whitespace) => {}
+                token => out.push(token.lexeme().to_vec()),
+            }
+        }
+    }
+    match parse_code(code) {
+        Ok((&[], tokens)) => {
+            let mut out = vec![];
+            walk(tokens, comments, &mut out);
+            Some(out)
+        }
+        _ => None,
+    }
+}
+
+/// How long the `#!` line at the start of `code` is with its newline, or 0
+/// if there isn't one. It's a comment, so a program runs the same with it,
+/// but it isn't part of the program as golf counts it.
+pub fn shebang_len(code: &[u8]) -> usize {
+    if !code.starts_with(b"#!") {
+        return 0;
+    }
+    code.iter()
+        .position(|&b| b == b'\n')
+        .map_or(code.len(), |i| i + 1)
+}
+
+/// Whether `code` assigns to a name made of whitespace, like `:` followed
+/// by a newline, which makes that whitespace do something wherever it is.
+pub fn assigns_whitespace(code: &[u8]) -> bool {
+    fn walk(tokens: &[Gtoken]) -> bool {
+        tokens.iter().any(|token| match token {
+            Gtoken::Block(inner, _) => walk(inner),
+            Gtoken::Assignment(name) => name.iter().all(u8::is_ascii_whitespace),
+            _ => false,
+        })
+    }
+    parse_code(code).is_ok_and(|(_, tokens)| walk(&tokens))
+}
+
+/// What kind of token a [`Lexeme`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Class {
+    Int,
+    String,
+    Comment,
+    /// A name like `puts` or `x`.
+    Word,
+    /// Any other single character, like `+` or `.`, or a multibyte one.
+    Symbol,
+    /// `:` and the name it assigns to.
+    Assignment,
+    Brace,
+    Whitespace,
+    /// What's left where tokenizing stopped.
+    Unparsed,
+}
+
+/// A piece of a program and what kind of token it is.
+#[derive(Clone, Copy, Debug)]
+pub struct Lexeme {
+    pub offset: usize,
+    pub len: usize,
+    pub class: Class,
+}
+
+/// `code` split into lexemes that cover all of it, in order, with blocks
+/// split into their braces and contents. A multibyte character is one
+/// symbol, though it tokenizes as one per byte.
+pub fn lex(code: &[u8]) -> Vec<Lexeme> {
+    fn walk(code: &[u8], tokens: Vec<Gtoken>, out: &mut Vec<Lexeme>) {
+        for token in tokens {
+            let lexeme = token.lexeme();
+            let at = offset(code, lexeme);
+            let (offset, len, class) = match token {
+                Gtoken::Block(inner, _) => {
+                    out.push(Lexeme {
+                        offset: at - 1,
+                        len: 1,
+                        class: Class::Brace,
+                    });
+                    walk(code, inner, out);
+                    (at + lexeme.len(), 1, Class::Brace)
+                }
+                Gtoken::Assignment(_) => (at - 1, lexeme.len() + 1, Class::Assignment),
+                Gtoken::IntLiteral(_) => (at, lexeme.len(), Class::Int),
+                Gtoken::SingleQuotedString(_) | Gtoken::DoubleQuotedString(_) => {
+                    (at, lexeme.len(), Class::String)
+                }
+                Gtoken::Comment(_) => (at, lexeme.len(), Class::Comment),
+                Gtoken::Symbol(s) if s.iter().all(u8::is_ascii_whitespace) => {
+                    (at, lexeme.len(), Class::Whitespace)
+                }
+                Gtoken::Symbol(s) if s[0].is_ascii_alphabetic() || s[0] == b'_' => {
+                    (at, lexeme.len(), Class::Word)
+                }
+                Gtoken::Symbol(s) => match out.last_mut() {
+                    Some(last)
+                        if last.class == Class::Symbol
+                            && std::str::from_utf8(&code[last.offset..at])
+                                .is_err_and(|e| e.error_len().is_none()) =>
+                    {
+                        last.len += s.len();
+                        continue;
+                    }
+                    _ => (at, lexeme.len(), Class::Symbol),
+                },
+            };
+            out.push(Lexeme { offset, len, class });
+        }
+    }
+    let mut out = vec![];
+    let (rest, tokens) = match parse_code(code) {
+        Ok(parsed) => parsed,
+        Err(_) => (code, vec![]),
+    };
+    walk(code, tokens, &mut out);
+    if !rest.is_empty() {
+        out.push(Lexeme {
+            offset: code.len() - rest.len(),
+            len: rest.len(),
+            class: Class::Unparsed,
+        });
+    }
+    out
+}
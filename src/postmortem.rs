@@ -0,0 +1,82 @@
+//! What the interpreter looked like when a program failed: the stack as the
+//! error left it, the variables the program defined or changed, and the last
+//! few tokens that ran, for a crash report to show along with the trace.
+
+use crate::util::preview;
+use crate::value::Gval;
+use serde_json::{json, Value};
+
+pub struct PostMortem {
+    /// The stack, bottom first.
+    pub stack: Vec<Gval>,
+    /// Variables that aren't as the builtin definitions left them, by name.
+    pub vars: Vec<(Vec<u8>, Gval)>,
+    /// The last tokens that ran, oldest first, each with how deeply it was
+    /// nested (1 for the program). Long tokens are cut short.
+    pub recent: Vec<(usize, Vec<u8>)>,
+}
+
+/// How many characters of a value to show.
+const SHOWN: usize = 60;
+
+impl PostMortem {
+    /// The state as text, the top of the stack first.
+    pub fn report(&self) -> String {
+        // A deep stack is mostly leftovers; its top is what failed.
+        const TOP: usize = 20;
+        let mut lines = vec![format!("stack ({} values, top first):", self.stack.len())];
+        for v in self.stack.iter().rev().take(TOP) {
+            lines.push(format!("  {}", preview(v, SHOWN)));
+        }
+        if self.stack.len() > TOP {
+            lines.push(format!("  ... {} more ...", self.stack.len() - TOP));
+        }
+        if !self.vars.is_empty() {
+            lines.push("variables:".to_string());
+            for (name, v) in &self.vars {
+                lines.push(format!(
+                    "  {} = {}",
+                    String::from_utf8_lossy(name),
+                    preview(v, SHOWN)
+                ));
+            }
+        }
+        lines.push("last tokens run, oldest first:".to_string());
+        for (depth, token) in &self.recent {
+            let token = String::from_utf8_lossy(token).replace('\n', " ");
+            lines.push(format!(
+                "  {}{}",
+                "  ".repeat(depth.saturating_sub(1)),
+                token
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// The state as a JSON object, with values shown as GolfScript and cut
+    /// short like in the report.
+    pub fn to_json(&self) -> Value {
+        let vars: serde_json::Map<String, Value> = self
+            .vars
+            .iter()
+            .map(|(name, v)| {
+                (
+                    String::from_utf8_lossy(name).into_owned(),
+                    preview(v, SHOWN).into(),
+                )
+            })
+            .collect();
+        let recent: Vec<_> = self
+            .recent
+            .iter()
+            .map(
+                |(depth, token)| json!({ "depth": depth, "token": String::from_utf8_lossy(token) }),
+            )
+            .collect();
+        json!({
+            "stack": self.stack.iter().map(|v| preview(v, SHOWN)).collect::<Vec<_>>(),
+            "vars": vars,
+            "recent": recent,
+        })
+    }
+}
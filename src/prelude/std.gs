@@ -0,0 +1,23 @@
+# The standard prelude, loaded with `--prelude std`. Every name here is an
+# ordinary variable, so programs can still redefine any of them.
+
+# Numbers
+{0\{+}/}:sum;           # [1 2 3]sum -> 6
+{1\{*}/}:product;       # [1 2 3 4]product -> 24
+{$)\;}:max;             # [3 1 2]max -> 3
+{$0=}:min;              # [3 1 2]min -> 1
+{.*}:sq;                # 5sq -> 25
+{10base}:digits;        # 123digits -> [1 2 3], and [1 2 3]digits -> 123
+{2base}:bits;           # 6bits -> [1 1 0]
+
+# Arrays
+{zip}:transpose;        # [[1 2][3 4]]transpose -> [[1 3][2 4]]
+{-1%}:reverse;          # [1 2 3]reverse -> [3 2 1]
+{.&}:dedup;             # [1 2 1 3]dedup -> [1 2 3]
+{[.,,\]zip}:enumerate;  # ["a" "b"]enumerate -> [[0 "a"] [1 "b"]]
+
+# Strings
+{n/}:lines;             # "a\nb"lines -> ["a" "b"]
+{n*}:unlines;           # ["a" "b"]unlines -> "a\nb"
+{" "*}:unwords;         # ["a" "b"]unwords -> "a b"
+{.-1%=}:palindrome;     # "abba"palindrome -> 1
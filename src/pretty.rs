@@ -0,0 +1,212 @@
+//! Values printed for reading rather than as code: like `` ` ``, but an
+//! array too long for one line is broken up, indented by nesting, with
+//! each element that's an array on a line of its own and other elements
+//! filling lines. Each type can have its own ANSI color, and long values
+//! can be cut short to their ends, for values too big to read whole.
+
+use crate::value::Gval;
+
+/// How wide a line may be before an array on it is broken up.
+const WIDTH: usize = 80;
+
+/// How far each level of nesting is indented.
+const INDENT: usize = 2;
+
+/// How many elements at each end of a long array are shown when eliding,
+/// and how many characters at each end of a long string, block or number.
+const ENDS_LEN: usize = 32;
+const ENDS_CHARS: usize = 256;
+
+/// The ANSI color of a gap where part of a value was left out.
+const GAP: &str = "90";
+
+/// The ANSI color of a value of each type, like the highlighter's; an
+/// array's is its brackets'.
+fn color(v: &Gval) -> &'static str {
+    match v {
+        Gval::Int(_) => "36",
+        Gval::Str(_) => "32",
+        Gval::Blk(_) => "34",
+        Gval::Arr(_) => "1",
+    }
+}
+
+/// How to show a value.
+#[derive(Clone, Copy, Default)]
+pub struct Style {
+    /// Break arrays too long for a line up over several.
+    pub broken: bool,
+    /// Color each type with ANSI escapes.
+    pub colored: bool,
+    /// Show only the ends of long values, noting how much is left out.
+    pub elided: bool,
+}
+
+/// `v` pretty-printed, with ANSI colors if `colored`. There's no final
+/// newline.
+pub fn pretty(v: &Gval, colored: bool) -> Vec<u8> {
+    let style = Style {
+        broken: true,
+        colored,
+        elided: false,
+    };
+    show(v, style)
+}
+
+/// `v` shown in `style`, without a final newline.
+pub fn show(v: &Gval, style: Style) -> Vec<u8> {
+    let mut out = vec![];
+    style.write(&mut out, v, 0);
+    out
+}
+
+/// Whether showing `v` elided leaves part of it out.
+pub fn elides(v: &Gval) -> bool {
+    match v {
+        Gval::Arr(vs) => vs.len() > 2 * ENDS_LEN || vs.iter().any(elides),
+        _ => ends(&v.clone().inspect()).is_some(),
+    }
+}
+
+/// Where to cut `text` to keep its first and last `ENDS_CHARS` characters,
+/// and how many characters are left out between; `None` if it's short
+/// enough to show whole. A character starts at any byte that doesn't
+/// continue a UTF-8 sequence, so a cut never splits one.
+fn ends(text: &[u8]) -> Option<(usize, usize, usize)> {
+    let starts: Vec<usize> = (0..text.len())
+        .filter(|&i| text[i] & 0xc0 != 0x80)
+        .collect();
+    if starts.len() <= 2 * ENDS_CHARS {
+        return None;
+    }
+    let left_out = starts.len() - 2 * ENDS_CHARS;
+    Some((
+        starts[ENDS_CHARS],
+        starts[starts.len() - ENDS_CHARS],
+        left_out,
+    ))
+}
+
+/// An element of an array as shown: a value, or a gap where this many were
+/// left out.
+enum Part<'a> {
+    Value(&'a Gval),
+    Gap(usize),
+}
+
+impl Style {
+    /// Append `v` to `out`, at a line indented by `indent`.
+    fn write(&self, out: &mut Vec<u8>, v: &Gval, indent: usize) {
+        match v {
+            Gval::Arr(vs) if self.broken && !vs.is_empty() && indent + self.len(v) > WIDTH => {
+                self.paint(out, b"[", color(v));
+                if vs.iter().any(|e| matches!(e, Gval::Arr(_))) {
+                    for part in self.parts(vs) {
+                        newline(out, indent + INDENT);
+                        match part {
+                            Part::Value(e) => self.write(out, e, indent + INDENT),
+                            Part::Gap(_) => self.part(out, &part),
+                        }
+                    }
+                } else {
+                    // Fill lines with elements, like words in a paragraph.
+                    let mut column = WIDTH;
+                    for part in self.parts(vs) {
+                        let mut text = vec![];
+                        Style {
+                            colored: false,
+                            ..*self
+                        }
+                        .part(&mut text, &part);
+                        if column + 1 + text.len() > WIDTH {
+                            newline(out, indent + INDENT);
+                            column = indent + INDENT;
+                        } else {
+                            out.push(b' ');
+                            column += 1;
+                        }
+                        self.part(out, &part);
+                        column += text.len();
+                    }
+                }
+                newline(out, indent);
+                self.paint(out, b"]", color(v));
+            }
+            _ => self.inline(out, v),
+        }
+    }
+
+    /// How long `v` is on one line.
+    fn len(&self, v: &Gval) -> usize {
+        let mut text = vec![];
+        Style {
+            colored: false,
+            ..*self
+        }
+        .inline(&mut text, v);
+        text.len()
+    }
+
+    /// The elements of an array to show.
+    fn parts<'a>(&self, vs: &'a [Gval]) -> Vec<Part<'a>> {
+        if !self.elided || vs.len() <= 2 * ENDS_LEN {
+            return vs.iter().map(Part::Value).collect();
+        }
+        let (first, last) = (&vs[..ENDS_LEN], &vs[vs.len() - ENDS_LEN..]);
+        let gap = Part::Gap(vs.len() - 2 * ENDS_LEN);
+        let values = |vs: &'a [Gval]| vs.iter().map(Part::Value);
+        values(first)
+            .chain(std::iter::once(gap))
+            .chain(values(last))
+            .collect()
+    }
+
+    fn part(&self, out: &mut Vec<u8>, part: &Part) {
+        match part {
+            Part::Value(v) => self.inline(out, v),
+            Part::Gap(n) => self.paint(out, format!("...({} more)...", n).as_bytes(), GAP),
+        }
+    }
+
+    /// Append `v` to `out` on one line, as `` ` `` shows it.
+    fn inline(&self, out: &mut Vec<u8>, v: &Gval) {
+        match v {
+            Gval::Arr(vs) => {
+                self.paint(out, b"[", color(v));
+                for (i, part) in self.parts(vs).iter().enumerate() {
+                    if i > 0 {
+                        out.push(b' ');
+                    }
+                    self.part(out, part);
+                }
+                self.paint(out, b"]", color(v));
+            }
+            _ => {
+                let text = v.clone().inspect();
+                let cut = if self.elided { ends(&text) } else { None };
+                let (head, tail, left_out) = match cut {
+                    Some(cut) => cut,
+                    None => return self.paint(out, &text, color(v)),
+                };
+                self.paint(out, &text[..head], color(v));
+                self.part(out, &Part::Gap(left_out));
+                self.paint(out, &text[tail..], color(v));
+            }
+        }
+    }
+
+    fn paint(&self, out: &mut Vec<u8>, text: &[u8], color: &str) {
+        if self.colored {
+            out.extend(format!("\x1b[{}m", color).bytes());
+            out.extend(text);
+            out.extend(b"\x1b[0m");
+        } else {
+            out.extend(text);
+        }
+    }
+}
+
+fn newline(out: &mut Vec<u8>, indent: usize) {
+    out.push(b'\n');
+    out.resize(out.len() + indent, b' ');
+}
@@ -0,0 +1,258 @@
+//! Counting what a run does, token by token, and showing it on the program:
+//! which tokens ran, and where the time went. A token's time runs until the
+//! next token starts, so a builtin that runs a block isn't charged for the
+//! block's tokens. For flame graphs, a token's stack is the tokens that ran
+//! the blocks it's in, like a `%` or `do` or a variable holding a block.
+//! Tokens in code that isn't found in the program, like strings built at
+//! run time and run by `~`, aren't counted. Identical block literals all
+//! count as the first one.
+
+use crate::error::GsResult;
+use crate::hook::{Hook, Step};
+use crate::parse::{offset, parse_code, Gtoken, Location};
+use crate::util::preview_code;
+use crate::Gs;
+use serde_json::json;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeatmapFormat {
+    /// The program with each token shaded by its share of the time.
+    Text,
+    /// Counts and times per token, for other tools to render.
+    Json,
+}
+
+#[derive(Default)]
+pub struct Profiler {
+    /// How many times the token at each program offset ran.
+    counts: HashMap<usize, u64>,
+    /// How long the token at each program offset ran, in total.
+    times: HashMap<usize, Duration>,
+    /// How long each stack of tokens ran, innermost last.
+    stacks: HashMap<Vec<Option<usize>>, Duration>,
+    /// The stack running now and when its last token started.
+    current: Option<(Vec<Option<usize>>, Instant)>,
+}
+
+impl Hook for Profiler {
+    fn before(&mut self, _gs: &mut Gs, step: &Step) -> GsResult<()> {
+        let mut stack = self.finish().unwrap_or_default();
+        if let Some(at) = step.program_offset {
+            *self.counts.entry(at).or_default() += 1;
+        }
+        stack.truncate(step.depth.saturating_sub(1));
+        stack.push(step.program_offset);
+        self.current = Some((stack, Instant::now()));
+        Ok(())
+    }
+}
+
+/// The offset and length of every token in `program` that does something,
+/// including those inside blocks; blocks include their braces.
+fn tokens(program: &[u8]) -> Vec<(usize, usize)> {
+    fn walk(program: &[u8], tokens: Vec<Gtoken>, out: &mut Vec<(usize, usize)>) {
+        for token in tokens {
+            let lexeme = token.lexeme();
+            let at = offset(program, lexeme);
+            match token {
+                Gtoken::Block(inner, _) => {
+                    out.push((at - 1, lexeme.len() + 2));
+                    walk(program, inner, out);
+                }
+                Gtoken::Assignment(_) => out.push((at - 1, lexeme.len() + 1)),
+                Gtoken::Comment(_) => {}
+                _ if lexeme.iter().all(u8::is_ascii_whitespace) => {}
+                _ => out.push((at, lexeme.len())),
+            }
+        }
+    }
+    let mut out = vec![];
+    if let Ok((_, parsed)) = parse_code(program) {
+        walk(program, parsed, &mut out);
+    }
+    out.sort();
+    out
+}
+
+fn ms(d: Duration) -> String {
+    format!("{:.3}ms", d.as_secs_f64() * 1000.0)
+}
+
+/// How many characters of a token to show.
+const SHOWN: usize = 30;
+
+/// The lines of `program`, each followed by a line marking some of its
+/// tokens, if any, with the character given for each.
+fn annotate(program: &[u8], marked: &[(usize, usize, char)]) -> String {
+    let mut lines = vec![];
+    let mut start = 0;
+    for (n, line) in program.split(|&b| b == b'\n').enumerate() {
+        let end = start + line.len();
+        let text = String::from_utf8_lossy(line).replace('\t', " ");
+        lines.push(format!("{:>4} | {}", n + 1, text.trim_end_matches('\r')));
+        // Tokens inside a block come after it, so their marks go over its.
+        let mut marks: Vec<char> = vec![];
+        for &(at, len, mark) in marked.iter().filter(|t| (start..end).contains(&t.0)) {
+            let col = Location::new(program, at).col - 1;
+            let width = String::from_utf8_lossy(&program[at..(at + len).min(end)])
+                .chars()
+                .count();
+            if marks.len() < col + width.max(1) {
+                marks.resize(col + width.max(1), ' ');
+            }
+            marks[col..col + width.max(1)].fill(mark);
+        }
+        if !marks.is_empty() {
+            lines.push(format!("     | {}", marks.iter().collect::<String>()));
+        }
+        start = end + 1;
+    }
+    lines.join("\n")
+}
+
+impl Profiler {
+    /// Charge the time since the last token started to it; call this when
+    /// the run ends. Returns the stack it ran in.
+    pub fn finish(&mut self) -> Option<Vec<Option<usize>>> {
+        let (stack, started) = self.current.take()?;
+        let elapsed = started.elapsed();
+        if let Some(&Some(at)) = stack.last() {
+            *self.times.entry(at).or_default() += elapsed;
+        }
+        match self.stacks.get_mut(&stack) {
+            Some(time) => *time += elapsed,
+            None => {
+                self.stacks.insert(stack.clone(), elapsed);
+            }
+        }
+        Some(stack)
+    }
+
+    /// The time spent in each stack, in the collapsed format read by
+    /// `flamegraph.pl`, inferno and speedscope: one `frame;frame;token
+    /// nanoseconds` line per stack. Frames are tokens with their line and
+    /// column.
+    pub fn flamegraph(&self, program: &[u8]) -> String {
+        let lengths: HashMap<usize, usize> = tokens(program).into_iter().collect();
+        let frame = |at: &Option<usize>| match *at {
+            Some(at) => {
+                let len = lengths.get(&at).copied().unwrap_or(1);
+                let loc = Location::new(program, at);
+                // `;` separates frames, so the token `;` can't appear as is.
+                let token = preview_code(&program[at..at + len], SHOWN).replace(';', "pop");
+                format!("{} {}:{}", token, loc.line, loc.col)
+            }
+            None => "(code not in the program)".to_string(),
+        };
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .map(|(stack, time)| {
+                let frames: Vec<_> = stack.iter().map(frame).collect();
+                format!("{} {}", frames.join(";"), time.as_nanos())
+            })
+            .collect();
+        lines.sort();
+        lines.join("\n")
+    }
+
+    /// The program with each token that ran marked by how much of the time
+    /// it took, darkest for the most, followed by the slowest tokens.
+    pub fn heatmap(&self, program: &[u8], format: HeatmapFormat) -> String {
+        const SHADES: &[char] = &['.', ':', '-', '=', '+', '*', '#', '%', '@'];
+        let tokens = tokens(program);
+        let time = |at: &usize| self.times.get(at).copied().unwrap_or_default();
+        let total: Duration = tokens.iter().map(|(at, _)| time(at)).sum();
+        if format == HeatmapFormat::Json {
+            let tokens: Vec<_> = tokens
+                .iter()
+                .map(|&(at, len)| {
+                    let loc = Location::new(program, at);
+                    json!({
+                        "offset": at,
+                        "length": len,
+                        "line": loc.line,
+                        "col": loc.col,
+                        "token": String::from_utf8_lossy(&program[at..at + len]),
+                        "count": self.counts.get(&at).copied().unwrap_or(0),
+                        "nanos": time(&at).as_nanos() as u64,
+                    })
+                })
+                .collect();
+            let report = json!({ "total_nanos": total.as_nanos() as u64, "tokens": tokens });
+            return report.to_string();
+        }
+        let max = tokens
+            .iter()
+            .map(|(at, _)| time(at))
+            .max()
+            .unwrap_or_default();
+        let mut shaded: Vec<(usize, usize, char)> = vec![];
+        for &(at, len) in tokens.iter().filter(|(at, _)| self.counts.contains_key(at)) {
+            let shade = if max.is_zero() {
+                0
+            } else {
+                (time(&at).as_nanos() * (SHADES.len() as u128 - 1) / max.as_nanos()) as usize
+            };
+            shaded.push((at, len, SHADES[shade]));
+        }
+        let mut report = vec![
+            format!(
+                "heatmap: {} in tokens, shaded from . (least) to @ (most)",
+                ms(total)
+            ),
+            annotate(program, &shaded),
+            "       time  share  runs  line:col  token".to_string(),
+        ];
+        let mut slowest = tokens.clone();
+        slowest.sort_by_key(|(at, _)| std::cmp::Reverse(time(at)));
+        for &(at, len) in slowest
+            .iter()
+            .take(10)
+            .filter(|(at, _)| !time(at).is_zero())
+        {
+            let loc = Location::new(program, at);
+            report.push(format!(
+                "{:>11}  {:>4.1}%  {:>4}  {:>8}  {}",
+                ms(time(&at)),
+                100.0 * time(&at).as_secs_f64() / total.as_secs_f64(),
+                self.counts.get(&at).copied().unwrap_or(0),
+                format!("{}:{}", loc.line, loc.col),
+                preview_code(&program[at..at + len], SHOWN)
+            ));
+        }
+        report.join("\n")
+    }
+
+    /// The program with tokens that never ran underlined, followed by how
+    /// many times each token ran.
+    pub fn coverage(&self, program: &[u8]) -> String {
+        let tokens = tokens(program);
+        let unrun: Vec<_> = tokens
+            .iter()
+            .filter(|(at, _)| !self.counts.contains_key(at))
+            .map(|&(at, len)| (at, len, '^'))
+            .collect();
+        let mut report = vec![
+            format!(
+                "coverage: {} of {} tokens ran; those marked with ^ never did",
+                tokens.len() - unrun.len(),
+                tokens.len()
+            ),
+            annotate(program, &unrun),
+            "runs  line:col  token".to_string(),
+        ];
+        for &(at, len) in &tokens {
+            let loc = Location::new(program, at);
+            report.push(format!(
+                "{:>4}  {:>8}  {}",
+                self.counts.get(&at).copied().unwrap_or(0),
+                format!("{}:{}", loc.line, loc.col),
+                preview_code(&program[at..at + len], SHOWN)
+            ));
+        }
+        report.join("\n")
+    }
+}
@@ -0,0 +1,502 @@
+//! Programs spelled out as Python-like pseudocode, one token at a time, for
+//! reading and reviewing dense golfs. Each block becomes a function. The
+//! types of values pushed by literals are followed through straight-line
+//! code, so an operator whose operands are known is named for what it does,
+//! like `map` for `%` after a block; otherwise its name lists what it might
+//! do.
+
+use crate::builtins::lookup;
+use crate::parse::{offset, Gtoken, Location};
+use crate::unescape::unescape;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Ty {
+    Int,
+    Str,
+    Arr,
+    Blk,
+    Any,
+}
+
+impl Ty {
+    fn is_seq(self) -> bool {
+        matches!(self, Ty::Str | Ty::Arr)
+    }
+}
+
+/// What is known of the stack while translating straight-line code: the
+/// types of values pushed since it was last unknown, and where each `[`
+/// was.
+#[derive(Default)]
+struct Stack {
+    types: Vec<Ty>,
+    marks: Vec<usize>,
+}
+
+impl Stack {
+    fn pop(&mut self) -> Ty {
+        self.types.pop().unwrap_or(Ty::Any)
+    }
+
+    fn top(&self) -> Ty {
+        self.types.last().copied().unwrap_or(Ty::Any)
+    }
+
+    fn push(&mut self, ty: Ty) {
+        self.types.push(ty);
+    }
+
+    /// Code that was run could have done anything.
+    fn forget(&mut self) {
+        self.types.clear();
+        self.marks.clear();
+    }
+}
+
+struct Translator<'a> {
+    program: &'a [u8],
+    assigned: HashSet<&'a [u8]>,
+    /// Each block's function name, by where its body starts.
+    names: HashMap<usize, String>,
+    /// The functions for blocks, in order.
+    functions: Vec<Vec<String>>,
+}
+
+/// `bytes` as a Python string literal.
+fn py_string(bytes: &[u8]) -> String {
+    let mut s = String::from("\"");
+    for c in String::from_utf8_lossy(bytes).chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\t' => s.push_str("\\t"),
+            '\r' => s.push_str("\\r"),
+            c if c.is_control() => s.push_str(&format!("\\x{:02x}", c as u32)),
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+    s
+}
+
+/// A variable as a Python name, or an entry of `vars` if it isn't one.
+fn py_name(name: &[u8]) -> String {
+    let identifier = name[0].is_ascii_alphabetic() || name[0] == b'_';
+    match identifier && name.iter().all(|&b| b.is_ascii_alphanumeric() || b == b'_') {
+        true => String::from_utf8_lossy(name).into_owned(),
+        false => format!("vars[{}]", py_string(name)),
+    }
+}
+
+/// The pseudocode for the operator `name`, updating what's known of the
+/// stack. Binary operators pop `b`, then `a`.
+fn operator(name: &[u8], stack: &mut Stack) -> Option<Vec<String>> {
+    let lines = |ls: &[&str]| Some(ls.iter().map(|l| l.to_string()).collect());
+    let binary = |expr: &str| lines(&["b = pop(); a = pop()", &format!("push({})", expr)]);
+    let unknown = |stack: &mut Stack, ls: &[&str]| {
+        stack.forget();
+        lines(ls)
+    };
+    let ty = match name {
+        b"." => {
+            let t = stack.top();
+            stack.push(t);
+            return lines(&["dup()"]);
+        }
+        b"\\" => {
+            let (b, a) = (stack.pop(), stack.pop());
+            stack.push(b);
+            stack.push(a);
+            return lines(&["swap()"]);
+        }
+        b";" => {
+            stack.pop();
+            return lines(&["pop()"]);
+        }
+        b"@" => {
+            let (c, b, a) = (stack.pop(), stack.pop(), stack.pop());
+            stack.types.extend([b, c, a]);
+            return lines(&["rotate()  # a b c -> b c a"]);
+        }
+        b"[" => {
+            stack.marks.push(stack.types.len());
+            return lines(&["mark()"]);
+        }
+        b"]" => {
+            match stack.marks.pop() {
+                Some(mark) => stack.types.truncate(mark),
+                None => stack.forget(),
+            }
+            stack.push(Ty::Arr);
+            return lines(&["push(pop_to_mark())"]);
+        }
+        b"`" => {
+            stack.pop();
+            stack.push(Ty::Str);
+            return lines(&["push(inspect(pop()))"]);
+        }
+        b"!" => {
+            stack.pop();
+            stack.push(Ty::Int);
+            return lines(&["push(int(not truthy(pop())))"]);
+        }
+        _ => stack.top(),
+    };
+    // Operators whose meaning depends on the top's type, then on both.
+    match (name, ty) {
+        (b"~", Ty::Int) => return lines(&["push(~pop())"]),
+        (b"~", Ty::Arr) => return unknown(stack, &["push(*pop())  # each element"]),
+        (b"~", Ty::Str) => return unknown(stack, &["run(parse(pop()))"]),
+        (b"~", Ty::Blk) => return unknown(stack, &["run(pop())"]),
+        (b"~", _) => return unknown(stack, &["evaluate(pop())  # run, dump or bitwise not"]),
+        (b"$", Ty::Int) => {
+            stack.pop();
+            stack.push(Ty::Any);
+            return lines(&["push(pick(pop()))  # copy the nth value below"]);
+        }
+        (b"$", Ty::Str | Ty::Arr) => return lines(&["push(sorted(pop()))"]),
+        (b"$", Ty::Blk) => {
+            stack.pop();
+            return lines(&["key = pop()", "push(sorted(pop(), key=key))"]);
+        }
+        (b"$", _) => return unknown(stack, &["pick_or_sort()"]),
+        (b",", Ty::Int) => {
+            stack.pop();
+            stack.push(Ty::Arr);
+            return lines(&["push(list(range(pop())))"]);
+        }
+        (b",", Ty::Str | Ty::Arr) => {
+            stack.pop();
+            stack.push(Ty::Int);
+            return lines(&["push(len(pop()))"]);
+        }
+        (b",", Ty::Blk) => {
+            stack.pop();
+            return lines(&[
+                "block = pop()",
+                "push([x for x in pop() if truthy(block(x))])",
+            ]);
+        }
+        (b",", _) => return unknown(stack, &["range_length_or_select()"]),
+        (b"(" | b")", Ty::Int) => {
+            let op = if name == b"(" { "-" } else { "+" };
+            return lines(&[&format!("push(pop() {} 1)", op)]);
+        }
+        (b"(", Ty::Str | Ty::Arr) => {
+            stack.push(Ty::Any);
+            return lines(&["seq = pop()", "push(seq[1:]); push(seq[0])"]);
+        }
+        (b")", Ty::Str | Ty::Arr) => {
+            stack.push(Ty::Any);
+            return lines(&["seq = pop()", "push(seq[:-1]); push(seq[-1])"]);
+        }
+        (b"(", _) => return unknown(stack, &["decrement_or_take_first()"]),
+        (b")", _) => return unknown(stack, &["increment_or_take_last()"]),
+        _ => {}
+    }
+    let (b, a) = (stack.top(), stack.types.iter().rev().nth(1).copied());
+    let a = a.unwrap_or(Ty::Any);
+    let result = |stack: &mut Stack, ty: Ty, expr: &str| {
+        stack.pop();
+        stack.pop();
+        stack.push(ty);
+        binary(expr)
+    };
+    match (name, a, b) {
+        (b"+" | b"-" | b"*" | b"|" | b"&" | b"^", Ty::Int, Ty::Int) => {
+            let op = String::from_utf8_lossy(name);
+            result(stack, Ty::Int, &format!("a {} b", op))
+        }
+        (b"+", a, b) if a == b && a.is_seq() => result(stack, a, "a + b"),
+        (b"+", ..) => result(stack, Ty::Any, "add_or_concat(a, b)"),
+        (b"-", a, b) if a == b && a.is_seq() => result(stack, a, "without(a, b)"),
+        (b"-", ..) => result(stack, Ty::Any, "subtract_or_remove(a, b)"),
+        (b"*", a, Ty::Int) if a.is_seq() => result(stack, a, "a * b  # repeat"),
+        (b"*", Ty::Int, b) if b.is_seq() => result(stack, b, "b * a  # repeat"),
+        (b"*", Ty::Int, Ty::Blk) => unknown(
+            stack,
+            &["block = pop(); n = pop()", "for _ in range(n): block()"],
+        ),
+        (b"*", a, Ty::Blk) if a.is_seq() => unknown(
+            stack,
+            &[
+                "block = pop()",
+                "fold(pop(), block)  # run block between elements",
+            ],
+        ),
+        (b"*", Ty::Arr, b) if b.is_seq() => result(stack, b, "join(a, b)"),
+        (b"*", ..) => unknown(stack, &["multiply_repeat_join_or_fold()"]),
+        (b"/", Ty::Int, Ty::Int) => result(stack, Ty::Int, "a // b"),
+        (b"/", a, Ty::Blk) if a.is_seq() => unknown(
+            stack,
+            &["block = pop()", "for x in pop(): push(x); block()"],
+        ),
+        (b"/", Ty::Blk, Ty::Blk) => unknown(stack, &["unfold()"]),
+        (b"/", a, b) if a == b && a.is_seq() => result(stack, Ty::Arr, "split(a, b)"),
+        (b"/", a, Ty::Int) if a.is_seq() => result(stack, Ty::Arr, "chunks(a, b)"),
+        (b"/", ..) => unknown(stack, &["divide_split_chunk_or_each()"]),
+        (b"%", Ty::Int, Ty::Int) => result(stack, Ty::Int, "a % b"),
+        (b"%", a, Ty::Blk) if a.is_seq() => {
+            stack.pop();
+            lines(&["block = pop()", "push(map(block, pop()))"])
+        }
+        (b"%", a, b) if a == b && a.is_seq() => {
+            result(stack, Ty::Arr, "[s for s in split(a, b) if s]")
+        }
+        (b"%", a, Ty::Int) if a.is_seq() => result(stack, a, "a[::b]"),
+        (b"%", ..) => unknown(stack, &["mod_map_split_or_step()"]),
+        (b"|", ..) => result(stack, Ty::Any, "union(a, b)"),
+        (b"&", ..) => result(stack, Ty::Any, "intersection(a, b)"),
+        (b"^", ..) => result(stack, Ty::Any, "symmetric_difference(a, b)"),
+        (b"<" | b">" | b"=", a, b) if a == b && a != Ty::Any && a != Ty::Blk => {
+            let op = if name == b"=" {
+                "=="
+            } else {
+                std::str::from_utf8(name).unwrap()
+            };
+            result(stack, Ty::Int, &format!("int(a {} b)", op))
+        }
+        (b"<", a, Ty::Int) if a.is_seq() => result(stack, a, "a[:b]"),
+        (b">", a, Ty::Int) if a.is_seq() => result(stack, a, "a[b:]"),
+        (b"=", a, Ty::Int) if a.is_seq() => result(stack, Ty::Any, "a[b]"),
+        (b"<" | b">" | b"=", ..) => result(stack, Ty::Any, "compare_or_index(a, b)"),
+        (b"?", Ty::Int, Ty::Int) => result(stack, Ty::Int, "a ** b"),
+        (b"?", a, Ty::Blk) if a.is_seq() => unknown(
+            stack,
+            &[
+                "block = pop()",
+                "push(next((x for x in pop() if truthy(block(x))), None))",
+            ],
+        ),
+        (b"?", a, _) if a.is_seq() => result(stack, Ty::Int, "index_of(a, b)"),
+        (b"?", ..) => unknown(stack, &["power_index_or_find()"]),
+        _ => None,
+    }
+}
+
+/// The pseudocode for a word builtin, or one defined in GolfScript.
+fn word(name: &[u8], stack: &mut Stack) -> Option<Vec<String>> {
+    let lines = |ls: &[&str]| Some(ls.iter().map(|l| l.to_string()).collect());
+    let mut pushes = |pops: usize, ty: Option<Ty>| {
+        for _ in 0..pops {
+            stack.pop();
+        }
+        match ty {
+            Some(ty) => stack.push(ty),
+            None => stack.forget(),
+        }
+    };
+    match name {
+        b"print" => {
+            pushes(1, None);
+            lines(&["print(pop())"])
+        }
+        b"puts" => {
+            pushes(1, None);
+            lines(&["print(pop()); print(n)"])
+        }
+        b"p" => {
+            pushes(1, None);
+            lines(&["print(inspect(pop())); print(n)"])
+        }
+        b"n" => {
+            pushes(0, Some(Ty::Str));
+            lines(&["push(n)  # a newline"])
+        }
+        b"rand" => {
+            pushes(1, Some(Ty::Int));
+            lines(&["push(random_below(pop()))"])
+        }
+        b"abs" => {
+            pushes(1, Some(Ty::Int));
+            lines(&["push(abs(pop()))"])
+        }
+        b"zip" => {
+            pushes(1, Some(Ty::Arr));
+            lines(&["push(transpose(pop()))"])
+        }
+        b"base" => {
+            pushes(2, Some(Ty::Any));
+            lines(&["b = pop(); a = pop()", "push(to_or_from_base(a, b))"])
+        }
+        b"and" => {
+            pushes(2, Some(Ty::Any));
+            lines(&["b = pop(); a = pop()", "push(b if truthy(a) else a)"])
+        }
+        b"or" => {
+            pushes(2, Some(Ty::Any));
+            lines(&["b = pop(); a = pop()", "push(a if truthy(a) else b)"])
+        }
+        b"xor" => {
+            pushes(2, Some(Ty::Int));
+            lines(&["b = pop(); a = pop()", "push(int(truthy(a) != truthy(b)))"])
+        }
+        b"if" => {
+            pushes(3, None);
+            lines(&[
+                "c = pop(); b = pop(); a = pop()",
+                "use(b if truthy(a) else c)",
+            ])
+        }
+        b"do" => {
+            pushes(1, None);
+            lines(&[
+                "body = pop()",
+                "while True:",
+                "    body()",
+                "    if not truthy(pop()): break",
+            ])
+        }
+        b"while" | b"until" => {
+            pushes(2, None);
+            let test = match name {
+                b"while" => "    if not truthy(pop()): break",
+                _ => "    if truthy(pop()): break",
+            };
+            lines(&[
+                "body = pop(); condition = pop()",
+                "while True:",
+                "    condition()",
+                test,
+                "    body()",
+            ])
+        }
+        _ => None,
+    }
+}
+
+impl<'a> Translator<'a> {
+    fn name_blocks(&mut self, tokens: &[Gtoken<'a>]) {
+        for token in tokens {
+            match token {
+                Gtoken::Block(inner, src) => {
+                    let name = format!("block_{}", self.names.len() + 1);
+                    self.names.insert(offset(self.program, src), name);
+                    self.name_blocks(inner);
+                }
+                Gtoken::Assignment(name) => {
+                    self.assigned.insert(name);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The pseudocode for `tokens`, each line followed by the token it's
+    /// for, with functions for their blocks added to `functions`.
+    fn body(&mut self, tokens: &[Gtoken<'a>]) -> Vec<String> {
+        let mut stack = Stack::default();
+        let mut out = vec![];
+        for token in tokens {
+            let lexeme = token.lexeme();
+            let (source, lines) = match token {
+                Gtoken::Comment(text) => {
+                    out.push(String::from_utf8_lossy(text).into_owned());
+                    continue;
+                }
+                Gtoken::Symbol(s) if s.iter().all(u8::is_ascii_whitespace) => continue,
+                Gtoken::Block(inner, src) => {
+                    let name = self.names[&offset(self.program, src)].clone();
+                    self.function(&name, inner, src);
+                    stack.push(Ty::Blk);
+                    (
+                        format!("{{{}}}", String::from_utf8_lossy(src)),
+                        vec![format!("push({})", name)],
+                    )
+                }
+                Gtoken::IntLiteral(n) => {
+                    stack.push(Ty::Int);
+                    (
+                        String::new(),
+                        vec![format!("push({})", String::from_utf8_lossy(n))],
+                    )
+                }
+                Gtoken::SingleQuotedString(s) | Gtoken::DoubleQuotedString(s) => {
+                    stack.push(Ty::Str);
+                    let single = matches!(token, Gtoken::SingleQuotedString(_));
+                    let value = py_string(&unescape(s, single));
+                    (String::new(), vec![format!("push({})", value)])
+                }
+                Gtoken::Assignment(name) => {
+                    let line = format!("{} = top()", py_name(name));
+                    (format!(":{}", String::from_utf8_lossy(name)), vec![line])
+                }
+                Gtoken::Symbol(name) if self.assigned.contains(name) => {
+                    stack.forget();
+                    (String::new(), vec![format!("use({})", py_name(name))])
+                }
+                Gtoken::Symbol(name) => {
+                    let lines = operator(name, &mut stack)
+                        .or_else(|| word(name, &mut stack))
+                        .unwrap_or_else(|| {
+                            let name = String::from_utf8_lossy(name);
+                            match lookup(name.as_bytes()) {
+                                Some(_) => {
+                                    stack.forget();
+                                    vec![format!("{}()  # an extension", name)]
+                                }
+                                None => vec!["pass  # not defined, so it does nothing".to_string()],
+                            }
+                        });
+                    (String::new(), lines)
+                }
+            };
+            let source = match source.is_empty() {
+                true => String::from_utf8_lossy(lexeme).into_owned(),
+                false => source,
+            };
+            let source = source.replace('\n', " ");
+            // The first line says which token it's for, with any note.
+            let (first, note) = match lines[0].split_once("  # ") {
+                Some((code, note)) => (code.to_string(), format!("  ({})", note)),
+                None => (lines[0].clone(), String::new()),
+            };
+            out.push(format!("{:<38}  # {}{}", first, source, note));
+            out.extend(lines.into_iter().skip(1));
+        }
+        out
+    }
+
+    fn function(&mut self, name: &str, tokens: &[Gtoken<'a>], src: &[u8]) {
+        let loc = Location::new(self.program, offset(self.program, src) - 1);
+        let mut lines = vec![format!(
+            "def {}():  # line {}, col {}",
+            name, loc.line, loc.col
+        )];
+        let body = self.body(tokens);
+        if body.iter().all(|l| l.starts_with('#')) {
+            lines.push("    pass".to_string());
+        }
+        lines.extend(body.into_iter().map(|l| format!("    {}", l)));
+        self.functions.push(lines);
+    }
+}
+
+/// `program`, parsed into `tokens` and named `name`, as pseudocode.
+pub fn python(program: &[u8], tokens: &[Gtoken], name: &str) -> String {
+    let mut translator = Translator {
+        program,
+        assigned: HashSet::new(),
+        names: HashMap::new(),
+        functions: vec![],
+    };
+    translator.name_blocks(tokens);
+    let body = translator.body(tokens);
+    let mut lines = vec![
+        format!("# {} as Python-like pseudocode, from golfscript-rs.", name),
+        "# It's for reading, not running. push() and pop() work on the stack,".to_string(),
+        "# top() looks at its top, use(x) pushes x or runs it if it's a block,".to_string(),
+        "# and truthy(x) is false for 0, \"\", [] and {}.".to_string(),
+    ];
+    for function in &translator.functions {
+        lines.push(String::new());
+        lines.extend(function.iter().cloned());
+    }
+    lines.push(String::new());
+    lines.push("# The program; the stack starts with the input.".to_string());
+    lines.extend(body);
+    lines.push("print(stack)  # what's left is printed".to_string());
+    lines.join("\n") + "\n"
+}
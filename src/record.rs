@@ -0,0 +1,165 @@
+//! Recording a run and replaying it elsewhere. A record holds the program,
+//! its input and the flags that change its meaning, plus a journal of what
+//! it did: the tokens it ran, its random draws and the lines `gets` read.
+//! Replaying feeds the same draws and lines back in. The clock, environment
+//! and files aren't recorded.
+
+use crate::ext::encoding::{base64_decode, base64_encode};
+use crate::value::Gval;
+use flate2::read::{ZlibDecoder, ZlibEncoder};
+use flate2::Compression;
+use num::BigInt;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, Read};
+
+const VERSION: u64 = 1;
+
+/// What a run did that can differ between runs of the same program.
+#[derive(Default)]
+pub struct Journal {
+    /// Program offsets of the tokens run, in order, or `None` for tokens in
+    /// code that isn't found in the program.
+    pub tokens: Vec<Option<usize>>,
+    pub draws: Vec<BigInt>,
+    pub reads: Vec<Vec<u8>>,
+    /// Where code that has run starts in the program, so each block is
+    /// only searched for once.
+    pub(crate) bases: HashMap<Vec<u8>, Option<usize>>,
+}
+
+impl Journal {
+    /// The index of the first token where `self` and `other` differ.
+    pub fn divergence(&self, other: &Journal) -> Option<usize> {
+        let (a, b) = (&self.tokens, &other.tokens);
+        (0..a.len().max(b.len())).find(|&i| a.get(i) != b.get(i))
+    }
+}
+
+pub struct Record {
+    pub code: Vec<u8>,
+    pub input: Gval,
+    /// Command-line flags to replay with, like `--extensions`.
+    pub flags: Vec<String>,
+    pub journal: Journal,
+}
+
+pub(crate) fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+pub(crate) fn bytes_to_json(bytes: &[u8]) -> Value {
+    Value::String(String::from_utf8(base64_encode(bytes)).expect("base64 is ASCII"))
+}
+
+pub(crate) fn bytes_from_json(v: &Value) -> io::Result<Vec<u8>> {
+    let s = v
+        .as_str()
+        .ok_or_else(|| invalid("expected a base64 string"))?;
+    base64_decode(s.as_bytes()).map_err(|e| invalid(&e.to_string()))
+}
+
+/// A value as JSON, with strings and blocks in base64 since they're bytes.
+pub fn value_to_json(v: &Gval) -> Value {
+    match v {
+        Gval::Int(n) => json!({ "int": n.to_string() }),
+        Gval::Arr(vs) => json!({ "arr": vs.iter().map(value_to_json).collect::<Vec<_>>() }),
+        Gval::Str(s) => json!({ "str": bytes_to_json(s) }),
+        Gval::Blk(s) => json!({ "blk": bytes_to_json(s) }),
+    }
+}
+
+pub fn value_from_json(v: &Value) -> io::Result<Gval> {
+    if let Some(n) = v.get("int") {
+        let n = n.as_str().and_then(|n| n.parse().ok());
+        return n.map(Gval::Int).ok_or_else(|| invalid("bad integer"));
+    }
+    if let Some(vs) = v.get("arr").and_then(Value::as_array) {
+        return vs
+            .iter()
+            .map(value_from_json)
+            .collect::<io::Result<_>>()
+            .map(Gval::Arr);
+    }
+    if let Some(s) = v.get("str") {
+        return bytes_from_json(s).map(Gval::Str);
+    }
+    if let Some(s) = v.get("blk") {
+        return bytes_from_json(s).map(Gval::Blk);
+    }
+    Err(invalid("expected a value"))
+}
+
+/// Write `v` to `path` as zlib-compressed JSON.
+pub(crate) fn write_compressed(path: &str, v: &Value) -> io::Result<()> {
+    let json = v.to_string();
+    let mut compressed = vec![];
+    ZlibEncoder::new(json.as_bytes(), Compression::best()).read_to_end(&mut compressed)?;
+    std::fs::write(path, compressed)
+}
+
+pub(crate) fn read_compressed(path: &str) -> io::Result<Value> {
+    let compressed = std::fs::read(path)?;
+    let mut json = vec![];
+    ZlibDecoder::new(&compressed[..]).read_to_end(&mut json)?;
+    serde_json::from_slice(&json).map_err(|e| invalid(&e.to_string()))
+}
+
+impl Record {
+    /// Write the record as zlib-compressed JSON.
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let j = &self.journal;
+        let record = json!({
+            "version": VERSION,
+            "code": bytes_to_json(&self.code),
+            "input": value_to_json(&self.input),
+            "flags": self.flags,
+            "tokens": j.tokens,
+            "draws": j.draws.iter().map(|n| n.to_string()).collect::<Vec<_>>(),
+            "reads": j.reads.iter().map(|r| bytes_to_json(r)).collect::<Vec<_>>(),
+        });
+        write_compressed(path, &record)
+    }
+
+    pub fn load(path: &str) -> io::Result<Record> {
+        let v = read_compressed(path)?;
+        if v["version"].as_u64() != Some(VERSION) {
+            return Err(invalid("unsupported record version"));
+        }
+        let list = |key: &str| {
+            v[key]
+                .as_array()
+                .cloned()
+                .ok_or_else(|| invalid(&format!("missing `{}`", key)))
+        };
+        let flags = list("flags")?
+            .iter()
+            .map(|f| f.as_str().map(str::to_string))
+            .collect::<Option<_>>()
+            .ok_or_else(|| invalid("bad flags"))?;
+        let tokens = list("tokens")?
+            .iter()
+            .map(|t| t.as_u64().map(|t| t as usize))
+            .collect();
+        let draws = list("draws")?
+            .iter()
+            .map(|d| d.as_str().and_then(|d| d.parse().ok()))
+            .collect::<Option<_>>()
+            .ok_or_else(|| invalid("bad draws"))?;
+        let reads = list("reads")?
+            .iter()
+            .map(bytes_from_json)
+            .collect::<io::Result<_>>()?;
+        Ok(Record {
+            code: bytes_from_json(&v["code"])?,
+            input: value_from_json(&v["input"])?,
+            flags,
+            journal: Journal {
+                tokens,
+                draws,
+                reads,
+                ..Journal::default()
+            },
+        })
+    }
+}
@@ -0,0 +1,209 @@
+//! Differential testing against the reference interpreter, `golfscript.rb`:
+//! running a program and its input through both and comparing what they
+//! print. Where outputs differ for a reason that's known and deliberate, the
+//! difference is listed in [`KNOWN`], so a report can tell new bugs from old
+//! differences.
+
+use crate::expect::check;
+use crate::util::Buffer;
+use crate::value::Gval;
+use crate::Gs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// How long either interpreter may take on a program by default.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A difference between this interpreter and the reference that's known
+/// and deliberate.
+pub struct Divergence {
+    /// A stable name, like `mixed-comparison`.
+    pub name: &'static str,
+    pub note: &'static str,
+    /// Whether a program could show the difference, given whether
+    /// `--compat` is on.
+    suspect: fn(&[u8], bool) -> bool,
+}
+
+fn uses(program: &[u8], names: &[&[u8]]) -> bool {
+    crate::parse::significant_tokens(program, false)
+        .unwrap_or_default()
+        .iter()
+        .any(|t| names.contains(&t.as_slice()))
+}
+
+pub const KNOWN: &[Divergence] = &[
+    Divergence {
+        name: "mixed-comparison",
+        note: "`<`, `=` and `>` order values of different types by type, where the \
+               reference coerces them first; `--compat` does as the reference does",
+        suspect: |program, compat| !compat && uses(program, &[b"<", b"=", b">"]),
+    },
+    Divergence {
+        name: "random",
+        note: "`rand` draws from a different generator and seed, so its numbers differ",
+        suspect: |program, _| uses(program, &[b"rand"]),
+    },
+];
+
+/// The known divergences that could explain a difference in `program`'s
+/// output.
+pub fn suspects(program: &[u8], compat: bool) -> Vec<&'static Divergence> {
+    KNOWN
+        .iter()
+        .filter(|d| (d.suspect)(program, compat))
+        .collect()
+}
+
+/// What a run printed, and whether it failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub output: Vec<u8>,
+    pub failed: bool,
+}
+
+/// How to run `golfscript.rb`.
+pub struct Reference {
+    ruby: String,
+    script: PathBuf,
+    timeout: Duration,
+}
+
+impl Reference {
+    /// The script at `script`, run by the `ruby` on the path, or the one the
+    /// `RUBY` environment variable names.
+    pub fn new(script: impl Into<PathBuf>) -> Reference {
+        Reference {
+            ruby: std::env::var("RUBY").unwrap_or_else(|_| "ruby".to_string()),
+            script: script.into(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// The script the `GOLFSCRIPT_RB` environment variable names, if any.
+    pub fn from_env() -> Option<Reference> {
+        std::env::var_os("GOLFSCRIPT_RB").map(Reference::new)
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Reference {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run `program` on `input`, which the reference reads from stdin.
+    pub fn run(&self, program: &[u8], input: &[u8]) -> Result<Outcome, String> {
+        static RUNS: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "golfscript-rs-{}-{}.gs",
+            std::process::id(),
+            RUNS.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, program).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let result = self.run_file(&path, input);
+        let _ = std::fs::remove_file(&path);
+        result
+    }
+
+    fn run_file(&self, path: &PathBuf, input: &[u8]) -> Result<Outcome, String> {
+        let mut child = Command::new(&self.ruby)
+            .arg(&self.script)
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("can't run {}: {}", self.ruby, e))?;
+        // Feed and drain the pipes on their own threads, so neither fills up
+        // while the other waits.
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = input.to_vec();
+        let writer = std::thread::spawn(move || stdin.write_all(&input));
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let reader = std::thread::spawn(move || {
+            let mut output = vec![];
+            stdout.read_to_end(&mut output).map(|_| output)
+        });
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            match child.try_wait().map_err(|e| e.to_string())? {
+                Some(status) => break status,
+                None if Instant::now() > deadline => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("timed out after {:?}", self.timeout));
+                }
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        };
+        // A program that doesn't read all its input closes the pipe early.
+        let _ = writer.join();
+        let output = reader
+            .join()
+            .map_err(|_| "couldn't read the output".to_string())?
+            .map_err(|e| e.to_string())?;
+        Ok(Outcome {
+            output,
+            failed: !status.success(),
+        })
+    }
+}
+
+/// Run `program` on `input` here, as `golfscript-rs` would with its input
+/// from stdin, stopping after `timeout`.
+pub fn run_ours(program: &[u8], input: &[u8], compat: bool, timeout: Duration) -> Outcome {
+    let buffer = Buffer::default();
+    let mut gs = Gs::builder()
+        .compat(compat)
+        .timeout(timeout)
+        .input(Box::new(std::io::empty()))
+        .output(Box::new(buffer.clone()))
+        .build();
+    gs.stack.push(Gval::Str(input.to_vec()));
+    let result = gs
+        .run(program)
+        .and_then(|()| {
+            gs.stack = vec![Gval::Arr(std::mem::take(&mut gs.stack))];
+            gs.run(b"puts")
+        })
+        .and_then(|()| gs.flush());
+    let output = buffer.0.take();
+    Outcome {
+        output,
+        failed: result.is_err(),
+    }
+}
+
+/// How `ours` differs from the reference's outcome, as a line diff of the
+/// output where `-` lines are the reference's and `+` lines ours, or `None`
+/// if they're the same.
+pub fn compare(reference: &Outcome, ours: &Outcome) -> Option<String> {
+    let output = &reference.output;
+    let lines: Vec<Vec<u8>> = match output.strip_suffix(b"\n").unwrap_or(output) {
+        _ if output.is_empty() => vec![],
+        output => output.split(|&b| b == b'\n').map(<[u8]>::to_vec).collect(),
+    };
+    let mut report = vec![];
+    // `check` doesn't mind a missing final newline, so that's compared
+    // apart.
+    let exact = reference.output == ours.output;
+    match check(&lines, &ours.output) {
+        Err(diff) => report.push(diff),
+        Ok(()) if !exact => report.push("the final newline differs".to_string()),
+        Ok(()) => {}
+    }
+    let failed = |f: bool| if f { "fails" } else { "succeeds" };
+    if reference.failed != ours.failed {
+        report.push(format!(
+            "the reference {}, this interpreter {}",
+            failed(reference.failed),
+            failed(ours.failed)
+        ));
+    }
+    match report.is_empty() {
+        true => None,
+        false => Some(report.join("\n")),
+    }
+}
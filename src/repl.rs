@@ -0,0 +1,315 @@
+//! An interactive session. Each line runs on the same stack and variables,
+//! and the stack is shown after it. A line that fails is undone: the stack
+//! and variables go back to how they were before it, and the session goes
+//! on. Long values are shown cut short to their ends, so a stray `10 7?,`
+//! doesn't flood the terminal; `#show n`, a comment as code, shows the nth
+//! value from the top in full. A line ending in an unclosed `{` or string
+//! goes on to the next. `#save FILE` writes the lines that ran to a
+//! file, to turn a session into a script, and `#replay FILE` runs a file's
+//! lines in a fresh session. With the `editor` feature, lines typed at a
+//! terminal can be edited, and are highlighted as they're typed.
+
+use crate::parse::{completeness, Completeness};
+use crate::pretty::{elides, show, Style};
+use crate::trace::render;
+use crate::util::Buffer;
+use crate::value::Gval;
+use crate::Gs;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+#[cfg(feature = "editor")]
+mod editor;
+#[cfg(feature = "editor")]
+pub use editor::edit;
+
+/// Run lines from `input` on `gs` until it ends, writing prompts and the
+/// stack to `output` and errors to stderr.
+pub fn run(gs: &mut Gs, mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    let mut session = Session::new(gs);
+    loop {
+        write!(output, "> ")?;
+        output.flush()?;
+        let mut line = vec![];
+        if input.read_until(b'\n', &mut line)? == 0 {
+            session.end(gs);
+            return writeln!(output);
+        }
+        // An unclosed `{` or string goes on to the next line.
+        while completeness(&line) == Completeness::Incomplete {
+            write!(output, ".. ")?;
+            output.flush()?;
+            if input.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+        }
+        session.line(gs, &line, &mut output)?;
+    }
+}
+
+#[cfg(not(feature = "editor"))]
+pub fn edit(_gs: &mut Gs, _output: impl Write) -> io::Result<()> {
+    Err(io::Error::other(
+        "golfscript-rs was built without the `editor` feature",
+    ))
+}
+
+/// A line run in a session, for `#save`: what was typed, what it printed,
+/// and the stack shown after it.
+struct Entry {
+    line: Vec<u8>,
+    printed: Vec<u8>,
+    shown: Vec<u8>,
+}
+
+/// Writes where a program's output went before the session, keeping a copy
+/// for the transcript.
+struct Copying {
+    out: Rc<RefCell<Box<dyn Write>>>,
+    copy: Buffer,
+}
+
+impl Write for Copying {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.out.borrow_mut().write(buf)?;
+        self.copy.write_all(&buf[..n])?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.borrow_mut().flush()
+    }
+}
+
+/// How a session shows values, whether it has said how to see those cut
+/// short, and the lines it has run since it started from `start`.
+struct Session {
+    style: Style,
+    noted: bool,
+    start: (Vec<Gval>, HashMap<Vec<u8>, Gval>, Vec<usize>),
+    transcript: Vec<Entry>,
+    /// Where output went before the session, and what's been printed since
+    /// the line running started.
+    out: Rc<RefCell<Box<dyn Write>>>,
+    printed: Buffer,
+}
+
+impl Session {
+    fn new(gs: &mut Gs) -> Session {
+        let style = Style {
+            broken: gs.pretty,
+            colored: gs.pretty && gs.color,
+            elided: true,
+        };
+        let out = Rc::new(RefCell::new(gs.replace_output(Box::new(io::sink()))));
+        let printed = Buffer::default();
+        gs.replace_output(Box::new(Copying {
+            out: out.clone(),
+            copy: printed.clone(),
+        }));
+        Session {
+            style,
+            noted: false,
+            start: (gs.stack.clone(), gs.vars.clone(), gs.lb.clone()),
+            transcript: vec![],
+            out,
+            printed,
+        }
+    }
+
+    /// Give `gs` back the output it had before the session.
+    fn end(self, gs: &mut Gs) {
+        let _ = gs.flush();
+        let out = std::mem::replace(&mut *self.out.borrow_mut(), Box::new(io::sink()));
+        gs.replace_output(out);
+    }
+
+    /// Run `line` on `gs`, undoing it if it fails, and show the stack after;
+    /// or if it's a command like `#show`, do that.
+    fn line(&mut self, gs: &mut Gs, line: &[u8], output: &mut impl Write) -> io::Result<()> {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let command = line.trim_ascii();
+        if let Some(arg) = command_arg(command, b"#show") {
+            let full = Style {
+                elided: false,
+                ..self.style
+            };
+            return show_value(gs, arg, full, output);
+        }
+        if let Some(arg) = command_arg(command, b"#save") {
+            return self.save(arg, output);
+        }
+        if let Some(arg) = command_arg(command, b"#replay") {
+            return self.replay(gs, arg, output);
+        }
+        self.printed.0.borrow_mut().clear();
+        let snapshot = (gs.stack.clone(), gs.vars.clone(), gs.lb.clone());
+        let result = gs.run(line).and_then(|()| gs.flush());
+        let failed = result.is_err();
+        if let Err(e) = result {
+            let _ = gs.flush();
+            eprintln!("error: {}", e);
+            if !gs.trace.is_empty() {
+                eprintln!("{}", render(line, &gs.trace));
+            }
+            eprintln!("(the line was undone)");
+            gs.trace.clear();
+            (gs.stack, gs.vars, gs.lb) = snapshot;
+        }
+        let stack = Gval::Arr(gs.stack.clone());
+        output.write_all(&show(&stack, self.style))?;
+        writeln!(output)?;
+        if !self.noted && elides(&stack) {
+            writeln!(
+                output,
+                "(values are cut short; `#show n` shows the nth from the top in full)"
+            )?;
+            self.noted = true;
+        }
+        if !failed && !command.is_empty() {
+            let plain = Style {
+                broken: false,
+                colored: false,
+                elided: true,
+            };
+            self.transcript.push(Entry {
+                line: line.to_vec(),
+                printed: self.printed.0.take(),
+                shown: show(&stack, plain),
+            });
+        }
+        Ok(())
+    }
+
+    /// `#save FILE`: write the lines run so far to a file, which runs as a
+    /// program to the same end. `#save -o FILE` also writes what each
+    /// printed, as `#=>` comments, and the stack after it.
+    fn save(&self, arg: &[u8], output: &mut impl Write) -> io::Result<()> {
+        let arg = String::from_utf8_lossy(arg.trim_ascii());
+        let (outputs, path) = match arg.strip_prefix("-o ") {
+            Some(path) => (true, path.trim()),
+            None => (false, arg.as_ref()),
+        };
+        if path.is_empty() {
+            eprintln!("#save FILE saves the lines run so far; #save -o FILE also their output");
+            return Ok(());
+        }
+        let mut text = vec![];
+        for entry in &self.transcript {
+            text.extend(&entry.line);
+            text.push(b'\n');
+            if !outputs {
+                continue;
+            }
+            if !entry.printed.is_empty() {
+                let printed = entry.printed.strip_suffix(b"\n").unwrap_or(&entry.printed);
+                for line in printed.split(|&b| b == b'\n') {
+                    text.extend(b"#=> ");
+                    text.extend(line);
+                    text.push(b'\n');
+                }
+            }
+            text.extend(b"# stack: ");
+            text.extend(&entry.shown);
+            text.push(b'\n');
+        }
+        let n = self.transcript.len();
+        let plural = if n == 1 { "" } else { "s" };
+        match std::fs::write(path, text) {
+            Ok(()) => writeln!(output, "(saved {} line{} to {})", n, plural, path),
+            Err(e) => {
+                eprintln!("#save {}: {}", path, e);
+                Ok(())
+            }
+        }
+    }
+
+    /// `#replay FILE`: go back to how the session started, and run the
+    /// lines of a file as if they were typed, skipping comments like those
+    /// `#save -o` writes.
+    fn replay(&mut self, gs: &mut Gs, arg: &[u8], output: &mut impl Write) -> io::Result<()> {
+        let path = String::from_utf8_lossy(arg.trim_ascii()).into_owned();
+        if path.is_empty() {
+            eprintln!("#replay FILE runs the lines of a file in a fresh session");
+            return Ok(());
+        }
+        let text = match std::fs::read(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("#replay {}: {}", path, e);
+                return Ok(());
+            }
+        };
+        (gs.stack, gs.vars, gs.lb) = self.start.clone();
+        self.transcript.clear();
+        let mut pending = vec![];
+        for line in text.split(|&b| b == b'\n') {
+            let command = line.trim_ascii();
+            if pending.is_empty() && (command.is_empty() || command.starts_with(b"#")) {
+                continue;
+            }
+            pending.extend_from_slice(line);
+            if completeness(&pending) == Completeness::Incomplete {
+                pending.push(b'\n');
+                continue;
+            }
+            self.echo(gs, &std::mem::take(&mut pending), output)?;
+        }
+        if !pending.is_empty() {
+            self.echo(gs, &pending, output)?;
+        }
+        Ok(())
+    }
+
+    /// Show `line` as if typed at the prompt, and run it.
+    fn echo(&mut self, gs: &mut Gs, line: &[u8], output: &mut impl Write) -> io::Result<()> {
+        write!(output, "> ")?;
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        for (i, part) in line.split(|&b| b == b'\n').enumerate() {
+            if i > 0 {
+                write!(output, "\n.. ")?;
+            }
+            output.write_all(part)?;
+        }
+        writeln!(output)?;
+        self.line(gs, line, output)
+    }
+}
+
+/// What follows the command `name` at the start of `line`, if `line` is
+/// that command: `#saved` is a comment, not `#save d`.
+fn command_arg<'a>(line: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    let arg = line.strip_prefix(name)?;
+    match arg.first() {
+        None => Some(arg),
+        Some(b) if b.is_ascii_whitespace() => Some(arg),
+        Some(_) => None,
+    }
+}
+
+/// `#show n`: write the nth value from the top of the stack in `style`.
+fn show_value(gs: &Gs, arg: &[u8], style: Style, output: &mut impl Write) -> io::Result<()> {
+    let n = match String::from_utf8_lossy(arg.trim_ascii()).as_ref() {
+        "" => 0,
+        arg => match arg.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => {
+                eprintln!("#show n shows the nth value from the top, counting from 0");
+                return Ok(());
+            }
+        },
+    };
+    match gs.stack.iter().rev().nth(n) {
+        Some(v) => {
+            output.write_all(&show(v, style))?;
+            writeln!(output)
+        }
+        None => {
+            eprintln!("#show: the stack has {} values", gs.stack.len());
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Line editing for the REPL, with the line highlighted as it's typed, and
+//! going on over more lines while a `{` or string is open.
+
+use super::Session;
+use crate::highlight::ansi_unfinished;
+use crate::parse::{completeness, Completeness};
+use crate::Gs;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Editor, Helper};
+use std::borrow::Cow;
+use std::io::{self, Write};
+
+/// Run lines typed at the terminal on `gs` until input ends, as `run`
+/// does, but with line editing and history, and each line highlighted as
+/// it's typed.
+pub fn edit(gs: &mut Gs, mut output: impl Write) -> io::Result<()> {
+    let mut editor: Editor<Colors, DefaultHistory> = Editor::new().map_err(io::Error::other)?;
+    editor.set_helper(Some(Colors));
+    let mut session = Session::new(gs);
+    loop {
+        match editor.readline("> ") {
+            Ok(line) => {
+                editor.add_history_entry(&line).map_err(io::Error::other)?;
+                session.line(gs, line.as_bytes(), &mut output)?;
+            }
+            // Ctrl-C drops the line being typed.
+            Err(ReadlineError::Interrupted) => {}
+            Err(ReadlineError::Eof) => {
+                session.end(gs);
+                return Ok(());
+            }
+            Err(e) => return Err(io::Error::other(e)),
+        }
+    }
+}
+
+/// Colors the line being typed, as [`ansi_unfinished`] does.
+struct Colors;
+
+impl Highlighter for Colors {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        Cow::Owned(ansi_unfinished(line.as_bytes()))
+    }
+
+    /// Every character typed can change the colors, like a quote.
+    fn highlight_char(&self, _line: &str, _pos: usize, _forced: bool) -> bool {
+        true
+    }
+}
+
+impl Completer for Colors {
+    type Candidate = String;
+}
+
+impl Hinter for Colors {
+    type Hint = String;
+}
+
+impl Validator for Colors {
+    /// An unclosed `{` or string goes on to another line.
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        Ok(match completeness(ctx.input().as_bytes()) {
+            Completeness::Incomplete => ValidationResult::Incomplete,
+            _ => ValidationResult::Valid(None),
+        })
+    }
+}
+
+impl Helper for Colors {}
@@ -0,0 +1,183 @@
+use num::bigint::Sign;
+use num::BigInt;
+use num::Integer;
+use num::Signed;
+use num::Zero;
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const N: usize = 624;
+const M: usize = 397;
+
+/// MT19937, the generator behind Ruby's `Kernel#rand`.
+#[derive(Clone)]
+pub struct Mt19937 {
+    mt: [u32; N],
+    index: usize,
+}
+
+impl Mt19937 {
+    fn init_genrand(seed: u32) -> Mt19937 {
+        let mut mt = [0u32; N];
+        mt[0] = seed;
+        for i in 1..N {
+            mt[i] = 1812433253u32
+                .wrapping_mul(mt[i - 1] ^ (mt[i - 1] >> 30))
+                .wrapping_add(i as u32);
+        }
+        Mt19937 { mt, index: N }
+    }
+
+    fn init_by_array(key: &[u32]) -> Mt19937 {
+        let mut r = Mt19937::init_genrand(19650218);
+        let mt = &mut r.mt;
+        let (mut i, mut j) = (1, 0);
+        for _ in 0..N.max(key.len()) {
+            mt[i] = (mt[i] ^ (mt[i - 1] ^ (mt[i - 1] >> 30)).wrapping_mul(1664525))
+                .wrapping_add(key[j])
+                .wrapping_add(j as u32);
+            i += 1;
+            j += 1;
+            if i >= N {
+                mt[0] = mt[N - 1];
+                i = 1;
+            }
+            if j >= key.len() {
+                j = 0;
+            }
+        }
+        for _ in 0..N - 1 {
+            mt[i] = (mt[i] ^ (mt[i - 1] ^ (mt[i - 1] >> 30)).wrapping_mul(1566083941))
+                .wrapping_sub(i as u32);
+            i += 1;
+            if i >= N {
+                mt[0] = mt[N - 1];
+                i = 1;
+            }
+        }
+        mt[0] = 0x80000000;
+        r
+    }
+
+    /// Seed the way Ruby's `srand(seed)` does.
+    pub fn new(seed: &BigInt) -> Mt19937 {
+        let (_, mut key) = seed.abs().to_u32_digits();
+        if key.is_empty() {
+            key.push(0);
+        }
+        if key.len() == 1 {
+            Mt19937::init_genrand(key[0])
+        } else {
+            Mt19937::init_by_array(&key)
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        if self.index >= N {
+            for k in 0..N {
+                let y = (self.mt[k] & 0x80000000) | (self.mt[(k + 1) % N] & 0x7fffffff);
+                let mag = if y & 1 == 1 { 0x9908b0df } else { 0 };
+                self.mt[k] = self.mt[(k + M) % N] ^ (y >> 1) ^ mag;
+            }
+            self.index = 0;
+        }
+        let mut y = self.mt[self.index];
+        self.index += 1;
+        y ^= y >> 11;
+        y ^= (y << 7) & 0x9d2c5680;
+        y ^= (y << 15) & 0xefc60000;
+        y ^= y >> 18;
+        y
+    }
+}
+
+/// A fresh seed from the OS-keyed hasher and the clock, for runs without `--seed`.
+pub fn entropy_seed() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(t) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(t.as_nanos());
+    }
+    hasher.write_u32(std::process::id());
+    hasher.finish()
+}
+
+fn make_mask(x: u32) -> u32 {
+    if x == 0 {
+        0
+    } else {
+        u32::MAX >> x.leading_zeros()
+    }
+}
+
+#[derive(Clone)]
+pub enum Rng {
+    Lcg(u64),
+    Mt(Box<Mt19937>),
+    /// Recorded draws, handed out in order; see [`crate::record`].
+    Replay(VecDeque<BigInt>),
+}
+
+impl Rng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            Rng::Lcg(state) => {
+                let (m, _) = state.overflowing_mul(1664525);
+                let (m, _) = m.overflowing_add(1013904223);
+                *state = m;
+                // The high bits of an LCG are far better distributed than the low ones.
+                (m >> 32) as u32
+            }
+            Rng::Mt(mt) => mt.next_u32(),
+            Rng::Replay(_) => unreachable!("replayed draws aren't generated"),
+        }
+    }
+
+    /// A uniform number in `0..=limit`, by rejection sampling one 32-bit word at a
+    /// time from the top. For MT this is exactly Ruby's `limited_big_rand`.
+    fn limited(&mut self, limit: &BigInt) -> BigInt {
+        let (_, lim) = limit.to_u32_digits();
+        let mut digits = vec![0u32; lim.len()];
+        'retry: loop {
+            let mut mask = 0u32;
+            let mut boundary = true;
+            for i in (0..lim.len()).rev() {
+                let mut r = 0;
+                mask = if mask != 0 {
+                    u32::MAX
+                } else {
+                    make_mask(lim[i])
+                };
+                if mask != 0 {
+                    r = self.next_u32() & mask;
+                    if boundary {
+                        if lim[i] < r {
+                            continue 'retry;
+                        }
+                        if r < lim[i] {
+                            boundary = false;
+                        }
+                    }
+                }
+                digits[i] = r;
+            }
+            return BigInt::from_slice(Sign::Plus, &digits);
+        }
+    }
+
+    /// A number in `0..n`, for positive `n`.
+    pub fn below(&mut self, n: &BigInt) -> BigInt {
+        if let Rng::Replay(draws) = self {
+            // Past the end of the record, or if the run has diverged from it,
+            // still stay in range.
+            return draws.pop_front().unwrap_or_default().mod_floor(n);
+        }
+        let limit: BigInt = n - 1;
+        if limit.is_zero() {
+            BigInt::zero()
+        } else {
+            self.limited(&limit)
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! A program's size as golf sites score it, and where its bytes go. What an
+//! editor adds, a final newline and CRLF line endings, doesn't count, unless
+//! removing it would change the tokens: a newline can be what `:` assigns
+//! to, and a string can hold a CRLF.
+
+use crate::parse::{
+    assigns_whitespace, escape_controls, lex, shebang_len, significant_tokens, Class,
+};
+use std::collections::HashMap;
+
+/// `program` without a `#!` line or a final newline and with LF line
+/// endings, as far as that keeps its tokens, and notes on what was dropped
+/// or kept.
+pub fn normalize(program: &[u8]) -> (Vec<u8>, Vec<String>) {
+    let same = |a: &[u8], b: &[u8]| {
+        significant_tokens(a, true) == significant_tokens(b, true) && !assigns_whitespace(a)
+    };
+    let mut notes = vec![];
+    let shebang = shebang_len(program);
+    if shebang > 0 {
+        notes.push("the #! line isn't counted".to_string());
+    }
+    let program = &program[shebang..];
+    let mut code = program.to_vec();
+    let crlfs = program.windows(2).filter(|w| w == b"\r\n").count();
+    if crlfs > 0 {
+        let lf = replace_crlf(program);
+        if same(&code, &lf) {
+            notes.push(format!("{} CRLF line ending(s) counted as LF", crlfs));
+            code = lf;
+        } else {
+            notes.push("CRLF line endings kept, since the tokens need them".to_string());
+        }
+    }
+    if let Some(trimmed) = code.strip_suffix(b"\n") {
+        if same(&code, trimmed) {
+            notes.push("the final newline isn't counted".to_string());
+            code = trimmed.to_vec();
+        } else {
+            notes.push("the final newline is counted, since the program needs it".to_string());
+        }
+    }
+    (code, notes)
+}
+
+fn replace_crlf(program: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(program.len());
+    for (i, &b) in program.iter().enumerate() {
+        if !(b == b'\r' && program.get(i + 1) == Some(&b'\n')) {
+            out.push(b);
+        }
+    }
+    out
+}
+
+fn kind(class: Class) -> &'static str {
+    match class {
+        Class::Int => "numbers",
+        Class::String => "strings",
+        Class::Comment => "comments",
+        Class::Word => "names",
+        Class::Symbol => "operators",
+        Class::Assignment => "assignments",
+        Class::Brace => "braces",
+        Class::Whitespace => "whitespace",
+        Class::Unparsed => "unparsed",
+    }
+}
+
+/// The size of `program` in bytes and characters, then its bytes by kind of
+/// token and by token, most first.
+pub fn report(program: &[u8]) -> String {
+    let (code, notes) = normalize(program);
+    let chars = match std::str::from_utf8(&code) {
+        Ok(s) => format!("{} characters", s.chars().count()),
+        Err(_) => "not valid UTF-8".to_string(),
+    };
+    let mut lines = vec![format!("{} bytes, {}", code.len(), chars)];
+    if code.len() != program.len() {
+        lines.push(format!("  ({} bytes as saved)", program.len()));
+    }
+    for note in notes {
+        lines.push(format!("  {}", note));
+    }
+    let lexemes = lex(&code);
+    let mut kinds: HashMap<&str, usize> = HashMap::new();
+    let mut tokens: HashMap<&[u8], (usize, usize)> = HashMap::new();
+    for l in &lexemes {
+        *kinds.entry(kind(l.class)).or_default() += l.len;
+        let (count, _) = tokens
+            .entry(&code[l.offset..l.offset + l.len])
+            .or_insert((0, l.len));
+        *count += 1;
+    }
+    let mut kinds: Vec<_> = kinds.into_iter().collect();
+    kinds.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    lines.push("by kind:".to_string());
+    for (kind, bytes) in kinds {
+        lines.push(format!("  {:>5}  {}", bytes, kind));
+    }
+    let mut tokens: Vec<_> = tokens.into_iter().collect();
+    tokens.sort_by(|a, b| (b.1 .0 * b.1 .1).cmp(&(a.1 .0 * a.1 .1)).then(a.0.cmp(b.0)));
+    lines.push("by token:".to_string());
+    lines.push("  bytes  uses  token".to_string());
+    for (token, (count, len)) in tokens {
+        let token = match token {
+            b" " => "(space)".to_string(),
+            b"\n" => "(newline)".to_string(),
+            b"\t" => "(tab)".to_string(),
+            b"\r" => "(carriage return)".to_string(),
+            token => escape_controls(token),
+        };
+        lines.push(format!("  {:>5}  {:>4}  {}", count * len, count, token));
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,94 @@
+//! An interpreter's state saved to a file, to load into a later run: the
+//! stack and the variables programs set, so a computation can stop and go on
+//! where it left off. It's zlib-compressed JSON like a [`crate::record`],
+//! with the flags that change what code means, which a later run should
+//! match. Where a run was in its program isn't saved, so a state is taken
+//! between runs, not in the middle of one.
+
+use crate::record::{
+    bytes_from_json, bytes_to_json, invalid, read_compressed, value_from_json, value_to_json,
+    write_compressed,
+};
+use crate::value::Gval;
+use crate::Gs;
+use serde_json::{json, Value};
+use std::io;
+
+const VERSION: u64 = 1;
+
+pub struct State {
+    /// The stack, bottom first.
+    pub stack: Vec<Gval>,
+    /// Variables that aren't as the builtin definitions left them, by name.
+    pub vars: Vec<(Vec<u8>, Gval)>,
+    /// Command-line flags the state was saved with, like `--extensions`.
+    pub flags: Vec<String>,
+}
+
+impl State {
+    /// The state `gs` is in, run with `flags`.
+    pub fn of(gs: &Gs, flags: Vec<String>) -> State {
+        State {
+            stack: gs.stack.clone(),
+            vars: gs.changed_vars(),
+            flags,
+        }
+    }
+
+    /// Put `gs` in this state, replacing its stack and the variables
+    /// programs set.
+    pub fn restore(self, gs: &mut Gs) {
+        gs.stack = self.stack;
+        gs.reset_vars();
+        for (name, v) in self.vars {
+            gs.set_var(name, v);
+        }
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let vars: Vec<Value> = self
+            .vars
+            .iter()
+            .map(|(name, v)| json!({ "name": bytes_to_json(name), "value": value_to_json(v) }))
+            .collect();
+        let state = json!({
+            "version": VERSION,
+            "stack": self.stack.iter().map(value_to_json).collect::<Vec<_>>(),
+            "vars": vars,
+            "flags": self.flags,
+        });
+        write_compressed(path, &state)
+    }
+
+    pub fn load(path: &str) -> io::Result<State> {
+        let v = read_compressed(path)?;
+        if v["version"].as_u64() != Some(VERSION) {
+            return Err(invalid("unsupported state version"));
+        }
+        let list = |key: &str| {
+            v[key]
+                .as_array()
+                .cloned()
+                .ok_or_else(|| invalid(&format!("missing `{}`", key)))
+        };
+        let stack = list("stack")?
+            .iter()
+            .map(value_from_json)
+            .collect::<io::Result<_>>()?;
+        let vars = list("vars")?
+            .iter()
+            .map(|var| {
+                Ok((
+                    bytes_from_json(&var["name"])?,
+                    value_from_json(&var["value"])?,
+                ))
+            })
+            .collect::<io::Result<_>>()?;
+        let flags = list("flags")?
+            .iter()
+            .map(|f| f.as_str().map(str::to_string))
+            .collect::<Option<_>>()
+            .ok_or_else(|| invalid("bad flags"))?;
+        Ok(State { stack, vars, flags })
+    }
+}
@@ -0,0 +1,56 @@
+//! How much room a run needed: the deepest stack, the deepest nesting of
+//! blocks, and the biggest values. Values are looked at on top of the stack
+//! between tokens, which is where each token leaves what it produces.
+
+use crate::error::GsResult;
+use crate::hook::{Hook, Step};
+use crate::value::Gval;
+use crate::Gs;
+
+#[derive(Default)]
+pub struct Stats {
+    tokens: u64,
+    max_stack: usize,
+    /// How deeply blocks and `~` nested; 0 for the program itself.
+    max_nesting: usize,
+    longest_array: usize,
+    longest_string: usize,
+    biggest_int_bits: u64,
+}
+
+impl Hook for Stats {
+    fn before(&mut self, gs: &mut Gs, step: &Step) -> GsResult<()> {
+        self.tokens += 1;
+        self.max_nesting = self.max_nesting.max(step.depth.saturating_sub(1));
+        self.finish(gs);
+        Ok(())
+    }
+}
+
+impl Stats {
+    /// Look at the stack the last token left; call this when the run ends.
+    pub fn finish(&mut self, gs: &Gs) {
+        self.max_stack = self.max_stack.max(gs.stack.len());
+        match gs.stack.last() {
+            Some(Gval::Int(n)) => self.biggest_int_bits = self.biggest_int_bits.max(n.bits()),
+            Some(Gval::Arr(a)) => self.longest_array = self.longest_array.max(a.len()),
+            Some(Gval::Str(s) | Gval::Blk(s)) => {
+                self.longest_string = self.longest_string.max(s.len())
+            }
+            None => {}
+        }
+    }
+
+    pub fn report(&self) -> String {
+        format!(
+            "stats: {} tokens, stack up to {} values, blocks nested up to {} deep\n\
+             largest values: array of {} elements, string or block of {} bytes, integer of {} bits",
+            self.tokens,
+            self.max_stack,
+            self.max_nesting,
+            self.longest_array,
+            self.longest_string,
+            self.biggest_int_bits
+        )
+    }
+}
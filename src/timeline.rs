@@ -0,0 +1,144 @@
+//! A run as JSON, token by token with the stack each token found, for a
+//! separate viewer to animate. Everything is capped, so a long run or a big
+//! value makes a big file, not an unbounded one.
+//!
+//! The schema, version 1:
+//!
+//! ```text
+//! {
+//!   "version": 1,
+//!   "program": "~]{+}*",
+//!   "steps": [
+//!     {"token": "~", "offset": 0, "depth": 1, "stack_len": 1, "stack": [{"str": "1 2", "len": 3}]},
+//!     ...
+//!   ],
+//!   "total_steps": 8,        // how many tokens ran, recorded or not
+//!   "truncated": false,      // whether steps past the cap were left out
+//!   "end": {"stack_len": 1, "stack": [{"int": "3"}], "error": null}
+//! }
+//! ```
+//!
+//! `offset` is the token's byte offset in the program, or null for code
+//! that isn't in it, like a string run with `~`; `depth` is 1 for the
+//! program and one more for each block or `~` it's inside. A stack lists at
+//! most its top values, bottom first; `stack_len` is its full length.
+//! Values are `{"int": "digits"}`, `{"str": text, "len": bytes}`,
+//! `{"blk": source, "len": bytes}` or `{"arr": [values], "len": elements}`,
+//! where text and elements past the caps are left out and `len` tells how
+//! many there were. Text is UTF-8, with invalid bytes replaced.
+
+use crate::error::{GsError, GsResult};
+use crate::hook::{Hook, Step};
+use crate::value::Gval;
+use crate::Gs;
+use serde_json::{json, Value};
+
+const VERSION: u64 = 1;
+
+pub struct Timeline {
+    max_steps: usize,
+    max_values: usize,
+    max_len: usize,
+    steps: Vec<Value>,
+    total: u64,
+}
+
+impl Default for Timeline {
+    fn default() -> Timeline {
+        Timeline {
+            max_steps: 10_000,
+            max_values: 32,
+            max_len: 64,
+            steps: vec![],
+            total: 0,
+        }
+    }
+}
+
+impl Hook for Timeline {
+    fn before(&mut self, gs: &mut Gs, step: &Step) -> GsResult<()> {
+        self.total += 1;
+        if self.steps.len() < self.max_steps {
+            let mut entry = json!({
+                "token": String::from_utf8_lossy(step.token),
+                "offset": step.program_offset,
+                "depth": step.depth,
+            });
+            self.snapshot(&gs.stack, &mut entry);
+            self.steps.push(entry);
+        }
+        Ok(())
+    }
+}
+
+impl Timeline {
+    /// How many steps to record; 10,000 by default.
+    pub fn max_steps(mut self, max: usize) -> Timeline {
+        self.max_steps = max;
+        self
+    }
+
+    /// How many values from the top of the stack to record; 32 by default.
+    pub fn max_values(mut self, max: usize) -> Timeline {
+        self.max_values = max;
+        self
+    }
+
+    /// How many bytes of text, and elements of arrays in all, to record of
+    /// each value; 64 by default.
+    pub fn max_len(mut self, max: usize) -> Timeline {
+        self.max_len = max;
+        self
+    }
+
+    fn snapshot(&self, stack: &[Gval], entry: &mut Value) {
+        let top = &stack[stack.len().saturating_sub(self.max_values)..];
+        entry["stack_len"] = json!(stack.len());
+        entry["stack"] = top
+            .iter()
+            .map(|v| {
+                let mut budget = self.max_len;
+                self.value(v, &mut budget)
+            })
+            .collect();
+    }
+
+    /// `v` as JSON, with at most `budget` array elements in all.
+    fn value(&self, v: &Gval, budget: &mut usize) -> Value {
+        let text = |bytes: &[u8]| {
+            let cut = &bytes[..bytes.len().min(self.max_len)];
+            String::from_utf8_lossy(cut).into_owned()
+        };
+        match v {
+            Gval::Int(n) => json!({ "int": n.to_string() }),
+            Gval::Str(s) => json!({ "str": text(s), "len": s.len() }),
+            Gval::Blk(s) => json!({ "blk": text(s), "len": s.len() }),
+            Gval::Arr(a) => {
+                let mut items = vec![];
+                for x in a {
+                    if *budget == 0 {
+                        break;
+                    }
+                    *budget -= 1;
+                    items.push(self.value(x, budget));
+                }
+                json!({ "arr": items, "len": a.len() })
+            }
+        }
+    }
+
+    /// The run of `program` as JSON, ending with the stack `gs` was left
+    /// with and the error that stopped it, if one did.
+    pub fn to_json(&self, program: &[u8], gs: &Gs, error: Option<&GsError>) -> Value {
+        let mut end = json!({ "error": error.map(|e| e.to_string()) });
+        self.snapshot(&gs.stack, &mut end);
+        json!({
+            "version": VERSION,
+            "program": String::from_utf8_lossy(program),
+            "steps": self.steps,
+            "total_steps": self.total,
+            "truncated": self.total > self.steps.len() as u64,
+            "end": end,
+        })
+    }
+}
@@ -0,0 +1,129 @@
+//! Where a runtime error happened. As an error unwinds, each level of running
+//! code records the token it was on, from the failing operator out to the
+//! program.
+
+use crate::parse::{snippet, Location};
+use crate::util::preview_code;
+
+pub struct Frame {
+    /// What was running: the program, a block body, or an evaluated string.
+    pub code: Vec<u8>,
+    pub offset: usize,
+    pub token: Vec<u8>,
+}
+
+/// What a token does with the code it runs, for builtins that run blocks.
+fn role(token: &[u8]) -> Option<&'static str> {
+    Some(match token {
+        b"%" => "map",
+        b"/" => "each or unfold",
+        b"*" => "fold or times",
+        b"," => "select",
+        b"?" => "find",
+        b"$" => "sort",
+        b"~" => "eval",
+        b"do" | b"while" | b"until" => "loop",
+        b"if" => "if",
+        _ => return None,
+    })
+}
+
+/// How many characters of a token or block to show.
+const SHOWN: usize = 30;
+
+/// Where `code` starts in `program`, if it's the body of a block literal
+/// there or, when `~` evaluated it, of a string literal. Identical literals
+/// all map to the first one.
+pub(crate) fn find_literal(program: &[u8], code: &[u8], evaluated: bool) -> Option<usize> {
+    let delimiters: &[(&[u8], &[u8])] = if evaluated {
+        &[(b"{", b"}"), (b"\"", b"\""), (b"'", b"'")]
+    } else {
+        &[(b"{", b"}")]
+    };
+    delimiters.iter().find_map(|(open, close)| {
+        let literal = [open, code, close].concat();
+        program
+            .windows(literal.len())
+            .position(|w| w == literal)
+            .map(|i| i + 1)
+    })
+}
+
+/// Where the code of `frames[i]` starts in `program`, if it can be found.
+fn base(program: &[u8], frames: &[Frame], i: usize) -> Option<usize> {
+    let frame = &frames[i];
+    if i + 1 == frames.len() && frame.code == program {
+        return Some(0);
+    }
+    let evaluated = frames.get(i + 1).is_some_and(|f| f.token == b"~");
+    find_literal(program, &frame.code, evaluated)
+}
+
+/// The offset in `program` of the innermost frame that can be found there.
+pub fn locate(program: &[u8], frames: &[Frame]) -> Option<usize> {
+    (0..frames.len()).find_map(|i| base(program, frames, i).map(|b| b + frames[i].offset))
+}
+
+/// Describe `frames`, innermost first, with positions in `program` where
+/// they can be found, followed by the program line where the error happened.
+/// Code run by `~` is also located within itself, since a string may not
+/// appear verbatim in the program.
+pub fn render(program: &[u8], frames: &[Frame]) -> String {
+    // Deep recursion makes for long traces; the ends are what matter.
+    const ENDS: usize = 10;
+    let mut lines = vec![];
+    let mut underline = None;
+    for (i, frame) in frames.iter().enumerate() {
+        if frames.len() > 3 * ENDS && (ENDS..frames.len() - ENDS).contains(&i) {
+            if i == ENDS {
+                lines.push(format!("  ... {} more ...", frames.len() - 2 * ENDS));
+            }
+            continue;
+        }
+        let what = match role(&frame.token) {
+            Some(role) if i > 0 => format!("in `{}` ({})", preview_code(&frame.token, SHOWN), role),
+            _ if i > 0 => format!("in `{}`", preview_code(&frame.token, SHOWN)),
+            _ => format!("at `{}`", preview_code(&frame.token, SHOWN)),
+        };
+        let evaluated = frames.get(i + 1).is_some_and(|f| f.token == b"~");
+        let local = Location::new(&frame.code, frame.offset);
+        match base(program, frames, i) {
+            Some(base) => {
+                let at = base + frame.offset;
+                let loc = Location::new(program, at);
+                let within = if evaluated {
+                    format!(
+                        " (line {}, col {} of the evaluated code)",
+                        local.line, local.col
+                    )
+                } else {
+                    String::new()
+                };
+                lines.push(format!(
+                    "  {}, line {}, col {}{}",
+                    what, loc.line, loc.col, within
+                ));
+                underline.get_or_insert((at, frame.token.len()));
+            }
+            None => {
+                let code = if evaluated { "evaluated code" } else { "code" };
+                lines.push(format!(
+                    "  {}, line {}, col {} of {} `{}`",
+                    what,
+                    local.line,
+                    local.col,
+                    code,
+                    preview_code(&frame.code, SHOWN)
+                ));
+                let snippet = snippet(&frame.code, frame.offset, frame.token.len());
+                for line in snippet.lines() {
+                    lines.push(format!("    {}", line));
+                }
+            }
+        }
+    }
+    if let Some((at, len)) = underline {
+        lines.push(snippet(program, at, len));
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,129 @@
+//! Translating a program into source code in another language.
+//!
+//! Rust: a standalone `main.rs` for a crate that depends on this one. The
+//! program is tokenized ahead of time and its token tree is written out as
+//! Rust values, so the binary starts running at once, with no program file
+//! to read or parse, and runs like `golfscript-rs --input-from-stdin`.
+//! Blocks keep their source too, since GolfScript can print them or run
+//! them as strings.
+//!
+//! Python: readable pseudocode rather than something to run, spelling out
+//! what each token does; see [`crate::pseudocode`].
+
+use crate::parse::{offset, parse_code, Gtoken};
+use crate::pseudocode::python;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// A `main.rs` for a crate that depends on golfscript-rs.
+    Rust,
+    /// Python-like pseudocode, for reading.
+    Python,
+}
+
+/// `bytes` as a Rust byte string literal.
+fn byte_string(bytes: &[u8]) -> String {
+    let escaped: String = bytes
+        .iter()
+        .flat_map(|&b| std::ascii::escape_default(b))
+        .map(char::from)
+        .collect();
+    format!("b\"{}\"", escaped)
+}
+
+/// `tokens` as Rust expressions slicing `p`, the program, one per line.
+fn rust_tokens(program: &[u8], tokens: &[Gtoken], indent: usize, out: &mut Vec<String>) {
+    let pad = " ".repeat(indent);
+    for token in tokens {
+        let lexeme = token.lexeme();
+        let at = offset(program, lexeme);
+        let slice = format!("&p[{}..{}]", at, at + lexeme.len());
+        let variant = match token {
+            Gtoken::Block(inner, _) => {
+                out.push(format!("{}Block(", pad));
+                out.push(format!("{}    vec![", pad));
+                rust_tokens(program, inner, indent + 8, out);
+                out.push(format!("{}    ],", pad));
+                out.push(format!("{}    {},", pad, slice));
+                out.push(format!("{}),", pad));
+                continue;
+            }
+            Gtoken::Symbol(_) => "Symbol",
+            Gtoken::SingleQuotedString(_) => "SingleQuotedString",
+            Gtoken::DoubleQuotedString(_) => "DoubleQuotedString",
+            Gtoken::IntLiteral(_) => "IntLiteral",
+            Gtoken::Comment(_) => "Comment",
+            Gtoken::Assignment(_) => "Assignment",
+        };
+        out.push(format!("{}{}({}),", pad, variant, slice));
+    }
+}
+
+fn rust(program: &[u8], tokens: &[Gtoken], name: &str) -> String {
+    let mut lines = vec![
+        format!(
+            "//! `{}`, translated by golfscript-rs.",
+            name.replace('`', "'")
+        ),
+        "".to_string(),
+        "use golfscript_rs::parse::Gtoken::{self, *};".to_string(),
+        "use golfscript_rs::trace::render;".to_string(),
+        "use golfscript_rs::value::Gval;".to_string(),
+        "use golfscript_rs::Gs;".to_string(),
+        "use std::io::Read;".to_string(),
+        "".to_string(),
+        format!("const PROGRAM: &[u8] = {};", byte_string(program)),
+        "".to_string(),
+        "/// The tokens of the program `p`.".to_string(),
+        "fn tokens(p: &[u8]) -> Vec<Gtoken<'_>> {".to_string(),
+        "    vec![".to_string(),
+    ];
+    rust_tokens(program, tokens, 8, &mut lines);
+    lines.extend(
+        r#"    ]
+}
+
+fn main() {
+    let mut input = vec![];
+    if let Err(e) = std::io::stdin().read_to_end(&mut input) {
+        eprintln!("can't read stdin: {}", e);
+        std::process::exit(1)
+    }
+    let mut gs = Gs::new();
+    gs.stack.push(Gval::Str(input));
+    let result = gs
+        .run_parsed(PROGRAM, tokens(PROGRAM))
+        .and_then(|()| {
+            gs.stack = vec![Gval::Arr(std::mem::take(&mut gs.stack))];
+            gs.run(b"puts")
+        })
+        .and_then(|()| gs.flush());
+    if let Err(e) = result {
+        let _ = gs.flush();
+        eprintln!("error: {}", e);
+        if !gs.trace().is_empty() {
+            eprintln!("{}", render(PROGRAM, gs.trace()));
+        }
+        std::process::exit(1)
+    }
+}"#
+        .lines()
+        .map(str::to_string),
+    );
+    lines.join("\n") + "\n"
+}
+
+/// `program`, named `name`, translated to `target`, or why it can't be.
+pub fn transpile(program: &[u8], name: &str, target: Target) -> Result<String, String> {
+    let tokens = match parse_code(program) {
+        Ok((&[], tokens)) => tokens,
+        _ => return Err("the program doesn't parse".to_string()),
+    };
+    if let Some(Gtoken::Symbol(b":")) = tokens.last() {
+        return Err("`:` at end of code has no name to assign to".to_string());
+    }
+    Ok(match target {
+        Target::Rust => rust(program, &tokens, name),
+        Target::Python => python(program, &tokens, name),
+    })
+}
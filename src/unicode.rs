@@ -0,0 +1,62 @@
+//! Helpers for `--unicode`, where strings are sequences of Unicode scalar
+//! values instead of bytes. Strings are still stored as UTF-8, so input and
+//! output pass through unchanged; the operators that count, index or split
+//! strings decode them first and encode the results again.
+
+use crate::value::Gval;
+use num::ToPrimitive;
+
+/// The scalar values of `s`, with invalid UTF-8 replaced by U+FFFD.
+pub fn decode(s: &[u8]) -> Vec<char> {
+    String::from_utf8_lossy(s).chars().collect()
+}
+
+pub fn encode(cs: &[char]) -> Vec<u8> {
+    cs.iter().collect::<String>().into_bytes()
+}
+
+/// `f` of the scalar values of `x` and `y`, as a string again.
+pub fn by_chars(x: &[u8], y: &[u8], f: impl FnOnce(Vec<char>, Vec<char>) -> Vec<char>) -> Vec<u8> {
+    encode(&f(decode(x), decode(y)))
+}
+
+/// A character as a value, the way a byte is one in byte mode.
+pub fn scalar(c: char) -> Gval {
+    Gval::Int((c as u32).into())
+}
+
+/// `vs` as a string, like [`flatten`](crate::coerce::flatten) but taking
+/// each integer as a scalar value. Integers that aren't one become U+FFFD.
+pub fn flatten_chars(vs: Vec<Gval>) -> Vec<u8> {
+    let mut bytes = vec![];
+    for v in vs {
+        match v {
+            Gval::Int(n) => {
+                let c = n.to_u32().and_then(char::from_u32).unwrap_or('\u{FFFD}');
+                bytes.extend(c.to_string().into_bytes());
+            }
+            Gval::Arr(vs) => bytes.extend(flatten_chars(vs)),
+            Gval::Str(bs) | Gval::Blk(bs) => bytes.extend(bs),
+        }
+    }
+    bytes
+}
+
+/// The UTF-8 character starting at `at` in `code`, or just the byte there
+/// if none does. Code tokenizes a byte at a time, so a multibyte character
+/// is several symbols.
+pub fn char_at(code: &[u8], at: usize) -> &[u8] {
+    if code[at].is_ascii() {
+        return &code[at..at + 1];
+    }
+    (2..=4)
+        .filter_map(|len| code.get(at..at + len))
+        .find(|bytes| std::str::from_utf8(bytes).is_ok())
+        .unwrap_or(&code[at..at + 1])
+}
+
+/// Whether the byte at `at` in `code` continues a character started before.
+pub fn continues_char(code: &[u8], at: usize) -> bool {
+    (0x80..0xc0).contains(&code[at])
+        && (at.saturating_sub(3)..at).any(|start| start + char_at(code, start).len() > at)
+}
@@ -0,0 +1,82 @@
+//! `--utf8-output`: check program output is valid UTF-8 before it's written.
+
+use std::io::{self, Write};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Utf8Mode {
+    /// Fail with an error at the first invalid sequence.
+    Strict,
+    /// Replace each invalid sequence with U+FFFD.
+    Lossy,
+}
+
+/// Validates everything written through it. A character split across two
+/// writes is held back until its last byte arrives; on flush, any incomplete
+/// character left over counts as invalid.
+pub struct Utf8Writer<W: Write> {
+    inner: W,
+    mode: Utf8Mode,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Utf8Writer<W> {
+    pub fn new(inner: W, mode: Utf8Mode) -> Self {
+        Utf8Writer {
+            inner,
+            mode,
+            pending: vec![],
+        }
+    }
+
+    fn invalid(&mut self) -> io::Result<()> {
+        match self.mode {
+            Utf8Mode::Strict => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "output is not valid UTF-8",
+            )),
+            Utf8Mode::Lossy => self.inner.write_all("\u{FFFD}".as_bytes()),
+        }
+    }
+
+    /// Write out the valid prefix of `pending`, stopping at an incomplete
+    /// character unless `at_end`.
+    fn drain(&mut self, at_end: bool) -> io::Result<()> {
+        let mut start = 0;
+        while start < self.pending.len() {
+            match std::str::from_utf8(&self.pending[start..]) {
+                Ok(_) => {
+                    self.inner.write_all(&self.pending[start..])?;
+                    start = self.pending.len();
+                }
+                Err(e) => {
+                    let valid = start + e.valid_up_to();
+                    self.inner.write_all(&self.pending[start..valid])?;
+                    start = valid;
+                    match e.error_len() {
+                        Some(n) => start += n,
+                        None if !at_end => break,
+                        None => start = self.pending.len(),
+                    }
+                    self.pending.drain(..start);
+                    start = 0;
+                    self.invalid()?;
+                }
+            }
+        }
+        self.pending.drain(..start);
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Utf8Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        self.drain(false)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.drain(true)?;
+        self.inner.flush()
+    }
+}
@@ -1,3 +1,5 @@
+use crate::error::{GsError, GsResult};
+use crate::value::Gval;
 use core::cmp::Ordering;
 use core::hash::Hash;
 use num::BigInt;
@@ -6,35 +8,48 @@ use num::One;
 use num::Signed;
 use num::ToPrimitive;
 use num::Zero;
+use std::cell::RefCell;
 use std::collections::HashSet;
+use std::io::Write;
+use std::rc::Rc;
 
-pub fn to_byte(n: BigInt) -> u8 {
-    n.mod_floor(&256.into()).to_u8().unwrap()
-}
+/// Output kept in memory.
+#[derive(Clone, Default)]
+pub(crate) struct Buffer(pub(crate) Rc<RefCell<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
 
-pub fn repeat<T: Clone>(a: Vec<T>, mut n: BigInt) -> Vec<T> {
-    let mut v = vec![];
-    while n.is_positive() {
-        v.extend(a.clone());
-        n -= 1;
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
-    v
 }
 
-pub fn chunk<T: Clone>(a: &mut Vec<T>, n: BigInt) -> Vec<&[T]> {
+pub fn to_byte(n: BigInt) -> u8 {
+    n.mod_floor(&256.into()).to_u8().unwrap()
+}
+
+pub fn chunk<T: Clone>(a: &mut [T], n: BigInt) -> GsResult<Vec<&[T]>> {
     if a.is_empty() {
-        return vec![];
+        return Ok(vec![]);
     }
     if n.is_zero() {
-        panic!("chunk division by 0");
+        return Err(GsError::Runtime("chunk division by 0".into()));
     }
     if n.is_negative() {
         a.reverse();
     }
-    a.chunks(n.abs().to_usize().unwrap()).collect()
+    Ok(a.chunks(n.abs().to_usize().unwrap_or(usize::MAX)).collect())
 }
 
 pub fn split<T: Clone + Eq>(a: Vec<T>, sep: Vec<T>, clean: bool) -> Vec<Vec<T>> {
+    // Like Ruby, an empty separator splits between every element.
+    if sep.is_empty() {
+        return a.into_iter().map(|x| vec![x]).collect();
+    }
     let mut r: Vec<Vec<T>> = vec![];
     let mut i: Vec<T> = vec![];
     let mut j: usize = 0;
@@ -57,23 +72,28 @@ pub fn split<T: Clone + Eq>(a: Vec<T>, sep: Vec<T>, clean: bool) -> Vec<Vec<T>>
     r
 }
 
-pub fn every_nth<T>(a: Vec<T>, n: BigInt) -> Vec<T> {
-    let m = n.abs().to_usize().unwrap();
-    if n.is_negative() {
+pub fn every_nth<T>(a: Vec<T>, n: BigInt) -> GsResult<Vec<T>> {
+    if n.is_zero() {
+        return Err(GsError::Runtime("every-nth step 0".into()));
+    }
+    // A step past the end still takes the first element.
+    let m = n.abs().to_usize().unwrap_or(usize::MAX);
+    Ok(if n.is_negative() {
         a.into_iter().rev().step_by(m).collect()
     } else {
         a.into_iter().step_by(m).collect()
-    }
+    })
 }
 
-pub fn set_subtract<T: Eq>(a: Vec<T>, b: Vec<T>) -> Vec<T> {
-    a.into_iter().filter(|x| !b.contains(x)).collect()
+pub fn set_subtract<T: Eq + Hash>(a: Vec<T>, b: Vec<T>) -> Vec<T> {
+    let in_b: HashSet<T> = b.into_iter().collect();
+    a.into_iter().filter(|x| !in_b.contains(x)).collect()
 }
 
 pub fn set_or<T: Clone + Eq + Hash>(a: Vec<T>, b: Vec<T>) -> Vec<T> {
     let mut seen: HashSet<T> = HashSet::new();
     let mut result: Vec<T> = vec![];
-    for v in a.into_iter().chain(b.into_iter()) {
+    for v in a.into_iter().chain(b) {
         if seen.insert(v.clone()) {
             result.push(v)
         }
@@ -107,7 +127,7 @@ pub fn set_xor<T: Clone + Eq + Hash>(a: Vec<T>, b: Vec<T>) -> Vec<T> {
     for v in &b {
         in_b.insert(v.clone());
     }
-    for v in a.into_iter().chain(b.into_iter()) {
+    for v in a.into_iter().chain(b) {
         if !seen.contains(&v) && (in_a.contains(&v) ^ in_b.contains(&v)) {
             seen.insert(v.clone());
             result.push(v)
@@ -116,7 +136,7 @@ pub fn set_xor<T: Clone + Eq + Hash>(a: Vec<T>, b: Vec<T>) -> Vec<T> {
     result
 }
 
-pub fn index<T>(a: &Vec<T>, i: BigInt) -> Option<&T> {
+pub fn index<T>(a: &[T], i: BigInt) -> Option<&T> {
     let l: BigInt = a.len().into();
     if i >= l {
         None
@@ -148,7 +168,7 @@ pub fn slice<T: Clone>(o: Ordering, a: Vec<T>, i: BigInt) -> Vec<T> {
     }
 }
 
-pub fn string_index(haystack: &[u8], needle: &[u8]) -> BigInt {
+pub fn string_index<T: PartialEq>(haystack: &[T], needle: &[T]) -> BigInt {
     let hl = haystack.len();
     let nl = needle.len();
     if nl <= hl {
@@ -160,3 +180,75 @@ pub fn string_index(haystack: &[u8], needle: &[u8]) -> BigInt {
     }
     -BigInt::one()
 }
+
+/// How many bytes of a rendering to look at to show `max` characters of
+/// it, and know whether there's more: a character is at most four bytes.
+fn preview_bytes(max: usize) -> usize {
+    4 * max + 8
+}
+
+/// At most `limit` bytes from the start of what `v.inspect()` gives, without
+/// rendering the rest.
+fn inspect_prefix(v: &Gval, limit: usize, out: &mut Vec<u8>) {
+    if out.len() >= limit {
+        return;
+    }
+    match v {
+        Gval::Int(n) => out.extend(n.to_str_radix(10).into_bytes()),
+        Gval::Arr(vs) => {
+            out.push(b'[');
+            for (i, v) in vs.iter().enumerate() {
+                if out.len() >= limit {
+                    return;
+                }
+                if i > 0 {
+                    out.push(b' ');
+                }
+                inspect_prefix(v, limit, out);
+            }
+            out.push(b']');
+        }
+        Gval::Str(bs) => {
+            out.push(b'"');
+            for &b in bs {
+                if out.len() >= limit {
+                    return;
+                }
+                if b == b'\'' {
+                    out.push(b)
+                } else {
+                    out.extend(std::ascii::escape_default(b))
+                }
+            }
+            out.push(b'"');
+        }
+        Gval::Blk(bs) => {
+            out.push(b'{');
+            out.extend_from_slice(&bs[..bs.len().min(limit)]);
+            out.push(b'}');
+        }
+    }
+}
+
+/// `s` cut to `max` characters, with `...` if that cut anything.
+fn cut(s: String, max: usize) -> String {
+    match s.char_indices().nth(max) {
+        Some((i, _)) => format!("{}...", &s[..i]),
+        None => s,
+    }
+}
+
+/// `v` as [`Gval::inspect`] shows it, on one line and cut to `max`
+/// characters, rendering only as much of `v` as that needs.
+pub fn preview(v: &Gval, max: usize) -> String {
+    let mut out = vec![];
+    inspect_prefix(v, preview_bytes(max), &mut out);
+    out.truncate(preview_bytes(max));
+    cut(String::from_utf8_lossy(&out).replace('\n', "\\n"), max)
+}
+
+/// Code as text on one line, cut to `max` characters.
+pub fn preview_code(code: &[u8], max: usize) -> String {
+    let code = &code[..code.len().min(preview_bytes(max))];
+    cut(String::from_utf8_lossy(code).replace('\n', " "), max)
+}
@@ -1,5 +1,7 @@
+use crate::coerce::coerce;
 use crate::coerce::flatten_append;
-use crate::coerce::{coerce, Coerced};
+use crate::error::{type_error, GsResult};
+use crate::util::preview;
 use num::BigInt;
 use num::One;
 use num::Zero;
@@ -92,22 +94,16 @@ impl Gval {
     }
 
     pub fn plus(self, other: Gval) -> Gval {
-        match coerce(self, other) {
-            Coerced::Ints(x, y) => Gval::Int(x + y),
-            Coerced::Arrs(mut x, y) => {
-                x.extend(y);
-                Gval::Arr(x)
-            }
-            Coerced::Strs(mut x, y) => {
-                x.extend(y);
-                Gval::Str(x)
-            }
-            Coerced::Blks(x, y) => {
-                let mut joined = x.clone();
-                joined.push(b' ');
-                joined.extend(y);
-                Gval::Blk(joined)
-            }
+        coerce(self, other).concat()
+    }
+
+    /// About how much copying `self` copies along with it: the elements of
+    /// an array, and theirs, and a unit per word of a string or integer.
+    pub fn weight(&self) -> usize {
+        match self {
+            Gval::Int(n) => (n.bits() / 64) as usize,
+            Gval::Arr(vs) => vs.iter().map(|v| 1 + v.weight()).sum(),
+            Gval::Str(bs) | Gval::Blk(bs) => bs.len() / 8,
         }
     }
 
@@ -120,51 +116,76 @@ impl Gval {
         }
     }
 
-    pub fn push(&mut self, other: Gval) {
+    /// A sequence of the same type as `self`, holding `elems`.
+    pub fn rebuild(&self, elems: Vec<Gval>) -> GsResult<Gval> {
+        let mut r = self.factory();
+        for e in elems {
+            r.push(e)?;
+        }
+        Ok(r)
+    }
+
+    pub fn push(&mut self, other: Gval) -> GsResult<()> {
         match self {
-            Gval::Int(_) => panic!("push"),
+            Gval::Int(_) => return Err(type_error(format!("can't append to {}", self.describe()))),
             Gval::Arr(vs) => vs.push(other),
             Gval::Str(vs) => flatten_append(vs, other),
             Gval::Blk(vs) => flatten_append(vs, other),
         }
+        Ok(())
     }
 
-    pub fn unwrap_int(self) -> BigInt {
+    /// The value's type and a short rendering of it, for error messages.
+    pub fn describe(&self) -> String {
+        const MAX: usize = 24;
+        let kind = match self {
+            Gval::Int(_) => "integer",
+            Gval::Arr(_) => "array",
+            Gval::Str(_) => "string",
+            Gval::Blk(_) => "block",
+        };
+        format!("{} {}", kind, preview(self, MAX))
+    }
+
+    pub fn unwrap_int(self) -> GsResult<BigInt> {
         match self {
-            Gval::Int(n) => n,
-            _ => panic!("expected int"),
+            Gval::Int(n) => Ok(n),
+            v => Err(type_error(format!(
+                "expected integer, got {}",
+                v.describe()
+            ))),
         }
     }
 
-    pub fn unwrap_arr(self) -> Vec<Gval> {
+    pub fn unwrap_arr(self) -> GsResult<Vec<Gval>> {
         match self {
-            Gval::Arr(a) => a,
-            _ => panic!("expected array"),
+            Gval::Arr(a) => Ok(a),
+            v => Err(type_error(format!("expected array, got {}", v.describe()))),
         }
     }
 
-    pub fn into_arr(self) -> Vec<Gval> {
+    pub fn unwrap_str(self) -> GsResult<Vec<u8>> {
         match self {
-            Gval::Int(_) => panic!("into_arr"),
-            Gval::Arr(a) => a,
-            Gval::Str(a) | Gval::Blk(a) => a.into_iter().map(|b| b.into()).collect(),
+            Gval::Str(s) => Ok(s),
+            v => Err(type_error(format!("expected string, got {}", v.describe()))),
         }
     }
-}
 
-pub fn join(a: Vec<Gval>, sep: Gval) -> Gval {
-    let mut a = a.into_iter();
-    match a.next() {
-        None => match sep {
-            Gval::Arr(_) => Gval::Arr(vec![]),
-            _ => Gval::Str(vec![]),
-        },
-        Some(mut r) => {
-            r = coerce(r, sep.clone()).left();
-            for i in a {
-                r = r.plus(sep.clone()).plus(i);
-            }
-            r
+    pub fn unwrap_blk(self) -> GsResult<Vec<u8>> {
+        match self {
+            Gval::Blk(s) => Ok(s),
+            v => Err(type_error(format!("expected block, got {}", v.describe()))),
+        }
+    }
+
+    pub fn into_arr(self) -> GsResult<Vec<Gval>> {
+        match self {
+            Gval::Int(_) => Err(type_error(format!(
+                "expected a sequence, got {}",
+                self.describe()
+            ))),
+            Gval::Arr(a) => Ok(a),
+            Gval::Str(a) | Gval::Blk(a) => Ok(a.into_iter().map(|b| b.into()).collect()),
         }
     }
 }
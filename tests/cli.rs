@@ -0,0 +1,124 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Run the interpreter with `args`, feeding it `stdin`.
+fn gs(args: &[&str], stdin: &[u8]) -> Output {
+    feed(
+        Command::new(env!("CARGO_BIN_EXE_golfscript-rs")).args(args),
+        stdin,
+    )
+}
+
+fn feed(command: &mut Command, stdin: &[u8]) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn cross_stops_at_the_step_limit() {
+    let output = gs(&["-x", "--max-steps", "10000", "-e", "100000,.cross,"], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("step limit exceeded"));
+}
+
+#[test]
+fn unicode_string_operators_keep_characters_whole() {
+    for (code, want) in [
+        ("'héllo' ',' *", "h,é,l,l,o"),
+        ("'éa' 'èb' |", "éaèb"),
+        ("'éa' 'èa' &", "a"),
+        ("'éa' 'éb' ^", "ab"),
+        ("'éaè' 'è' -", "éa"),
+        ("['éa' 'èb']zip' '*", "éè ab"),
+    ] {
+        let output = gs(&["--unicode", "--utf8-output", "strict", "-e", code], b"");
+        assert!(output.status.success(), "{}: {}", code, stderr(&output));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!("{}\n", want),
+            "{}",
+            code
+        );
+    }
+}
+
+#[test]
+fn repl_commands_need_a_word_boundary() {
+    let dir = std::env::temp_dir().join(format!("gs-repl-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = feed(
+        Command::new(env!("CARGO_BIN_EXE_golfscript-rs"))
+            .arg("repl")
+            .current_dir(&dir),
+        b"1 2\n#saved for later\n#shown\n#replayed\n",
+    );
+    let saved: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(saved.is_empty(), "a comment saved the session");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "> [\"\" 1 2]\n".repeat(4) + "> \n"
+    );
+}
+
+#[test]
+fn elided_values_keep_characters_whole() {
+    let output = gs(&["repl"], format!("{{{}}}\n", "é".repeat(600)).as_bytes());
+    let stdout = String::from_utf8(output.stdout).expect("a character was cut in two");
+    let kept = "é".repeat(255);
+    assert!(stdout.contains(&format!("{{{}...(90 more)...{}}}", kept, kept)));
+}
+
+#[test]
+fn printf_padding_stops_at_the_step_limit() {
+    let output = gs(
+        &[
+            "-x",
+            "--max-steps",
+            "10000",
+            "-e",
+            r#""%999999999d"[1]\printf,"#,
+        ],
+        b"",
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("step limit exceeded"));
+}
+
+#[test]
+fn catch_restores_values_the_body_popped() {
+    let output = gs(&["-x", "-e", r#"1 2 {;; "e" throw} {} catch"#], b"");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(output.stdout, b"12e\n");
+}
+
+#[test]
+fn unknown_multibyte_symbols_warn_once_whole() {
+    let output = gs(&["-e", "1 é"], b"");
+    let warnings = stderr(&output);
+    assert_eq!(warnings.matches("warning:").count(), 1, "{}", warnings);
+    assert!(warnings.contains("`é` is not defined"), "{}", warnings);
+}
+
+#[test]
+fn output_separators_skip_the_empty_input() {
+    let output = gs(&["--output-separator", ",", "-e", "1 2 3"], b"");
+    assert_eq!(output.stdout, b"1,2,3\n");
+    let output = gs(&["--output-lines", "-e", "1 [2 3]"], b"");
+    assert_eq!(output.stdout, b"1\n23\n");
+    let output = gs(
+        &["--input-from-stdin", "--output-separator", ",", "-e", "1"],
+        b"in",
+    );
+    assert_eq!(output.stdout, b"in,1\n");
+}